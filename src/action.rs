@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
-use crate::components::views::wifi::ImplWiFi;
+use crate::{app::Mode, components::views::wifi::ImplWiFi};
 
 #[derive(Debug, Clone, PartialEq, Display, Serialize, Deserialize)]
 pub enum Action {
@@ -15,7 +15,20 @@ pub enum Action {
     Error(String),
     Help,
     BackToMenu,
+    /// Open the password view and keep it open until the password of `user` is changed.
+    ForcePasswordChange { user: String, reason: String },
+    /// Switch to the screen of another mode.
+    EnterMode(Mode),
     ScanComplete,
+    /// Connecting to the WiFi network `ssid` finished, or failed with `error`.
+    WifiConnected { ssid: String, error: Option<String> },
+    /// A known WiFi network was forgotten or changed, the message says how, or why it failed.
+    KnownNetworkChanged(Result<String, String>),
+    /// The setup wizard made the changes kept by its pages, or stopped at the one that failed
+    /// with `error`.
+    WizardApplied { error: Option<String> },
+    /// The SSH server status was read in the background, as the SSH view shows it.
+    SshStatus(String),
     #[serde(skip)]
     UpdateWifiState(ImplWiFi),
 }
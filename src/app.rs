@@ -1,4 +1,4 @@
-use std::{error::Error, sync::Arc};
+use std::{collections::HashMap, error::Error, sync::Arc};
 use color_eyre::Result;
 use crossterm::event::KeyEvent;
 use iwdrs::session::Session;
@@ -10,7 +10,7 @@ use tracing::{debug, info};
 pub type AppResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
 use crate::{
-    action::Action, components::{fps::FpsCounter, home::Home, Component}, config::Config, tui::{Event, Tui}
+    action::Action, components::{fps::FpsCounter, home::Home, wizard::Wizard, Component}, config::{get_data_dir, Config}, system::{authorized_keys, password::StalePassword, privilege::SudoSession}, tui::{Event, Tui}
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -37,9 +37,14 @@ pub struct App {
     config: Config,
     tick_rate: f64,
     frame_rate: f64,
+    /// The full-screen component of each mode; only the one of the current mode gets events and is drawn.
+    screens: HashMap<Mode, Box<dyn Component>>,
+    /// Drawn on top of the screen in every mode.
     components: Vec<Box<dyn Component>>,
     should_quit: bool,
     should_suspend: bool,
+    /// Quit once the mandatory password change or the wizard is done.
+    first_boot: bool,
     mode: Mode,
     last_tick_key_events: Vec<KeyEvent>,
     action_tx: mpsc::UnboundedSender<Action>,
@@ -50,19 +55,48 @@ pub struct App {
 pub enum Mode {
     #[default]
     Home,
+    /// The first-boot setup wizard.
+    Wizard,
 }
 
 impl App {
-    pub async fn new(tick_rate: f64, frame_rate: f64) -> Result<Self> {
+    pub async fn new(tick_rate: f64, frame_rate: f64, first_boot: bool, wizard: bool) -> Result<Self> {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
+        let config = Config::new()?;
+
+        // Nothing else should be configured while everyone can log in with the image's password.
+        let user = authorized_keys::target_user();
+        let stale = StalePassword::detect_unless_checked(&get_data_dir(), &user, &config.password_policy, first_boot);
+        let reason = match stale {
+            Some(stale) => Some(stale.reason(&user)),
+            None if first_boot => Some(format!("Choose a password for {user} to finish setting up the board")),
+            None => None,
+        };
+        // One sudo password for all views, never pkexec, whose terminal agent would garble the screen.
+        let session = SudoSession::default();
+        let mut screens: HashMap<Mode, Box<dyn Component>> = HashMap::new();
+        screens.insert(Mode::Home, Box::new(Home::new(action_tx.clone(), session.clone()).await));
+        let mode = if wizard {
+            // The wizard starts with the password and does not let a stale one through.
+            screens.insert(Mode::Wizard, Box::new(Wizard::new(action_tx.clone(), session, user, reason).await));
+            Mode::Wizard
+        } else {
+            if let Some(reason) = reason {
+                action_tx.send(Action::ForcePasswordChange { user, reason })?;
+            }
+            Mode::Home
+        };
+
         Ok(Self {
             tick_rate,
             frame_rate,
-            components: vec![Box::new(Home::new(action_tx.clone()).await), Box::new(FpsCounter::default())],
+            screens,
+            components: vec![Box::new(FpsCounter::default())],
             should_quit: false,
             should_suspend: false,
-            config: Config::new()?,
-            mode: Mode::Home,
+            first_boot,
+            config,
+            mode,
             last_tick_key_events: Vec::new(),
             action_tx,
             action_rx,
@@ -72,17 +106,18 @@ impl App {
     pub async fn run(&mut self) -> Result<()> {
         let mut tui = Tui::new()?
             // .mouse(true) // uncomment this line to enable mouse support
+            .paste(true)
             .tick_rate(self.tick_rate)
             .frame_rate(self.frame_rate);
         tui.enter()?;
 
-        for component in self.components.iter_mut() {
+        for component in self.screens.values_mut().chain(self.components.iter_mut()) {
             component.register_action_handler(self.action_tx.clone())?;
         }
-        for component in self.components.iter_mut() {
+        for component in self.screens.values_mut().chain(self.components.iter_mut()) {
             component.register_config_handler(self.config.clone())?;
         }
-        for component in self.components.iter_mut() {
+        for component in self.screens.values_mut().chain(self.components.iter_mut()) {
             component.init(tui.size()?)?;
         }
 
@@ -118,7 +153,7 @@ impl App {
             Event::Key(key) => self.handle_key_event(key)?,
             _ => {}
         }
-        for component in self.components.iter_mut() {
+        for component in self.screens.get_mut(&self.mode).into_iter().chain(self.components.iter_mut()) {
             if let Some(action) = component.handle_events(Some(event.clone()))? {
                 action_tx.send(action)?;
             }
@@ -163,12 +198,20 @@ impl App {
                 Action::Quit => self.should_quit = true,
                 Action::Suspend => self.should_suspend = true,
                 Action::Resume => self.should_suspend = false,
+                // The password view only lets go once the password was changed.
+                Action::BackToMenu if self.first_boot => self.should_quit = true,
+                // So does the wizard, whose first page is the password.
+                Action::EnterMode(Mode::Home) if self.first_boot => self.should_quit = true,
+                Action::EnterMode(mode) => {
+                    self.mode = mode;
+                    tui.terminal.clear()?;
+                }
                 Action::ClearScreen => tui.terminal.clear()?,
                 Action::Resize(w, h) => self.handle_resize(tui, w, h)?,
                 Action::Render => self.render(tui)?,
                 _ => {}
             }
-            for component in self.components.iter_mut() {
+            for component in self.screens.get_mut(&self.mode).into_iter().chain(self.components.iter_mut()) {
                 if let Some(action) = component.update(action.clone())? {
                     self.action_tx.send(action)?
                 };
@@ -185,7 +228,7 @@ impl App {
 
     fn render(&mut self, tui: &mut Tui) -> Result<()> {
         tui.draw(|frame| {
-            for component in self.components.iter_mut() {
+            for component in self.screens.get_mut(&self.mode).into_iter().chain(self.components.iter_mut()) {
                 if let Err(err) = component.draw(frame, frame.area()) {
                     let _ = self
                         .action_tx
@@ -0,0 +1,4 @@
+pub mod description;
+pub mod eeprom;
+pub mod identity;
+pub mod pinmux;
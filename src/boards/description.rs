@@ -0,0 +1,224 @@
+use std::fs;
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::get_config_dir;
+
+/// Board used when nothing better is known.
+pub const DEFAULT_BOARD: &str = "beagley-ai";
+
+const BUILTIN_BOARDS: &[(&str, &str)] = &[
+    ("beagley-ai", include_str!("../../.config/boards/beagley-ai.json5")),
+    ("beagleplay", include_str!("../../.config/boards/beagleplay.json5")),
+    ("beaglebone-black", include_str!("../../.config/boards/beaglebone-black.json5")),
+    ("pocketbeagle2", include_str!("../../.config/boards/pocketbeagle2.json5")),
+];
+
+/// Header layout of a single board, as read from a `boards/<id>.json5` file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BoardDescription {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub pinmux: Option<PinmuxTarget>,
+    pub headers: Vec<Header>,
+}
+
+/// Where pinmux overlays for a board are applied.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PinmuxTarget {
+    /// Label of the pin controller node, e.g. `am33xx_pinmux`.
+    pub target: String,
+    pub compatible: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Header {
+    pub name: String,
+    pub pins: Vec<PinInfo>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum PinType {
+    Power3v3,
+    Power5v,
+    Ground,
+    GPIO,
+    SPI,
+    I2C,
+    UART,
+    PCM,
+    PWM,
+    Special,
+}
+
+/// One alternate function a pin can be muxed to.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MuxMode {
+    pub mode: u8,
+    pub function: String,
+    #[serde(rename = "type")]
+    pub pin_type: PinType,
+}
+
+/// Explicit GPIO line for a pin, for boards whose device tree does not name its lines.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GpioLine {
+    /// Chip name (`gpiochip1`) or label (`600000.gpio`).
+    pub chip: String,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PinInfo {
+    pub number: u16,
+    pub name: String,
+    #[serde(default)]
+    pub function: String,
+    #[serde(rename = "type")]
+    pub pin_type: PinType,
+    /// Offset of the pad control register inside the board's pin controller.
+    #[serde(default)]
+    pub pad: Option<u16>,
+    #[serde(default)]
+    pub modes: Vec<MuxMode>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub gpio: Option<GpioLine>,
+}
+
+impl PinInfo {
+    /// The function the pin has before any pinmux change.
+    pub fn default_function(&self) -> &str {
+        if self.function.is_empty() {
+            &self.name
+        } else {
+            &self.function
+        }
+    }
+}
+
+impl BoardDescription {
+    /// Load the description for `id`, preferring `<config dir>/boards/<id>.json5`
+    /// over the copy built into the binary.
+    pub fn load(id: &str) -> Result<Self> {
+        let user_file = get_config_dir().join("boards").join(format!("{id}.json5"));
+        if user_file.exists() {
+            let content = fs::read_to_string(&user_file)?;
+            return Self::parse(&content)
+                .map_err(|e| eyre!("Invalid board file {}: {e}", user_file.display()));
+        }
+
+        Self::builtin(id)
+    }
+
+    /// Load the description built into the binary, ignoring user overrides.
+    pub fn builtin(id: &str) -> Result<Self> {
+        let content = builtin_source(id).ok_or_else(|| {
+            let known = Self::builtin_ids().collect::<Vec<_>>().join(", ");
+            eyre!("Unknown board `{id}`, expected one of: {known}")
+        })?;
+        Self::parse(content)
+    }
+
+    /// Parse a board file. The views index into its headers, so it must have at least one and
+    /// each must list pins.
+    pub fn parse(content: &str) -> Result<Self> {
+        let board: Self = json5::from_str(content)?;
+        if board.headers.is_empty() {
+            return Err(eyre!("Board `{}` has no headers", board.id));
+        }
+        if let Some(header) = board.headers.iter().find(|h| h.pins.is_empty()) {
+            return Err(eyre!("Header `{}` of board `{}` has no pins", header.name, board.id));
+        }
+        Ok(board)
+    }
+
+    /// Ids of every board description shipped with the binary.
+    pub fn builtin_ids() -> impl Iterator<Item = &'static str> {
+        BUILTIN_BOARDS.iter().map(|(id, _)| *id)
+    }
+}
+
+fn builtin_source(id: &str) -> Option<&'static str> {
+    BUILTIN_BOARDS
+        .iter()
+        .find(|(builtin_id, _)| *builtin_id == id)
+        .map(|(_, content)| *content)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_builtin_boards_parse() {
+        for id in BoardDescription::builtin_ids() {
+            let board = BoardDescription::builtin(id).unwrap();
+            assert_eq!(board.id, id);
+            assert!(!board.headers.is_empty(), "{id} has no headers");
+        }
+    }
+
+    #[test]
+    fn test_builtin_pin_numbers_unique() {
+        for id in BoardDescription::builtin_ids() {
+            let board = BoardDescription::builtin(id).unwrap();
+            for header in &board.headers {
+                let mut seen = HashSet::new();
+                for pin in &header.pins {
+                    assert!(
+                        seen.insert(pin.number),
+                        "{id} {} pin {} listed twice",
+                        header.name,
+                        pin.number
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_empty_headers() {
+        let error = BoardDescription::parse(r#"{ "id": "test", "name": "Test", "headers": [] }"#).unwrap_err();
+        assert_eq!(error.to_string(), "Board `test` has no headers");
+        let error =
+            BoardDescription::parse(r#"{ "id": "test", "name": "Test", "headers": [{ "name": "P1", "pins": [] }] }"#)
+                .unwrap_err();
+        assert_eq!(error.to_string(), "Header `P1` of board `test` has no pins");
+    }
+
+    #[test]
+    fn test_parse_mux_modes() {
+        let board = BoardDescription::parse(
+            r#"{
+                "id": "test",
+                "name": "Test",
+                "headers": [{
+                    "name": "P1",
+                    "pins": [
+                        { "number": 1, "name": "GPIO 1", "type": "GPIO",
+                          "modes": [{ "mode": 2, "function": "I2C1 SDA", "type": "I2C" }] },
+                        { "number": 2, "name": "Ground", "type": "Ground",
+                          "gpio": { "chip": "gpiochip0", "line": 7 } },
+                    ],
+                }],
+            }"#,
+        )
+        .unwrap();
+        let header = &board.headers[0];
+        assert_eq!(header.pins[0].modes[0].pin_type, PinType::I2C);
+        assert_eq!(header.pins[1].function, "");
+        assert!(header.pins[1].note.is_none());
+        assert!(header.pins[0].gpio.is_none());
+        assert!(board.pinmux.is_none());
+        assert_eq!(header.pins[1].gpio.as_ref().unwrap().line, 7);
+    }
+}
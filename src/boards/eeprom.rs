@@ -0,0 +1,142 @@
+use serde::Serialize;
+
+/// First four bytes of every Beagle/TI board-ID EEPROM (0xEE3355AA, little endian).
+const MAGIC: [u8; 4] = [0xAA, 0x55, 0x33, 0xEE];
+
+const AM6_RECORD_BOARD_INFO: u8 = 0x10;
+const AM6_RECORD_END_LIST: u8 = 0xFE;
+
+/// Contents of the board-ID EEPROM.
+///
+/// AM335x boards (BeagleBone Black, ...) use the original fixed layout, newer K3 boards
+/// (BeaglePlay, BeagleY-AI, PocketBeagle 2) use TI's AM6 record list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Eeprom {
+    pub board_name: String,
+    pub version: String,
+    pub serial: String,
+}
+
+impl Eeprom {
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 5 || bytes[..4] != MAGIC {
+            return None;
+        }
+
+        if bytes[4].is_ascii_graphic() {
+            Self::parse_legacy(bytes)
+        } else {
+            Self::parse_am6(bytes)
+        }
+    }
+
+    // magic[4] name[8] version[4] serial[12] config[32]
+    fn parse_legacy(bytes: &[u8]) -> Option<Self> {
+        let field = |start: usize, len: usize| bytes.get(start..start + len).map(text);
+        Some(Eeprom {
+            board_name: field(4, 8)?,
+            version: field(12, 4)?,
+            serial: field(16, 12)?,
+        })
+    }
+
+    // magic[4] type[1] length[2], then records of id[1] len[2] data[len]
+    fn parse_am6(bytes: &[u8]) -> Option<Self> {
+        let mut offset = 7;
+        while offset + 3 <= bytes.len() {
+            let id = bytes[offset];
+            let len = u16::from_le_bytes([bytes[offset + 1], bytes[offset + 2]]) as usize;
+            let data = bytes.get(offset + 3..offset + 3 + len)?;
+
+            match id {
+                AM6_RECORD_BOARD_INFO => {
+                    // name[16] version[2] proc[4] variant[2] pcb[2] bom[2] sw[2]
+                    // vendor[2] week[2] year[2] board_id[6] serial[4]
+                    let field = |start: usize, len: usize| data.get(start..start + len).map(text);
+                    let serial = [field(32, 2)?, field(34, 2)?, field(36, 6)?, field(42, 4)?]
+                        .concat();
+                    return Some(Eeprom {
+                        board_name: field(0, 16)?,
+                        version: field(16, 2)?,
+                        serial,
+                    });
+                }
+                AM6_RECORD_END_LIST => return None,
+                _ => offset += 3 + len,
+            }
+        }
+        None
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|b| **b != 0 && **b != 0xFF)
+        .map(|b| *b as char)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    pub(crate) fn legacy_image(name: &str, version: &str, serial: &str) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(format!("{name:<8}").bytes());
+        bytes.extend(format!("{version:<4}").bytes());
+        bytes.extend(format!("{serial:<12}").bytes());
+        bytes.extend([0xFF; 32]);
+        bytes
+    }
+
+    pub(crate) fn am6_image(name: &str, serial: &str) -> Vec<u8> {
+        let mut info = format!("{name:\0<16}").into_bytes();
+        info.extend(b"A0");
+        info.extend(b"0000"); // proc
+        info.extend(b"00"); // variant
+        info.extend(b"A0"); // pcb
+        info.extend(b"00"); // bom
+        info.extend(b"00"); // sw
+        info.extend(b"01"); // vendor
+        info.extend(b"12"); // week
+        info.extend(b"23"); // year
+        info.extend(b"PLAY00"); // board id
+        info.extend(serial.bytes());
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(0x01);
+        bytes.extend(((info.len() + 3) as u16).to_le_bytes());
+        bytes.push(AM6_RECORD_BOARD_INFO);
+        bytes.extend((info.len() as u16).to_le_bytes());
+        bytes.extend(info);
+        bytes.push(AM6_RECORD_END_LIST);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_legacy() {
+        let eeprom = Eeprom::parse(&legacy_image("A335BNLT", "00C0", "4018BBBK1234")).unwrap();
+        assert_eq!(eeprom.board_name, "A335BNLT");
+        assert_eq!(eeprom.version, "00C0");
+        assert_eq!(eeprom.serial, "4018BBBK1234");
+    }
+
+    #[test]
+    fn test_parse_am6() {
+        let eeprom = Eeprom::parse(&am6_image("BEAGLEPLAY-A0-", "0042")).unwrap();
+        assert_eq!(eeprom.board_name, "BEAGLEPLAY-A0-");
+        assert_eq!(eeprom.version, "A0");
+        assert_eq!(eeprom.serial, "1223PLAY000042");
+    }
+
+    #[test]
+    fn test_parse_rejects_blank() {
+        assert_eq!(Eeprom::parse(&[0xFF; 64]), None);
+        assert_eq!(Eeprom::parse(&[]), None);
+    }
+}
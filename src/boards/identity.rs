@@ -0,0 +1,241 @@
+use std::{fs, path::Path};
+
+use color_eyre::Result;
+use serde::Serialize;
+use tracing::{error, info};
+
+use super::{
+    description::{BoardDescription, DEFAULT_BOARD},
+    eeprom::Eeprom,
+};
+
+/// Locations of the board-ID EEPROM, relative to the filesystem root.
+const EEPROM_PATHS: &[&str] = &[
+    "sys/bus/i2c/devices/0-0050/eeprom",
+    "sys/bus/nvmem/devices/0-00500/nvmem",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BoardKind {
+    BeagleYAi,
+    BeaglePlay,
+    BeagleBoneBlack,
+    PocketBeagle2,
+    Unknown,
+}
+
+impl BoardKind {
+    fn from_compatible(compatible: &str) -> Option<Self> {
+        match compatible {
+            "beagle,am67a-beagley-ai" => Some(BoardKind::BeagleYAi),
+            "beagle,am625-beagleplay" => Some(BoardKind::BeaglePlay),
+            "ti,am335x-bone-black" | "ti,am335x-bone-green" => Some(BoardKind::BeagleBoneBlack),
+            c if c.contains("pocketbeagle2") => Some(BoardKind::PocketBeagle2),
+            _ => None,
+        }
+    }
+
+    fn from_eeprom_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_uppercase();
+        if name.starts_with("BEAGLEY") {
+            Some(BoardKind::BeagleYAi)
+        } else if name.starts_with("BEAGLEPLAY") {
+            Some(BoardKind::BeaglePlay)
+        } else if name.starts_with("POCKETBEAGLE2") {
+            Some(BoardKind::PocketBeagle2)
+        } else if name == "A335BNLT" {
+            Some(BoardKind::BeagleBoneBlack)
+        } else {
+            None
+        }
+    }
+
+    fn from_model(model: &str) -> Option<Self> {
+        let model = model.to_ascii_lowercase();
+        if model.contains("beagley-ai") {
+            Some(BoardKind::BeagleYAi)
+        } else if model.contains("beagleplay") {
+            Some(BoardKind::BeaglePlay)
+        } else if model.contains("pocketbeagle2") || model.contains("pocketbeagle 2") {
+            Some(BoardKind::PocketBeagle2)
+        } else if model.contains("beaglebone black") || model.contains("beaglebone green") {
+            Some(BoardKind::BeagleBoneBlack)
+        } else {
+            None
+        }
+    }
+
+    /// Id of the matching built-in board description.
+    pub fn description_id(&self) -> &'static str {
+        match self {
+            BoardKind::BeagleYAi => "beagley-ai",
+            BoardKind::BeaglePlay => "beagleplay",
+            BoardKind::BeagleBoneBlack => "beaglebone-black",
+            BoardKind::PocketBeagle2 => "pocketbeagle2",
+            BoardKind::Unknown => DEFAULT_BOARD,
+        }
+    }
+
+    pub fn has_wifi(&self) -> bool {
+        !matches!(self, BoardKind::BeagleBoneBlack | BoardKind::PocketBeagle2)
+    }
+}
+
+/// Identity of the board we are running on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Board {
+    pub kind: BoardKind,
+    pub model: Option<String>,
+    pub compatible: Vec<String>,
+    pub eeprom: Option<Eeprom>,
+}
+
+impl Board {
+    pub fn detect() -> Self {
+        Self::detect_from(Path::new("/"))
+    }
+
+    /// Detect the board using `root` in place of `/`, so a fake procfs/sysfs tree can be used.
+    pub fn detect_from(root: &Path) -> Self {
+        let dt = root.join("proc/device-tree");
+        let model = fs::read(dt.join("model"))
+            .ok()
+            .and_then(|bytes| split_nul(&bytes).into_iter().next());
+        let compatible = fs::read(dt.join("compatible"))
+            .map(|bytes| split_nul(&bytes))
+            .unwrap_or_default();
+        let eeprom = EEPROM_PATHS
+            .iter()
+            .map(|path| root.join(path))
+            .find_map(|path| read_eeprom(&path));
+
+        let kind = compatible
+            .iter()
+            .find_map(|c| BoardKind::from_compatible(c))
+            .or_else(|| {
+                eeprom
+                    .as_ref()
+                    .and_then(|e| BoardKind::from_eeprom_name(&e.board_name))
+            })
+            .or_else(|| model.as_deref().and_then(BoardKind::from_model))
+            .unwrap_or(BoardKind::Unknown);
+
+        info!("Detected board {kind:?} (model: {model:?})");
+
+        Board {
+            kind,
+            model,
+            compatible,
+            eeprom,
+        }
+    }
+
+    /// Human readable board name.
+    pub fn name(&self) -> String {
+        match (&self.model, self.kind) {
+            (Some(model), _) => model.clone(),
+            (None, BoardKind::Unknown) => String::from("Unknown board"),
+            (None, kind) => format!("{kind:?}"),
+        }
+    }
+
+    /// Header description for this board, from the user's board file when there is one. Boards
+    /// of unknown kind get the description of the default board.
+    pub fn description(&self) -> Result<BoardDescription> {
+        BoardDescription::load(self.kind.description_id())
+    }
+}
+
+fn read_eeprom(path: &Path) -> Option<Eeprom> {
+    match fs::read(path) {
+        Ok(bytes) => Eeprom::parse(&bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            error!("Unable to read {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn split_nul(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::boards::eeprom::tests::{am6_image, legacy_image};
+
+    fn fake_root(model: Option<&str>, compatible: &[&str], eeprom: Option<Vec<u8>>) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let dt = root.path().join("proc/device-tree");
+        fs::create_dir_all(&dt).unwrap();
+        if let Some(model) = model {
+            fs::write(dt.join("model"), format!("{model}\0")).unwrap();
+        }
+        if !compatible.is_empty() {
+            fs::write(dt.join("compatible"), compatible.join("\0") + "\0").unwrap();
+        }
+        if let Some(eeprom) = eeprom {
+            let path = root.path().join(EEPROM_PATHS[0]);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, eeprom).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_detect_from_compatible() {
+        let root = fake_root(
+            Some("BeagleBoard.org BeaglePlay"),
+            &["beagle,am625-beagleplay", "ti,am625"],
+            None,
+        );
+        let board = Board::detect_from(root.path());
+        assert_eq!(board.kind, BoardKind::BeaglePlay);
+        assert_eq!(board.name(), "BeagleBoard.org BeaglePlay");
+        assert_eq!(board.compatible.len(), 2);
+    }
+
+    #[test]
+    fn test_detect_from_eeprom() {
+        let root = fake_root(
+            None,
+            &["ti,am33xx"],
+            Some(legacy_image("A335BNLT", "00C0", "4018BBBK1234")),
+        );
+        let board = Board::detect_from(root.path());
+        assert_eq!(board.kind, BoardKind::BeagleBoneBlack);
+        assert_eq!(board.eeprom.unwrap().serial, "4018BBBK1234");
+    }
+
+    #[test]
+    fn test_detect_compatible_wins_over_eeprom() {
+        let root = fake_root(
+            None,
+            &["beagle,am67a-beagley-ai"],
+            Some(am6_image("BEAGLEPLAY-A0-", "0001")),
+        );
+        assert_eq!(Board::detect_from(root.path()).kind, BoardKind::BeagleYAi);
+    }
+
+    #[test]
+    fn test_detect_from_model() {
+        let root = fake_root(Some("TI AM335x BeagleBone Black"), &[], None);
+        assert_eq!(Board::detect_from(root.path()).kind, BoardKind::BeagleBoneBlack);
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        let root = tempfile::tempdir().unwrap();
+        let board = Board::detect_from(root.path());
+        assert_eq!(board.kind, BoardKind::Unknown);
+        assert_eq!(board.kind.description_id(), DEFAULT_BOARD);
+    }
+}
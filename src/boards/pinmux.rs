@@ -0,0 +1,450 @@
+//! Staging of pin function changes and the device tree overlay that applies them.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use super::description::{BoardDescription, MuxMode, PinInfo, PinType};
+use crate::config::get_data_dir;
+
+/// File name, without extension, of the generated overlay.
+pub const OVERLAY_NAME: &str = "beagle-config-pinmux";
+
+/// Pad configuration bits shared by the TI pin controllers: receiver enabled, pull up selected.
+const PAD_INPUT_ENABLE: u16 = 0x20;
+const PAD_PULL_UP: u16 = 0x10;
+/// Mux mode bits of a pad configuration value.
+const PAD_MODE_MASK: u16 = 0x07;
+
+/// Directory the generated overlay is written to.
+pub fn staging_dir() -> PathBuf {
+    get_data_dir().join("overlays")
+}
+
+/// Name of a header pin as used in overlays and provisioning files, e.g. `P9_14`.
+pub fn pin_name(header: &str, pin: u16) -> String {
+    format!("{header}_{pin:02}")
+}
+
+/// A pin moved to a function other than its default one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub header: String,
+    pub pin: u16,
+    pub pad: u16,
+    pub mode: MuxMode,
+}
+
+impl Assignment {
+    /// Peripheral the function belongs to (`uart4` for `UART4 RXD`), `None` for plain GPIO.
+    fn peripheral(&self) -> Option<String> {
+        (self.mode.pin_type != PinType::GPIO)
+            .then(|| self.mode.function.split_whitespace().next())
+            .flatten()
+            .map(|p| p.to_ascii_lowercase())
+    }
+
+    fn pad_value(&self) -> u16 {
+        let pull = if self.mode.pin_type == PinType::I2C { PAD_PULL_UP } else { 0 };
+        PAD_INPUT_ENABLE | pull | self.mode.mode as u16
+    }
+}
+
+/// Overlay files written by [`Pinmux::write_overlay`].
+#[derive(Debug)]
+pub struct StagedOverlay {
+    pub source: PathBuf,
+    /// Compiled overlay, missing when `dtc` is not installed.
+    pub blob: Option<PathBuf>,
+}
+
+/// Pin function changes that have been picked but not applied yet.
+#[derive(Debug, Default)]
+pub struct Pinmux {
+    assignments: Vec<Assignment>,
+}
+
+impl Pinmux {
+    /// Stage the `functions` of pins named like `P9_14`, e.g. from a provisioning file.
+    pub fn from_functions(board: &BoardDescription, functions: &BTreeMap<String, String>) -> Result<Self> {
+        let mut pinmux = Pinmux::default();
+        for (name, function) in functions {
+            let (header, pin) = find_pin(board, name)
+                .ok_or_else(|| eyre!("{} has no pin named {name}", board.name))?;
+            let mode = pin
+                .modes
+                .iter()
+                .find(|m| m.function.eq_ignore_ascii_case(function))
+                .ok_or_else(|| eyre!("{name} cannot be used as {function}"))?;
+            pinmux.stage(board, header, pin, mode)?;
+        }
+        Ok(pinmux)
+    }
+
+    /// Recover the assignments of an overlay written by [`Pinmux::write_overlay`] into `dir`,
+    /// `None` when there is none.
+    pub fn load(board: &BoardDescription, dir: &Path) -> Result<Option<Self>> {
+        let source = match fs::read_to_string(dir.join(format!("{OVERLAY_NAME}.dts"))) {
+            Ok(source) => source,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut pinmux = Pinmux::default();
+        // Pin lines look like `0x09c 0x27\t/* P9_14 EHRPWM1 A, mode 6 */`.
+        for line in source.lines().map(str::trim).filter(|l| l.starts_with("0x")) {
+            let mut words = line.split_whitespace();
+            let (Some(pad), Some(value)) = (words.next(), words.next()) else { continue };
+            let parse = |hex: &str| u16::from_str_radix(hex.trim_start_matches("0x"), 16);
+            let (pad, mode) = (parse(pad)?, parse(value)? & PAD_MODE_MASK);
+
+            let (header, pin) = board
+                .headers
+                .iter()
+                .find_map(|h| h.pins.iter().find(|p| p.pad == Some(pad)).map(|p| (h.name.as_str(), p)))
+                .ok_or_else(|| eyre!("No pin of {} has pad 0x{pad:03x}", board.name))?;
+            let mode = pin
+                .modes
+                .iter()
+                .find(|m| u16::from(m.mode) == mode)
+                .ok_or_else(|| eyre!("{} has no mode {mode}", pin_name(header, pin.number)))?;
+            pinmux.stage(board, header, pin, mode)?;
+        }
+        Ok(Some(pinmux))
+    }
+
+    #[cfg(test)]
+    pub fn assignments(&self) -> &[Assignment] {
+        &self.assignments
+    }
+
+    /// Function of every pin with a staged change, keyed by pin name.
+    pub fn functions(&self) -> BTreeMap<String, String> {
+        self.assignments
+            .iter()
+            .map(|a| (pin_name(&a.header, a.pin), a.mode.function.clone()))
+            .collect()
+    }
+
+    /// [`Pinmux::functions`] plus the current function of every pin in `names` that has no
+    /// staged change, so a file asking for a pin's default function compares equal.
+    pub fn functions_including<'a>(
+        &self,
+        board: &BoardDescription,
+        names: impl IntoIterator<Item = &'a String>,
+    ) -> BTreeMap<String, String> {
+        let mut functions = self.functions();
+        for name in names {
+            if let Some((header, pin)) = find_pin(board, name) {
+                let function = self.function_of(header, pin).to_string();
+                functions.entry(name.clone()).or_insert(function);
+            }
+        }
+        functions
+    }
+
+    pub fn staged(&self, header: &str, pin: u16) -> Option<&Assignment> {
+        self.assignments
+            .iter()
+            .find(|a| a.header == header && a.pin == pin)
+    }
+
+    /// Function of the pin once the staged changes are applied.
+    pub fn function_of<'a>(&'a self, header: &str, pin: &'a PinInfo) -> &'a str {
+        self.staged(header, pin.number)
+            .map_or(pin.default_function(), |a| a.mode.function.as_str())
+    }
+
+    /// Stage `mode` for `pin`, refusing it when another pin already carries the same signal.
+    /// Picking the pin's default function drops any staged change instead.
+    pub fn stage(
+        &mut self,
+        board: &BoardDescription,
+        header: &str,
+        pin: &PinInfo,
+        mode: &MuxMode,
+    ) -> Result<()> {
+        if board.pinmux.is_none() {
+            return Err(eyre!("Pin muxing is not supported on {}", board.name));
+        }
+        let pad = pin
+            .pad
+            .ok_or_else(|| eyre!("{header}_{:02} has no pad offset in the board description", pin.number))?;
+
+        if mode.pin_type != PinType::GPIO {
+            for other_header in &board.headers {
+                for other in &other_header.pins {
+                    if other_header.name == header && other.number == pin.number {
+                        continue;
+                    }
+                    if self.function_of(&other_header.name, other) == mode.function {
+                        return Err(eyre!(
+                            "{} is already assigned to {}_{:02}",
+                            mode.function,
+                            other_header.name,
+                            other.number
+                        ));
+                    }
+                }
+            }
+        }
+
+        self.assignments
+            .retain(|a| !(a.header == header && a.pin == pin.number));
+        if mode.function != pin.default_function() {
+            self.assignments.push(Assignment {
+                header: header.to_string(),
+                pin: pin.number,
+                pad,
+                mode: mode.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Device tree overlay source applying the staged changes. Pins muxed to a peripheral are
+    /// attached to that peripheral's node, which is enabled; GPIO pins are hogged by the pin
+    /// controller itself.
+    pub fn overlay_source(&self, board: &BoardDescription) -> Result<String> {
+        let pinmux = board
+            .pinmux
+            .as_ref()
+            .ok_or_else(|| eyre!("Pin muxing is not supported on {}", board.name))?;
+        if self.assignments.is_empty() {
+            return Err(eyre!("No pin changes staged"));
+        }
+
+        let mut groups: BTreeMap<String, Vec<&Assignment>> = BTreeMap::new();
+        for assignment in &self.assignments {
+            let group = assignment.peripheral().unwrap_or_else(|| String::from("gpio"));
+            groups.entry(group).or_default().push(assignment);
+        }
+
+        let mut dts = String::new();
+        writeln!(dts, "// Generated by beagle-config, changes will be overwritten.")?;
+        writeln!(dts, "/dts-v1/;\n/plugin/;\n")?;
+        writeln!(dts, "/ {{\n\tcompatible = \"{}\";\n}};\n", pinmux.compatible)?;
+
+        writeln!(dts, "&{} {{", pinmux.target)?;
+        if groups.contains_key("gpio") {
+            writeln!(dts, "\tpinctrl-names = \"default\";")?;
+            writeln!(dts, "\tpinctrl-0 = <&beagle_config_gpio_pins>;\n")?;
+        }
+        for (group, assignments) in &groups {
+            writeln!(dts, "\tbeagle_config_{group}_pins: beagle-config-{group}-pins {{")?;
+            writeln!(dts, "\t\tpinctrl-single,pins = <")?;
+            for a in assignments {
+                writeln!(
+                    dts,
+                    "\t\t\t0x{:03x} 0x{:02x}\t/* {}_{:02} {}, mode {} */",
+                    a.pad,
+                    a.pad_value(),
+                    a.header,
+                    a.pin,
+                    a.mode.function,
+                    a.mode.mode
+                )?;
+            }
+            writeln!(dts, "\t\t>;\n\t}};")?;
+        }
+        writeln!(dts, "}};")?;
+
+        for group in groups.keys().filter(|g| *g != "gpio") {
+            writeln!(dts, "\n&{group} {{")?;
+            writeln!(dts, "\tpinctrl-names = \"default\";")?;
+            writeln!(dts, "\tpinctrl-0 = <&beagle_config_{group}_pins>;")?;
+            writeln!(dts, "\tstatus = \"okay\";")?;
+            writeln!(dts, "}};")?;
+        }
+
+        Ok(dts)
+    }
+
+    /// Write the overlay source into `dir` and compile it when `dtc` is available.
+    pub fn write_overlay(&self, board: &BoardDescription, dir: &Path) -> Result<StagedOverlay> {
+        self.write_overlay_with(board, dir, "dtc")
+    }
+
+    /// [`Pinmux::write_overlay`] with another device tree compiler.
+    pub fn write_overlay_with(&self, board: &BoardDescription, dir: &Path, dtc: &str) -> Result<StagedOverlay> {
+        let content = self.overlay_source(board)?;
+        fs::create_dir_all(dir)?;
+        let source = dir.join(format!("{OVERLAY_NAME}.dts"));
+        let blob = dir.join(format!("{OVERLAY_NAME}.dtbo"));
+        fs::write(&source, content)?;
+
+        let output = Command::new(dtc)
+            .args(["-@", "-q", "-I", "dts", "-O", "dtb", "-o"])
+            .arg(&blob)
+            .arg(&source)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => Ok(StagedOverlay {
+                source,
+                blob: Some(blob),
+            }),
+            Ok(output) => Err(eyre!(
+                "dtc failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(StagedOverlay { source, blob: None }),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn find_pin<'a>(board: &'a BoardDescription, name: &str) -> Option<(&'a str, &'a PinInfo)> {
+    let (header, number) = name.rsplit_once('_')?;
+    let number: u16 = number.parse().ok()?;
+    board
+        .headers
+        .iter()
+        .filter(|h| h.name.eq_ignore_ascii_case(header))
+        .find_map(|h| h.pins.iter().find(|p| p.number == number).map(|p| (h.name.as_str(), p)))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn bbb() -> BoardDescription {
+        BoardDescription::builtin("beaglebone-black").unwrap()
+    }
+
+    fn pin<'a>(board: &'a BoardDescription, header: &str, number: u16) -> &'a PinInfo {
+        board
+            .headers
+            .iter()
+            .find(|h| h.name == header)
+            .and_then(|h| h.pins.iter().find(|p| p.number == number))
+            .unwrap()
+    }
+
+    fn mode<'a>(pin: &'a PinInfo, function: &str) -> &'a MuxMode {
+        pin.modes.iter().find(|m| m.function == function).unwrap()
+    }
+
+    #[test]
+    fn test_stage_and_revert() {
+        let board = bbb();
+        let mut pinmux = Pinmux::default();
+        let p9_11 = pin(&board, "P9", 11);
+
+        pinmux.stage(&board, "P9", p9_11, mode(p9_11, "UART4 RXD")).unwrap();
+        assert_eq!(pinmux.function_of("P9", p9_11), "UART4 RXD");
+        assert_eq!(pinmux.assignments()[0].pad, 0x070);
+
+        pinmux.stage(&board, "P9", p9_11, mode(p9_11, "GPIO0_30")).unwrap();
+        assert!(pinmux.assignments().is_empty());
+    }
+
+    #[test]
+    fn test_conflict_with_default_function() {
+        let board = bbb();
+        let mut pinmux = Pinmux::default();
+        let p9_24 = pin(&board, "P9", 24);
+
+        // P9_17 carries I2C1 SCL out of the box.
+        let err = pinmux.stage(&board, "P9", p9_24, mode(p9_24, "I2C1 SCL")).unwrap_err();
+        assert_eq!(err.to_string(), "I2C1 SCL is already assigned to P9_17");
+
+        let p9_17 = pin(&board, "P9", 17);
+        pinmux.stage(&board, "P9", p9_17, mode(p9_17, "GPIO0_5")).unwrap();
+        pinmux.stage(&board, "P9", p9_24, mode(p9_24, "I2C1 SCL")).unwrap();
+    }
+
+    #[test]
+    fn test_conflict_between_staged_pins() {
+        let board = bbb();
+        let mut pinmux = Pinmux::default();
+        let p9_21 = pin(&board, "P9", 21);
+        let p9_29 = pin(&board, "P9", 29);
+
+        pinmux.stage(&board, "P9", p9_21, mode(p9_21, "EHRPWM0 B")).unwrap();
+        assert!(pinmux.stage(&board, "P9", p9_29, mode(p9_29, "EHRPWM0 B")).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_board() {
+        let board = BoardDescription::builtin("beagley-ai").unwrap();
+        let header = &board.headers[0];
+        let pin = &header.pins[0];
+        let mode = MuxMode {
+            mode: 0,
+            function: String::from("SPI0 CS0"),
+            pin_type: PinType::SPI,
+        };
+        assert!(Pinmux::default().stage(&board, &header.name, pin, &mode).is_err());
+    }
+
+    #[test]
+    fn test_overlay_source() {
+        let board = bbb();
+        let mut pinmux = Pinmux::default();
+        let p9_11 = pin(&board, "P9", 11);
+        let p9_12 = pin(&board, "P9", 12);
+        let p9_13 = pin(&board, "P9", 13);
+        pinmux.stage(&board, "P9", p9_11, mode(p9_11, "UART4 RXD")).unwrap();
+        pinmux.stage(&board, "P9", p9_13, mode(p9_13, "UART4 TXD")).unwrap();
+        // Not a change, so it stays out of the overlay.
+        pinmux.stage(&board, "P9", p9_12, mode(p9_12, "GPIO1_28")).unwrap();
+
+        let dts = pinmux.overlay_source(&board).unwrap();
+        assert!(dts.contains("compatible = \"ti,beaglebone-black\";"));
+        assert!(dts.contains("&am33xx_pinmux {"));
+        assert!(dts.contains("0x070 0x26\t/* P9_11 UART4 RXD, mode 6 */"));
+        assert!(dts.contains("0x074 0x26\t/* P9_13 UART4 TXD, mode 6 */"));
+        assert!(dts.contains("&uart4 {"));
+        assert!(!dts.contains("gpio_pins"));
+    }
+
+    #[test]
+    fn test_write_overlay() {
+        let board = bbb();
+        let mut pinmux = Pinmux::default();
+        let dir = tempfile::tempdir().unwrap();
+        assert!(pinmux.write_overlay(&board, dir.path()).is_err());
+
+        let p9_17 = pin(&board, "P9", 17);
+        pinmux.stage(&board, "P9", p9_17, mode(p9_17, "GPIO0_5")).unwrap();
+        let staged = pinmux.write_overlay(&board, dir.path()).unwrap();
+        let dts = fs::read_to_string(staged.source).unwrap();
+        assert!(dts.contains("pinctrl-0 = <&beagle_config_gpio_pins>;"));
+    }
+
+    #[test]
+    fn test_load_written_overlay() {
+        let board = bbb();
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Pinmux::load(&board, dir.path()).unwrap().is_none());
+
+        let functions = BTreeMap::from([
+            (String::from("P9_14"), String::from("EHRPWM1 A")),
+            (String::from("P9_17"), String::from("GPIO0_5")),
+        ]);
+        let pinmux = Pinmux::from_functions(&board, &functions).unwrap();
+        pinmux.write_overlay(&board, dir.path()).unwrap();
+
+        let loaded = Pinmux::load(&board, dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.functions(), functions);
+
+        // P9_12 keeps its default function, which the overlay leaves out.
+        let mut wanted = functions.clone();
+        wanted.insert(String::from("P9_12"), String::from("GPIO1_28"));
+        let pinmux = Pinmux::from_functions(&board, &wanted).unwrap();
+        assert_eq!(pinmux.functions(), functions);
+        assert_eq!(loaded.functions_including(&board, wanted.keys()), wanted);
+
+        let unknown = BTreeMap::from([(String::from("P9_14"), String::from("UART9 TXD"))]);
+        assert!(Pinmux::from_functions(&board, &unknown).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Line based diff used to preview boot configuration changes.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Diff `old` against `new` line by line, using the longest common subsequence.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] is the length of the common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| DiffLine::Removed(l.to_string())));
+    lines.extend(new[j..].iter().map(|l| DiffLine::Added(l.to_string())));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc\n", "a\nx\nc\nd\n");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("a".into()),
+                DiffLine::Removed("b".into()),
+                DiffLine::Added("x".into()),
+                DiffLine::Same("c".into()),
+                DiffLine::Added("d".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        assert!(diff_lines("a\nb", "a\nb")
+            .iter()
+            .all(|l| matches!(l, DiffLine::Same(_))));
+    }
+}
@@ -0,0 +1,509 @@
+//! Device tree overlays enabled through `extlinux.conf` (`fdtoverlays`) or `uEnv.txt`
+//! (`uboot_overlay_addrN`).
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Serialize;
+
+use super::diff::{diff_lines, DiffLine};
+use crate::system::{privilege::Privilege, run_privileged, secret::Secret, write_as_root};
+
+/// Boot configuration files, relative to the filesystem root, in order of preference.
+const BOOT_CONFIGS: &[(&str, BootConfigKind)] = &[
+    ("boot/firmware/extlinux/extlinux.conf", BootConfigKind::Extlinux),
+    ("boot/extlinux/extlinux.conf", BootConfigKind::Extlinux),
+    ("boot/firmware/uEnv.txt", BootConfigKind::UEnv),
+    ("boot/uEnv.txt", BootConfigKind::UEnv),
+];
+
+/// Directories searched for `.dtbo` files, relative to the filesystem root.
+const OVERLAY_DIRS: &[&str] = &["boot/firmware/overlays", "boot/overlays", "lib/firmware"];
+
+/// U-Boot only looks at `uboot_overlay_addr0` to `uboot_overlay_addr7`.
+const UBOOT_OVERLAY_SLOTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BootConfigKind {
+    Extlinux,
+    UEnv,
+}
+
+impl BootConfigKind {
+    fn default_prefix(&self) -> &'static str {
+        match self {
+            BootConfigKind::Extlinux => "/overlays/",
+            BootConfigKind::UEnv => "/lib/firmware/",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BootConfig {
+    pub path: PathBuf,
+    pub kind: BootConfigKind,
+    #[serde(skip)]
+    original: String,
+    /// Enabled overlays as written in the file, in the order they are applied.
+    pub overlays: Vec<String>,
+}
+
+impl BootConfig {
+    /// Find and read the boot configuration under `root`.
+    pub fn load(root: &Path) -> Result<Self> {
+        let (path, kind) = BOOT_CONFIGS
+            .iter()
+            .map(|(path, kind)| (root.join(path), *kind))
+            .find(|(path, _)| path.exists())
+            .ok_or_else(|| eyre!("No extlinux.conf or uEnv.txt found under {}", root.display()))?;
+        let content = fs::read_to_string(&path)?;
+        Ok(Self::parse(path, kind, content))
+    }
+
+    pub fn parse(path: PathBuf, kind: BootConfigKind, content: String) -> Self {
+        let overlays = match kind {
+            BootConfigKind::Extlinux => {
+                let lines: Vec<&str> = content.lines().collect();
+                let (start, end) = extlinux_section(&lines);
+                lines[start..end]
+                    .iter()
+                    .find_map(|line| extlinux_overlays(line))
+                    .unwrap_or_default()
+            }
+            BootConfigKind::UEnv => {
+                let mut slots: Vec<(usize, String)> =
+                    content.lines().filter_map(uenv_overlay).collect();
+                slots.sort_by_key(|(slot, _)| *slot);
+                slots.into_iter().map(|(_, path)| path).collect()
+            }
+        };
+
+        BootConfig {
+            path,
+            kind,
+            original: content,
+            overlays,
+        }
+    }
+
+    pub fn is_enabled(&self, file_name: &str) -> bool {
+        self.position(file_name).is_some()
+    }
+
+    pub fn position(&self, file_name: &str) -> Option<usize> {
+        self.overlays.iter().position(|o| entry_file_name(o) == file_name)
+    }
+
+    /// Enable `file_name` after the overlays already enabled.
+    pub fn enable(&mut self, file_name: &str) {
+        if !self.is_enabled(file_name) {
+            let prefix = self
+                .overlays
+                .first()
+                .and_then(|o| o.rfind('/').map(|i| o[..=i].to_string()))
+                .unwrap_or_else(|| self.kind.default_prefix().to_string());
+            self.overlays.push(format!("{prefix}{file_name}"));
+        }
+    }
+
+    pub fn disable(&mut self, file_name: &str) {
+        self.overlays.retain(|o| entry_file_name(o) != file_name);
+    }
+
+    /// Move an enabled overlay one place earlier (`up`) or later in the boot order.
+    pub fn reorder(&mut self, file_name: &str, up: bool) {
+        let Some(i) = self.position(file_name) else { return };
+        if up && i > 0 {
+            self.overlays.swap(i, i - 1);
+        } else if !up && i + 1 < self.overlays.len() {
+            self.overlays.swap(i, i + 1);
+        }
+    }
+
+    /// The file content with the current overlay list written into it.
+    pub fn render(&self) -> Result<String> {
+        let lines: Vec<&str> = self.original.lines().collect();
+        let mut out: Vec<String> = match self.kind {
+            BootConfigKind::Extlinux => self.render_extlinux(&lines),
+            BootConfigKind::UEnv => self.render_uenv(&lines)?,
+        };
+        if self.original.ends_with('\n') || self.original.is_empty() {
+            out.push(String::new());
+        }
+        Ok(out.join("\n"))
+    }
+
+    fn render_extlinux(&self, lines: &[&str]) -> Vec<String> {
+        let (start, end) = extlinux_section(lines);
+        let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        let existing = (start..end).find(|i| extlinux_overlays(lines[*i]).is_some());
+        let indent = lines
+            .get(start + 1..end)
+            .and_then(|section| section.iter().find(|l| !l.trim().is_empty()))
+            .map_or("    ", |l| &l[..l.len() - l.trim_start().len()]);
+        let new_line = format!("{indent}fdtoverlays {}", self.overlays.join(" "));
+
+        match (existing, self.overlays.is_empty()) {
+            (Some(i), true) => {
+                out.remove(i);
+            }
+            (Some(i), false) => out[i] = new_line,
+            (None, true) => {}
+            (None, false) => {
+                let after = (start..end)
+                    .rev()
+                    .find(|i| {
+                        let key = first_word(lines[*i]);
+                        key == "fdt" || key == "fdtdir"
+                    })
+                    .or_else(|| (start..end).rev().find(|i| !lines[*i].trim().is_empty()))
+                    .unwrap_or(start);
+                out.insert(after + 1, new_line);
+            }
+        }
+        out
+    }
+
+    fn render_uenv(&self, lines: &[&str]) -> Result<Vec<String>> {
+        if self.overlays.len() > UBOOT_OVERLAY_SLOTS {
+            return Err(eyre!("uEnv.txt supports at most {UBOOT_OVERLAY_SLOTS} overlays"));
+        }
+
+        let mut out = Vec::new();
+        let mut insert_at = None;
+        let mut last_commented = None;
+        let mut overlays_enabled = false;
+        for line in lines {
+            let trimmed = line.trim();
+            if uenv_overlay(line).is_some() {
+                insert_at.get_or_insert(out.len());
+                continue;
+            }
+            if trimmed.starts_with("#uboot_overlay_addr") {
+                last_commented = Some(out.len() + 1);
+            }
+            if trimmed == "enable_uboot_overlays=1" {
+                overlays_enabled = true;
+            }
+            if trimmed == "#enable_uboot_overlays=1" && !self.overlays.is_empty() && !overlays_enabled {
+                out.push(String::from("enable_uboot_overlays=1"));
+                overlays_enabled = true;
+                continue;
+            }
+            out.push(line.to_string());
+        }
+
+        let at = insert_at.or(last_commented).unwrap_or(out.len());
+        let new_lines = self
+            .overlays
+            .iter()
+            .enumerate()
+            .map(|(slot, path)| format!("uboot_overlay_addr{slot}={path}"));
+        out.splice(at..at, new_lines);
+        if !self.overlays.is_empty() && !overlays_enabled {
+            out.push(String::from("enable_uboot_overlays=1"));
+        }
+        Ok(out)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.render().map_or(true, |content| content != self.original)
+    }
+
+    pub fn diff(&self) -> Result<Vec<DiffLine>> {
+        Ok(diff_lines(&self.original, &self.render()?))
+    }
+
+    /// Copy the current file to `<name>.bak`, then replace it with the new content, as root. The
+    /// content is written to a file next to it first and renamed over it, so a crash or a full
+    /// disk never leaves a truncated boot configuration behind. Returns the backup path.
+    pub fn save(&mut self, privilege: &dyn Privilege) -> Result<PathBuf> {
+        let content = self.render()?;
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        let path = self.path.to_string_lossy();
+        let run = |program: &str, args: &[&str]| run_privileged(privilege, program, args, &Secret::default());
+        run("cp", &["-p", &path, &backup.to_string_lossy()])
+            .map_err(|e| eyre!("Unable to back up {}: {e}", self.path.display()))?;
+
+        let temp_path = self.path.with_extension("beagle-config");
+        let temp = temp_path.to_string_lossy();
+        let replaced = write_as_root(privilege, &temp_path, &content)
+            .and_then(|_| run("chmod", &["--reference", &path, &temp]))
+            .and_then(|_| run("sync", &[&temp]))
+            .and_then(|_| run("mv", &[&temp, &path]));
+        if let Err(e) = replaced {
+            let _ = run("rm", &["-f", &temp]);
+            return Err(eyre!("Unable to write {}: {e}", self.path.display()));
+        }
+        self.original = content;
+        Ok(backup)
+    }
+}
+
+/// Names of the `.dtbo` files found in the overlay directories under `root`.
+pub fn available_overlays(root: &Path) -> Vec<String> {
+    let mut names: Vec<String> = OVERLAY_DIRS
+        .iter()
+        .filter_map(|dir| fs::read_dir(root.join(dir)).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".dtbo"))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Copy the compiled overlay `blob` as root into the first overlay directory that exists under
+/// `root`, where [`available_overlays`] and the boot loader find it.
+pub fn install_overlay(root: &Path, blob: &Path, privilege: &dyn Privilege) -> Result<PathBuf> {
+    let dir = OVERLAY_DIRS
+        .iter()
+        .map(|dir| root.join(dir))
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| root.join(OVERLAY_DIRS[0]));
+    let file_name = blob
+        .file_name()
+        .ok_or_else(|| eyre!("{} is not a file", blob.display()))?;
+    let target = dir.join(file_name);
+    let args = [
+        "-D",
+        "-m",
+        "644",
+        &blob.to_string_lossy(),
+        &target.to_string_lossy(),
+    ];
+    run_privileged(privilege, "install", &args, &Secret::default())?;
+    Ok(target)
+}
+
+pub fn entry_file_name(entry: &str) -> &str {
+    entry.rsplit('/').next().unwrap_or(entry)
+}
+
+/// Accept overlay names with or without the `.dtbo` extension, or as a path.
+pub fn overlay_file_name(name: &str) -> String {
+    let name = entry_file_name(name);
+    if name.ends_with(".dtbo") {
+        name.to_string()
+    } else {
+        format!("{name}.dtbo")
+    }
+}
+
+fn first_word(line: &str) -> String {
+    line.split_whitespace().next().unwrap_or("").to_ascii_lowercase()
+}
+
+/// Everything after the keyword, e.g. the label name in `label microSD (default)`.
+fn argument(line: &str) -> &str {
+    let line = line.trim_start();
+    line.split_once(char::is_whitespace).map_or("", |(_, rest)| rest.trim())
+}
+
+/// Line range of the label that boots by default: the one named by `default`, else the first.
+fn extlinux_section(lines: &[&str]) -> (usize, usize) {
+    let default = lines
+        .iter()
+        .find(|l| first_word(l) == "default")
+        .map(|l| argument(l));
+    let labels: Vec<usize> = (0..lines.len()).filter(|i| first_word(lines[*i]) == "label").collect();
+    let start = labels
+        .iter()
+        .copied()
+        .find(|i| Some(argument(lines[*i])) == default)
+        .or_else(|| labels.first().copied());
+
+    match start {
+        Some(start) => {
+            let end = labels.iter().copied().find(|i| *i > start).unwrap_or(lines.len());
+            (start, end)
+        }
+        None => (0, lines.len()),
+    }
+}
+
+fn extlinux_overlays(line: &str) -> Option<Vec<String>> {
+    (first_word(line) == "fdtoverlays")
+        .then(|| line.split_whitespace().skip(1).map(String::from).collect())
+}
+
+fn uenv_overlay(line: &str) -> Option<(usize, String)> {
+    let rest = line.trim().strip_prefix("uboot_overlay_addr")?;
+    let (slot, path) = rest.split_once('=')?;
+    let path = path.trim();
+    (!path.is_empty()).then(|| Some((slot.parse().ok()?, path.to_string()))).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::privilege::AsRoot;
+
+    const EXTLINUX: &str = "\
+menu title BeagleY-AI
+timeout 5
+default microSD (default)
+
+label microSD (default)
+    kernel /Image
+    append console=ttyS2,115200n8 root=/dev/mmcblk1p3 ro rootfstype=ext4 rootwait net.ifnames=0
+    fdtdir /
+    fdt /ti/k3-am67a-beagley-ai.dtb
+    initrd /initrd.img
+";
+
+    const UENV: &str = "\
+uname_r=5.10.168-ti-r72
+#uboot_overlay_addr0=<file0>.dtbo
+#uboot_overlay_addr1=<file1>.dtbo
+uboot_overlay_addr1=/lib/firmware/BB-SPIDEV0-00A0.dtbo
+uboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo
+enable_uboot_overlays=1
+";
+
+    fn extlinux(content: &str) -> BootConfig {
+        BootConfig::parse(PathBuf::from("extlinux.conf"), BootConfigKind::Extlinux, content.into())
+    }
+
+    fn uenv(content: &str) -> BootConfig {
+        BootConfig::parse(PathBuf::from("uEnv.txt"), BootConfigKind::UEnv, content.into())
+    }
+
+    #[test]
+    fn test_extlinux_add_overlay() {
+        let mut config = extlinux(EXTLINUX);
+        assert!(config.overlays.is_empty());
+        assert!(!config.is_modified());
+
+        config.enable("k3-am67a-beagley-ai-spidev0.dtbo");
+        let rendered = config.render().unwrap();
+        assert!(rendered.contains(
+            "    fdt /ti/k3-am67a-beagley-ai.dtb\n    fdtoverlays /overlays/k3-am67a-beagley-ai-spidev0.dtbo\n"
+        ));
+
+        let reparsed = extlinux(&rendered);
+        assert_eq!(reparsed.overlays, vec!["/overlays/k3-am67a-beagley-ai-spidev0.dtbo"]);
+    }
+
+    #[test]
+    fn test_extlinux_reorder_and_remove() {
+        let content = EXTLINUX.replace(
+            "    initrd",
+            "    fdtoverlays /overlays/a.dtbo /overlays/b.dtbo\n    initrd",
+        );
+        let mut config = extlinux(&content);
+        assert_eq!(config.overlays.len(), 2);
+
+        config.reorder("b.dtbo", true);
+        assert!(config.render().unwrap().contains("fdtoverlays /overlays/b.dtbo /overlays/a.dtbo"));
+
+        config.disable("a.dtbo");
+        config.disable("b.dtbo");
+        assert_eq!(config.render().unwrap(), EXTLINUX);
+    }
+
+    #[test]
+    fn test_extlinux_default_label() {
+        let content = "\
+default second
+label first
+    fdtoverlays /overlays/first.dtbo
+label second
+    fdtoverlays /overlays/second.dtbo
+";
+        assert_eq!(extlinux(content).overlays, vec!["/overlays/second.dtbo"]);
+    }
+
+    #[test]
+    fn test_uenv_slots() {
+        let mut config = uenv(UENV);
+        assert_eq!(
+            config.overlays,
+            vec!["/lib/firmware/BB-UART1-00A0.dtbo", "/lib/firmware/BB-SPIDEV0-00A0.dtbo"]
+        );
+
+        config.disable("BB-UART1-00A0.dtbo");
+        config.enable("BB-I2C2-00A0.dtbo");
+        let rendered = config.render().unwrap();
+        assert!(rendered.contains(
+            "uboot_overlay_addr0=/lib/firmware/BB-SPIDEV0-00A0.dtbo\nuboot_overlay_addr1=/lib/firmware/BB-I2C2-00A0.dtbo\n"
+        ));
+        assert!(!rendered.contains("BB-UART1"));
+        assert!(rendered.contains("#uboot_overlay_addr1=<file1>.dtbo"));
+    }
+
+    #[test]
+    fn test_uenv_enables_overlays() {
+        let mut config = uenv("uname_r=5.10\n#uboot_overlay_addr0=<file0>.dtbo\n#enable_uboot_overlays=1\n");
+        config.enable("BB-UART1-00A0.dtbo");
+        assert_eq!(
+            config.render().unwrap(),
+            "uname_r=5.10\n#uboot_overlay_addr0=<file0>.dtbo\nuboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo\nenable_uboot_overlays=1\n"
+        );
+    }
+
+    #[test]
+    fn test_uenv_slot_limit() {
+        let mut config = uenv("");
+        for i in 0..=UBOOT_OVERLAY_SLOTS {
+            config.enable(&format!("o{i}.dtbo"));
+        }
+        assert!(config.render().is_err());
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let root = tempfile::tempdir().unwrap();
+        let boot = root.path().join("boot/firmware/extlinux");
+        let overlays = root.path().join("boot/firmware/overlays");
+        fs::create_dir_all(&boot).unwrap();
+        fs::create_dir_all(&overlays).unwrap();
+        fs::write(boot.join("extlinux.conf"), EXTLINUX).unwrap();
+        fs::write(overlays.join("b.dtbo"), "").unwrap();
+        fs::write(overlays.join("a.dtbo"), "").unwrap();
+        fs::write(overlays.join("README"), "").unwrap();
+
+        assert_eq!(available_overlays(root.path()), vec!["a.dtbo", "b.dtbo"]);
+
+        let mut config = BootConfig::load(root.path()).unwrap();
+        assert_eq!(config.kind, BootConfigKind::Extlinux);
+        config.enable("a.dtbo");
+        let backup = config.save(&AsRoot).unwrap();
+
+        assert_eq!(fs::read_to_string(backup).unwrap(), EXTLINUX);
+        assert_eq!(
+            BootConfig::load(root.path()).unwrap().overlays,
+            vec!["/overlays/a.dtbo"]
+        );
+        assert!(!config.path.with_extension("beagle-config").exists());
+        assert!(!config.is_modified());
+    }
+
+    #[test]
+    fn test_install_overlay() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("boot/overlays")).unwrap();
+        let blob = root.path().join("staged.dtbo");
+        fs::write(&blob, "overlay").unwrap();
+
+        let target = install_overlay(root.path(), &blob, &AsRoot).unwrap();
+        assert_eq!(target, root.path().join("boot/overlays/staged.dtbo"));
+        assert_eq!(fs::read_to_string(target).unwrap(), "overlay");
+        assert_eq!(available_overlays(root.path()), vec!["staged.dtbo"]);
+    }
+
+    #[test]
+    fn test_overlay_file_name() {
+        assert_eq!(overlay_file_name("BB-UART1-00A0"), "BB-UART1-00A0.dtbo");
+        assert_eq!(overlay_file_name("/lib/firmware/BB-UART1-00A0.dtbo"), "BB-UART1-00A0.dtbo");
+    }
+}
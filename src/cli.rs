@@ -1,6 +1,9 @@
 use clap::Parser;
 
-use crate::config::{get_config_dir, get_data_dir};
+use crate::{
+    commands::Command,
+    config::{get_config_dir, get_data_dir},
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
@@ -12,6 +15,22 @@ pub struct Cli {
     /// Frame rate, i.e. number of frames per second
     #[arg(short, long, value_name = "FLOAT", default_value_t = 10.0)]
     pub frame_rate: f64,
+
+    /// Print the result of status queries as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Start with a mandatory password change and quit once it is done, e.g. on first boot
+    #[arg(long)]
+    pub first_boot: bool,
+
+    /// Start with the setup wizard: password, hostname, locale, time, WiFi, SSH and overlays
+    #[arg(long)]
+    pub wizard: bool,
+
+    /// Run a single command instead of starting the interactive interface
+    #[command(subcommand)]
+    pub command: Option<Command>,
 }
 
 const VERSION_MESSAGE: &str = concat!(
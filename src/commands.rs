@@ -0,0 +1,190 @@
+use std::{fmt, io};
+
+use clap::Subcommand;
+use color_eyre::{Report, Result};
+use serde::Serialize;
+
+use crate::system::password::PasswordError;
+
+pub mod apply;
+pub mod board;
+pub mod export;
+pub mod overlay;
+pub mod password;
+pub mod pinout;
+pub mod ssh;
+pub mod wifi;
+
+/// Exit codes shared by every command. Usage errors exit with 2, as reported by clap.
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_NOT_FOUND: u8 = 3;
+pub const EXIT_PERMISSION_DENIED: u8 = 4;
+pub const EXIT_UNAVAILABLE: u8 = 5;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Apply a provisioning file: hostname, password, SSH, locale, timezone, WiFi and overlays
+    Apply(apply::ApplyArgs),
+    /// Write the current settings as a provisioning file that `apply` accepts
+    Export(export::ExportArgs),
+    /// Show the detected board
+    Board {
+        #[command(subcommand)]
+        command: board::BoardCommand,
+    },
+    /// Scan for and connect to WiFi networks
+    Wifi {
+        #[command(subcommand)]
+        command: wifi::WifiCommand,
+    },
+    /// Control the SSH server
+    Ssh {
+        #[command(subcommand)]
+        command: ssh::SshCommand,
+    },
+    /// Change account passwords
+    Password {
+        #[command(subcommand)]
+        command: password::PasswordCommand,
+    },
+    /// List, enable and disable device tree overlays
+    Overlay {
+        #[command(subcommand)]
+        command: overlay::OverlayCommand,
+    },
+    /// Print the header pinout of the board
+    Pinout {
+        #[command(subcommand)]
+        command: pinout::PinoutCommand,
+    },
+}
+
+/// Failures that map to a dedicated exit code.
+#[derive(Debug)]
+pub enum CommandError {
+    /// The network, overlay, pin or other named thing does not exist.
+    NotFound(String),
+    /// A service the command relies on is missing or not running.
+    Unavailable(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::NotFound(message) | CommandError::Unavailable(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Run `command`, print any error to stderr and return the process exit code.
+///
+/// With `json` set, queries print their result as JSON instead of text.
+pub async fn run(command: Command, json: bool) -> u8 {
+    let result = match command {
+        Command::Apply(args) => apply::run(args).await,
+        Command::Export(args) => export::run(args).await,
+        Command::Board { command } => board::run(command, json),
+        Command::Wifi { command } => wifi::run(command, json).await,
+        Command::Ssh { command } => ssh::run(command, json),
+        Command::Password { command } => password::run(command),
+        Command::Overlay { command } => overlay::run(command, json),
+        Command::Pinout { command } => pinout::run(command, json),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            exit_code(&e)
+        }
+    }
+}
+
+pub fn exit_code(error: &Report) -> u8 {
+    if let Some(error) = error.downcast_ref::<CommandError>() {
+        return match error {
+            CommandError::NotFound(_) => EXIT_NOT_FOUND,
+            CommandError::Unavailable(_) => EXIT_UNAVAILABLE,
+        };
+    }
+    if let Some(error) = error.downcast_ref::<PasswordError>() {
+        return match error {
+            PasswordError::WrongPassword | PasswordError::PermissionDenied => EXIT_PERMISSION_DENIED,
+            _ => EXIT_FAILURE,
+        };
+    }
+    match error.downcast_ref::<io::Error>().map(|e| e.kind()) {
+        Some(io::ErrorKind::PermissionDenied) => EXIT_PERMISSION_DENIED,
+        Some(io::ErrorKind::NotFound) => EXIT_NOT_FOUND,
+        Some(io::ErrorKind::NotConnected) => EXIT_UNAVAILABLE,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Print `value` to stdout as pretty-printed JSON.
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Read a single line from stdin, without its line ending.
+fn read_stdin_line() -> Result<String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use color_eyre::eyre::eyre;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cli::Cli;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(exit_code(&eyre!("boom")), EXIT_FAILURE);
+        assert_eq!(exit_code(&CommandError::NotFound("x".into()).into()), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(&CommandError::Unavailable("x".into()).into()), EXIT_UNAVAILABLE);
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "no");
+        assert_eq!(exit_code(&denied.into()), EXIT_PERMISSION_DENIED);
+        let no_bus = io::Error::new(io::ErrorKind::NotConnected, "no");
+        assert_eq!(exit_code(&no_bus.into()), EXIT_UNAVAILABLE);
+        assert_eq!(exit_code(&PasswordError::WrongPassword.into()), EXIT_PERMISSION_DENIED);
+        assert_eq!(exit_code(&PasswordError::WeakPassword("short".into()).into()), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::try_parse_from(["beagle-config", "wifi", "connect", "Home", "--passphrase", "secret"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Wifi { command: wifi::WifiCommand::Connect { ref ssid, .. } }) if ssid == "Home"
+        ));
+
+        let cli = Cli::try_parse_from(["beagle-config", "wifi", "connect", "Attic", "--hidden"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Wifi { command: wifi::WifiCommand::Connect { hidden: true, passphrase: None, .. } })
+        ));
+
+        let cli = Cli::try_parse_from(["beagle-config", "overlay", "enable", "BB-UART1-00A0"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Overlay { .. })));
+
+        let cli = Cli::try_parse_from(["beagle-config"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(!cli.json);
+
+        let cli = Cli::try_parse_from(["beagle-config", "ssh", "status", "--json"]).unwrap();
+        assert!(cli.json);
+
+        let cli = Cli::try_parse_from(["beagle-config", "apply", "provision.toml", "--dry-run"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Apply(apply::ApplyArgs { dry_run: true, .. }))));
+
+        assert!(Cli::try_parse_from(["beagle-config", "ssh", "restart"]).is_err());
+    }
+}
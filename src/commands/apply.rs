@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use color_eyre::Result;
+
+use super::{wifi::Wifi, CommandError};
+use crate::{
+    boards::{
+        identity::Board,
+        pinmux::{staging_dir, Pinmux},
+    },
+    boot::overlays::{available_overlays, entry_file_name, install_overlay, BootConfig},
+    config::Config,
+    provision::{Change, Provision, State},
+    system::{
+        authorized_keys, clock, hostname, locale,
+        password::{change_password, PasswordError, PasswordPolicy},
+        privilege::{self, Privilege},
+        require, ssh, systemd,
+    },
+};
+
+#[derive(Args, Debug)]
+pub struct ApplyArgs {
+    /// Provisioning file, see the README for its format
+    pub file: PathBuf,
+    /// Print the changes that would be made without making them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+pub async fn run(args: ApplyArgs) -> Result<()> {
+    let provision = Provision::load(&args.file)?;
+
+    let root = Path::new("/");
+    let mut state = State::read(&provision, root);
+    // Only talk to iwd when there are networks to configure.
+    let wifi = if provision.wifi.is_empty() {
+        None
+    } else {
+        match Wifi::new().await {
+            Ok(wifi) => {
+                state.known_networks = Some(wifi.known_networks().await);
+                Some(wifi)
+            }
+            Err(e) if args.dry_run => {
+                eprintln!("Warning: {e}");
+                None
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    let changes = provision.plan(&state)?;
+    let policy = Config::new()?.password_policy;
+    if changes.is_empty() {
+        println!("Nothing to change");
+        return Ok(());
+    }
+    for change in changes {
+        if args.dry_run {
+            println!("would {change}");
+            continue;
+        }
+        apply(&change, root, wifi.as_ref(), &policy, &|| privilege::detect(None)).await?;
+        println!("{change}: done");
+    }
+    Ok(())
+}
+
+/// Makes a new way to become root for each change that needs one.
+pub(crate) type Privileges<'a> = &'a (dyn Fn() -> Option<Box<dyn Privilege>> + Sync);
+
+/// Make a single planned change, as `beagle-config apply` and the setup wizard do, becoming root
+/// through `privilege`. New passwords have to pass `policy`.
+pub(crate) async fn apply(
+    change: &Change,
+    root: &Path,
+    wifi: Option<&Wifi>,
+    policy: &PasswordPolicy,
+    privilege: Privileges<'_>,
+) -> Result<()> {
+    apply_with(change, root, wifi, policy, privilege, &staging_dir(), "dtc").await
+}
+
+/// [`apply`] with the pinmux overlay staged in `staging` and compiled with `dtc`.
+async fn apply_with(
+    change: &Change,
+    root: &Path,
+    wifi: Option<&Wifi>,
+    policy: &PasswordPolicy,
+    privilege: Privileges<'_>,
+    staging: &Path,
+    dtc: &str,
+) -> Result<()> {
+    match change {
+        Change::Hostname(name) => {
+            let old = hostname::hostname().unwrap_or_default();
+            hostname::update_hosts(root, &old, name, privilege().as_deref())?;
+            hostname::detect(root, privilege()).set_static(name)
+        }
+        Change::PrettyHostname(pretty) => hostname::detect(root, privilege()).set_pretty(pretty),
+        Change::Locale(lang) => locale::set_locale(lang, require(privilege().as_deref(), "Changing the locale")?),
+        Change::Timezone(tz) => clock::detect(root, privilege()).set_timezone(tz),
+        Change::Password { user, password } => {
+            let privilege = privilege().ok_or(PasswordError::PermissionDenied)?;
+            Ok(change_password(user, password, policy, privilege.as_ref())?)
+        }
+        Change::AuthorizedKeys { user, keys } => authorized_keys::add_keys(user, keys).map(|_| ()),
+        Change::Ssh(true) => ssh::enable(systemd::detect(privilege()).as_ref()),
+        Change::Ssh(false) => ssh::disable(systemd::detect(privilege()).as_ref()),
+        Change::Overlays(names) => set_overlays(root, names, privilege().as_deref()),
+        Change::Pins(pins) => {
+            let board = Board::detect_from(root).description()?;
+            let staged = Pinmux::from_functions(&board, pins)?.write_overlay_with(&board, staging, dtc)?;
+            let blob = staged.blob.ok_or_else(|| CommandError::Unavailable(String::from("dtc is not installed")))?;
+            install_overlay(root, &blob, require(privilege().as_deref(), "Installing the overlay")?).map(|_| ())
+        }
+        Change::Wifi(network) => {
+            let wifi = wifi.ok_or_else(|| CommandError::Unavailable(String::from("iwd is not running")))?;
+            wifi.connect(&network.ssid, network.passphrase.clone()).await.map(|_| ())
+        }
+    }
+}
+
+/// Make `names` the enabled overlays, in that order.
+fn set_overlays(root: &Path, names: &[String], privilege: Option<&dyn Privilege>) -> Result<()> {
+    let mut config = BootConfig::load(root).map_err(|e| CommandError::Unavailable(e.to_string()))?;
+    let available = available_overlays(root);
+    if let Some(name) = names.iter().find(|n| !available.contains(n) && !config.is_enabled(n)) {
+        return Err(CommandError::NotFound(format!("No overlay named {name}")).into());
+    }
+
+    let enabled: Vec<String> = config.overlays.iter().map(|o| entry_file_name(o).to_string()).collect();
+    for name in &enabled {
+        config.disable(name);
+    }
+    for name in names {
+        config.enable(name);
+    }
+    if config.is_modified() {
+        config.save(require(privilege, "Changing the boot configuration")?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs, os::unix::fs::PermissionsExt};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{boards::pinmux::OVERLAY_NAME, system::privilege::AsRoot};
+
+    #[test]
+    fn test_set_overlays() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("boot")).unwrap();
+        fs::create_dir_all(root.path().join("lib/firmware")).unwrap();
+        fs::write(
+            root.path().join("boot/uEnv.txt"),
+            "enable_uboot_overlays=1\nuboot_overlay_addr0=/lib/firmware/BB-I2C2-00A0.dtbo\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("lib/firmware/BB-UART1-00A0.dtbo"), "").unwrap();
+
+        let names = vec![String::from("BB-UART1-00A0.dtbo")];
+        set_overlays(root.path(), &names, Some(&AsRoot)).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.path().join("boot/uEnv.txt")).unwrap(),
+            "enable_uboot_overlays=1\nuboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo\n"
+        );
+
+        let state = State { overlays: Some(names.clone()), ..State::default() };
+        let provision = Provision { overlays: Some(vec![String::from("BB-UART1-00A0")]), ..Provision::default() };
+        assert_eq!(provision.plan(&state).unwrap(), Vec::new());
+
+        assert!(set_overlays(root.path(), &[String::from("BB-NOPE.dtbo")], Some(&AsRoot)).is_err());
+    }
+
+    /// A device tree compiler that copies the source, as `dtc` is not installed everywhere.
+    fn fake_dtc(dir: &Path) -> String {
+        let dtc = dir.join("dtc");
+        fs::write(&dtc, "#!/bin/sh\nwhile [ \"$1\" != -o ]; do shift; done\ncp \"$3\" \"$2\"\n").unwrap();
+        fs::set_permissions(&dtc, fs::Permissions::from_mode(0o755)).unwrap();
+        dtc.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_plan_and_apply_pins() {
+        let root = tempfile::tempdir().unwrap();
+        let staging = tempfile::tempdir().unwrap();
+        let dtc = fake_dtc(staging.path());
+        fs::create_dir_all(root.path().join("proc/device-tree")).unwrap();
+        fs::create_dir_all(root.path().join("boot")).unwrap();
+        fs::create_dir_all(root.path().join("lib/firmware")).unwrap();
+        fs::write(root.path().join("proc/device-tree/compatible"), "ti,am335x-bone-black\0ti,am33xx\0").unwrap();
+        let uenv = root.path().join("boot/uEnv.txt");
+        fs::write(&uenv, "enable_uboot_overlays=1\nuboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo\n").unwrap();
+        fs::write(root.path().join("lib/firmware/BB-UART1-00A0.dtbo"), "").unwrap();
+
+        let apply_all = |provision: Provision| {
+            let (root, staging, dtc) = (root.path().to_path_buf(), staging.path().to_path_buf(), dtc.clone());
+            async move {
+                let config = BootConfig::load(&root).unwrap();
+                let overlays = config.overlays.iter().map(|o| entry_file_name(o).to_string()).collect();
+                let changes = provision.plan(&State { overlays: Some(overlays), ..State::default() }).unwrap();
+                let policy = PasswordPolicy::default();
+                for change in &changes {
+                    apply_with(change, &root, None, &policy, &|| Some(Box::new(AsRoot)), &staging, &dtc).await.unwrap();
+                }
+                changes
+            }
+        };
+        let pins = BTreeMap::from([(String::from("P9_14"), String::from("EHRPWM1 A"))]);
+
+        // Without a list of overlays, the pinmux one is installed and added to the enabled ones.
+        let changes = apply_all(Provision { pins: pins.clone(), ..Provision::default() }).await;
+        assert_eq!(
+            changes,
+            [
+                Change::Pins(pins.clone()),
+                Change::Overlays(vec![String::from("BB-UART1-00A0.dtbo"), String::from("beagle-config-pinmux.dtbo")]),
+            ]
+        );
+        assert!(root.path().join("lib/firmware/beagle-config-pinmux.dtbo").exists());
+        assert_eq!(
+            fs::read_to_string(&uenv).unwrap(),
+            "enable_uboot_overlays=1\nuboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo\n\
+             uboot_overlay_addr1=/lib/firmware/beagle-config-pinmux.dtbo\n"
+        );
+
+        // A list naming the pinmux overlay is set after it was installed.
+        fs::remove_file(root.path().join("lib/firmware/beagle-config-pinmux.dtbo")).unwrap();
+        let provision = Provision { overlays: Some(vec![String::from(OVERLAY_NAME)]), pins, ..Provision::default() };
+        let changes = apply_all(provision).await;
+        assert!(matches!(changes[..], [Change::Pins(_), Change::Overlays(_)]));
+        assert_eq!(
+            fs::read_to_string(&uenv).unwrap(),
+            "enable_uboot_overlays=1\nuboot_overlay_addr0=/lib/firmware/beagle-config-pinmux.dtbo\n"
+        );
+    }
+}
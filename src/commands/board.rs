@@ -0,0 +1,35 @@
+use clap::Subcommand;
+use color_eyre::Result;
+
+use super::print_json;
+use crate::boards::identity::Board;
+
+#[derive(Subcommand, Debug)]
+pub enum BoardCommand {
+    /// Show the detected model, device tree compatibles and EEPROM contents
+    Show,
+}
+
+pub fn run(command: BoardCommand, json: bool) -> Result<()> {
+    match command {
+        BoardCommand::Show => {
+            let board = Board::detect();
+            if json {
+                return print_json(&board);
+            }
+            println!("model: {}", board.name());
+            println!("board: {:?}", board.kind);
+            println!("pinout: {}", board.kind.description_id());
+            println!("compatible: {}", board.compatible.join(", "));
+            match &board.eeprom {
+                Some(eeprom) => {
+                    println!("eeprom: {}", eeprom.board_name);
+                    println!("revision: {}", eeprom.version);
+                    println!("serial: {}", eeprom.serial);
+                }
+                None => println!("eeprom: -"),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::Args;
+use color_eyre::{eyre::eyre, Result};
+use iwdrs::session::Session;
+
+use crate::{
+    boot::overlays::{entry_file_name, BootConfig},
+    networks::know_network::KnownNetwork,
+    provision::{read_pins, Provision, SshConfig, UserConfig, WifiConfig},
+    system::{authorized_keys, clock, hostname, locale, ssh, systemd::SystemBus},
+};
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// File to write the snapshot to, stdout by default
+    pub file: Option<PathBuf>,
+    /// Account whose authorized SSH keys are exported, the one that ran sudo by default
+    #[arg(short, long)]
+    pub user: Option<String>,
+    /// Also export the passphrases of known WiFi networks. Must be run as root.
+    #[arg(long)]
+    pub include_secrets: bool,
+}
+
+pub async fn run(args: ExportArgs) -> Result<()> {
+    let provision = snapshot(&args).await?;
+    let content = toml::to_string_pretty(&provision)?;
+    match &args.file {
+        Some(path) => {
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            if args.include_secrets {
+                options.mode(0o600);
+            }
+            let mut file = options.open(path)?;
+            // The mode only applies to new files, so one that was there already is narrowed too.
+            if args.include_secrets {
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+            file.write_all(content.as_bytes())?;
+            eprintln!("Wrote {}", path.display());
+        }
+        None => print!("{content}"),
+    }
+    Ok(())
+}
+
+/// Capture the current settings in the form `apply` reads. Settings that cannot be read are
+/// left out, so applying the snapshot leaves them alone.
+async fn snapshot(args: &ExportArgs) -> Result<Provision> {
+    let user = args.user.clone().unwrap_or_else(authorized_keys::target_user);
+    let authorized_keys = authorized_keys::keys(&user).unwrap_or_default();
+
+    Ok(Provision {
+        hostname: hostname::hostname().ok(),
+        locale: locale::locale().ok().flatten(),
+        timezone: clock::detect(Path::new("/"), None).status().ok().map(|status| status.timezone),
+        overlays: BootConfig::load(Path::new("/"))
+            .ok()
+            .map(|config| config.overlays.iter().map(|o| entry_file_name(o).to_string()).collect()),
+        ssh: Some(SshConfig {
+            enabled: ssh::status(&SystemBus::new()).ok().map(|status| status.enabled),
+            user: None,
+            authorized_keys,
+        }),
+        user: Some(UserConfig { name: user, password: None }),
+        pins: read_pins().unwrap_or_default(),
+        wifi: known_networks(args.include_secrets).await?,
+    })
+}
+
+async fn known_networks(include_secrets: bool) -> Result<Vec<WifiConfig>> {
+    let session = match Session::new().await {
+        Ok(session) => Arc::new(session),
+        Err(e) => {
+            eprintln!("Warning: WiFi networks not exported, unable to reach iwd: {e}");
+            return Ok(Vec::new());
+        }
+    };
+
+    let mut networks = Vec::new();
+    for n in session.known_networks().await {
+        let network = KnownNetwork::new(n).await.map_err(|e| eyre!("{e}"))?;
+        let passphrase = if include_secrets {
+            network.stored_passphrase().map_err(|e| match e.downcast::<std::io::Error>() {
+                Ok(e) => (*e).into(),
+                Err(e) => eyre!("{e}"),
+            })?
+        } else {
+            None
+        };
+        networks.push(WifiConfig {
+            ssid: network.name,
+            security: Some(network.netowrk_type),
+            passphrase,
+        });
+    }
+    Ok(networks)
+}
@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use clap::Subcommand;
+use color_eyre::Result;
+use serde::Serialize;
+
+use super::{print_json, CommandError};
+use crate::{
+    boot::overlays::{available_overlays, entry_file_name, overlay_file_name, BootConfig},
+    system::{
+        privilege::{self, Privilege},
+        require,
+    },
+};
+
+#[derive(Subcommand, Debug)]
+pub enum OverlayCommand {
+    /// List available overlays and the ones enabled at boot, in order
+    List,
+    /// Enable overlays, appended after the ones already enabled
+    Enable {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Disable overlays
+    Disable {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+}
+
+/// Output of `overlay list --json`.
+#[derive(Serialize)]
+struct Listing<'a> {
+    #[serde(flatten)]
+    config: &'a BootConfig,
+    /// Overlays that could be enabled but are not.
+    available: Vec<&'a String>,
+}
+
+pub fn run(command: OverlayCommand, json: bool) -> Result<()> {
+    run_in(command, Path::new("/"), json, privilege::detect(None).as_deref())
+}
+
+/// Run `command` against the boot files found under `root`, writing them through `privilege`.
+fn run_in(command: OverlayCommand, root: &Path, json: bool, privilege: Option<&dyn Privilege>) -> Result<()> {
+    let mut config =
+        BootConfig::load(root).map_err(|e| CommandError::Unavailable(e.to_string()))?;
+    let available = available_overlays(root);
+
+    match command {
+        OverlayCommand::List => {
+            if json {
+                let available = available.iter().filter(|name| !config.is_enabled(name)).collect();
+                return print_json(&Listing { config: &config, available });
+            }
+            for (i, entry) in config.overlays.iter().enumerate() {
+                println!("{:>2}. {}", i + 1, entry_file_name(entry));
+            }
+            for name in available.iter().filter(|name| !config.is_enabled(name)) {
+                println!("    {name}");
+            }
+            return Ok(());
+        }
+        OverlayCommand::Enable { names } => {
+            for name in names.iter().map(|n| overlay_file_name(n)) {
+                if !available.contains(&name) && !config.is_enabled(&name) {
+                    return Err(CommandError::NotFound(format!("No overlay named {name}")).into());
+                }
+                config.enable(&name);
+            }
+        }
+        OverlayCommand::Disable { names } => {
+            for name in names.iter().map(|n| overlay_file_name(n)) {
+                if !config.is_enabled(&name) {
+                    return Err(CommandError::NotFound(format!("{name} is not enabled")).into());
+                }
+                config.disable(&name);
+            }
+        }
+    }
+
+    if config.is_modified() {
+        let backup = config.save(require(privilege, "Changing the boot configuration")?)?;
+        println!("Updated {} (backup in {})", config.path.display(), backup.display());
+    } else {
+        println!("{} is already up to date", config.path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        commands::{exit_code, EXIT_NOT_FOUND, EXIT_UNAVAILABLE},
+        system::privilege::AsRoot,
+    };
+
+    fn root() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("boot")).unwrap();
+        fs::create_dir_all(root.path().join("lib/firmware")).unwrap();
+        fs::write(root.path().join("boot/uEnv.txt"), "enable_uboot_overlays=1\n").unwrap();
+        fs::write(root.path().join("lib/firmware/BB-UART1-00A0.dtbo"), "").unwrap();
+        root
+    }
+
+    #[test]
+    fn test_enable_and_disable() {
+        let root = root();
+        let uenv = root.path().join("boot/uEnv.txt");
+
+        let enable = OverlayCommand::Enable { names: vec![String::from("BB-UART1-00A0")] };
+        run_in(enable, root.path(), false, Some(&AsRoot)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&uenv).unwrap(),
+            "enable_uboot_overlays=1\nuboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo\n"
+        );
+
+        let disable = OverlayCommand::Disable { names: vec![String::from("BB-UART1-00A0.dtbo")] };
+        run_in(disable, root.path(), false, Some(&AsRoot)).unwrap();
+        assert_eq!(fs::read_to_string(&uenv).unwrap(), "enable_uboot_overlays=1\n");
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let root = root();
+        let unknown = OverlayCommand::Enable { names: vec![String::from("BB-NOPE")] };
+        assert_eq!(exit_code(&run_in(unknown, root.path(), false, Some(&AsRoot)).unwrap_err()), EXIT_NOT_FOUND);
+
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(exit_code(&run_in(OverlayCommand::List, empty.path(), false, None).unwrap_err()), EXIT_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_json_listing() {
+        let root = root();
+        let mut config = BootConfig::load(root.path()).unwrap();
+        config.enable("BB-I2C2-00A0.dtbo");
+        let available = [String::from("BB-UART1-00A0.dtbo")];
+        let listing = Listing { config: &config, available: available.iter().collect() };
+
+        let json = serde_json::to_value(&listing).unwrap();
+        assert_eq!(json["kind"], "uenv");
+        assert_eq!(json["overlays"][0], "/lib/firmware/BB-I2C2-00A0.dtbo");
+        assert_eq!(json["available"][0], "BB-UART1-00A0.dtbo");
+        assert!(json.get("original").is_none());
+    }
+}
@@ -0,0 +1,36 @@
+use clap::Subcommand;
+use color_eyre::Result;
+
+use super::read_stdin_line;
+use crate::{
+    config::Config,
+    system::{
+        password::{change_password, PasswordError},
+        privilege,
+        secret::Secret,
+    },
+};
+
+#[derive(Subcommand, Debug)]
+pub enum PasswordCommand {
+    /// Set a password, read as a single line from stdin. Asks through polkit when not run as root.
+    Set {
+        /// Account to change, the current user by default
+        #[arg(short, long)]
+        user: Option<String>,
+    },
+}
+
+pub fn run(command: PasswordCommand) -> Result<()> {
+    match command {
+        PasswordCommand::Set { user } => {
+            let user = user.unwrap_or_else(whoami::username);
+            let password = Secret::new(read_stdin_line()?);
+            let policy = Config::new()?.password_policy;
+            let privilege = privilege::detect(None).ok_or(PasswordError::PermissionDenied)?;
+            change_password(&user, &password, &policy, privilege.as_ref())?;
+            println!("Password for {user} updated");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::fmt::Write;
+
+use clap::Subcommand;
+use color_eyre::Result;
+
+use super::{print_json, CommandError};
+use crate::boards::{
+    description::{BoardDescription, Header},
+    identity::Board,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum PinoutCommand {
+    /// Print every pin of the board headers
+    Show {
+        /// Board description to use instead of the detected board
+        #[arg(short, long)]
+        board: Option<String>,
+        /// Only print this header
+        #[arg(long)]
+        header: Option<String>,
+    },
+}
+
+pub fn run(command: PinoutCommand, json: bool) -> Result<()> {
+    match command {
+        PinoutCommand::Show { board, header } => {
+            let mut description = match board {
+                Some(id) => BoardDescription::load(&id)
+                    .map_err(|e| CommandError::NotFound(e.to_string()))?,
+                None => Board::detect().description()?,
+            };
+            description
+                .headers
+                .retain(|h| header.as_ref().is_none_or(|name| h.name.eq_ignore_ascii_case(name)));
+            if description.headers.is_empty() {
+                return Err(CommandError::NotFound(format!(
+                    "{} has no header named {}",
+                    description.name,
+                    header.unwrap_or_default()
+                ))
+                .into());
+            }
+
+            if json {
+                return print_json(&description);
+            }
+            println!("{}", description.name);
+            for header in &description.headers {
+                print!("\n{}", format_header(header)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn format_header(header: &Header) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "{}", header.name)?;
+    writeln!(out, "{:>4}  {:<20} {:<16} Type", "Pin", "Name", "Function")?;
+    let mut pins: Vec<_> = header.pins.iter().collect();
+    pins.sort_by_key(|p| p.number);
+    for pin in pins {
+        writeln!(
+            out,
+            "{:>4}  {:<20} {:<16} {:?}",
+            pin.number, pin.name, pin.function, pin.pin_type
+        )?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_header() {
+        let board = BoardDescription::builtin("beaglebone-black").unwrap();
+        let p9 = format_header(&board.headers[0]).unwrap();
+        let lines: Vec<&str> = p9.lines().collect();
+        assert_eq!(lines[0], "P9");
+        assert_eq!(lines.len(), 2 + 46);
+        assert!(lines[18].starts_with("  17  GPIO0_5              I2C1 SCL         I2C"));
+    }
+
+    #[test]
+    fn test_json_pin_table() {
+        let board = BoardDescription::builtin("beaglebone-black").unwrap();
+        let json = serde_json::to_value(&board).unwrap();
+        assert_eq!(json["id"], "beaglebone-black");
+        let pin = &json["headers"][0]["pins"][16];
+        assert_eq!(pin["number"], 17);
+        assert_eq!(pin["function"], "I2C1 SCL");
+        assert_eq!(pin["type"], "I2C");
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use clap::Subcommand;
+use color_eyre::Result;
+use serde::Serialize;
+
+use super::print_json;
+use crate::system::{
+    host_keys, privilege, require,
+    ssh::{self, SshStatus},
+    systemd,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum SshCommand {
+    /// Show whether the SSH server is running and starts at boot
+    Status,
+    /// Start the SSH server now and at every boot
+    Enable,
+    /// Stop the SSH server and keep it from starting at boot
+    Disable,
+    /// Show the fingerprints of the server's host keys
+    HostKeys,
+    /// Replace the host keys with new ones, e.g. on the first boot of a cloned image
+    RegenerateHostKeys,
+}
+
+#[derive(Serialize)]
+struct Status {
+    unit: &'static str,
+    #[serde(flatten)]
+    status: SshStatus,
+}
+
+pub fn run(command: SshCommand, json: bool) -> Result<()> {
+    // Without root, changes go through systemctl run with pkexec.
+    let systemd = systemd::detect(privilege::detect(None));
+    let systemd = systemd.as_ref();
+    match command {
+        SshCommand::Status => {
+            let status = ssh::status(systemd)?;
+            if json {
+                return print_json(&Status { unit: ssh::unit(systemd), status });
+            }
+            println!("unit: {}", ssh::unit(systemd));
+            println!("active: {}", if status.active { "yes" } else { "no" });
+            println!("enabled: {}", if status.enabled { "yes" } else { "no" });
+        }
+        SshCommand::Enable => {
+            ssh::enable(systemd)?;
+            println!("SSH server enabled");
+        }
+        SshCommand::Disable => {
+            ssh::disable(systemd)?;
+            println!("SSH server disabled");
+        }
+        SshCommand::HostKeys => {
+            let keys = host_keys::host_keys(Path::new("/"))?;
+            if json {
+                return print_json(&keys);
+            }
+            for key in keys {
+                println!("{} {} ({})", key.fingerprint, key.key_type, key.path.display());
+            }
+        }
+        SshCommand::RegenerateHostKeys => {
+            let privilege = privilege::detect(None);
+            let privilege = require(privilege.as_deref(), "Replacing the host keys")?;
+            let backup = host_keys::regenerate(Path::new("/"), systemd, privilege)?;
+            for key in host_keys::host_keys(Path::new("/"))? {
+                println!("{} {}", key.fingerprint, key.key_type);
+            }
+            println!("Host keys regenerated, the old ones are in {}", backup.display());
+        }
+    }
+    Ok(())
+}
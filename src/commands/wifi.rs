@@ -0,0 +1,214 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::Subcommand;
+use color_eyre::{eyre::eyre, Result};
+use futures::FutureExt;
+use iwdrs::{
+    agent::{Agent, AgentManager},
+    session::Session,
+};
+
+use super::{print_json, read_stdin_line, CommandError};
+use crate::networks::{
+    adaptor::Adapter,
+    network::{validate_passphrase, validate_ssid, Network},
+    rfkill,
+    station::{self, Station},
+};
+
+/// How long `scan` waits for iwd to finish scanning.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Subcommand, Debug)]
+pub enum WifiCommand {
+    /// Show the adapter and the current connection
+    Status,
+    /// Scan and list the networks in range
+    Scan,
+    /// Connect to a network in range
+    Connect {
+        ssid: String,
+        /// The network does not broadcast its SSID. Without a passphrase it is joined as open
+        #[arg(long)]
+        hidden: bool,
+        /// Passphrase for protected networks that are not known yet
+        #[arg(long, conflicts_with = "passphrase_stdin")]
+        passphrase: Option<String>,
+        /// Read the passphrase as a single line from stdin
+        #[arg(long)]
+        passphrase_stdin: bool,
+    },
+}
+
+pub async fn run(command: WifiCommand, json: bool) -> Result<()> {
+    let wifi = Wifi::new().await?;
+    let adapter = &wifi.adapter;
+
+    match command {
+        WifiCommand::Status => {
+            if json {
+                return print_json(adapter);
+            }
+            println!("adapter: {} ({})", adapter.name, if adapter.is_powered { "on" } else { "off" });
+            println!("device: {} {}", adapter.device.name, adapter.device.address);
+            if let Some(station) = &adapter.device.station {
+                println!("state: {}", station.state);
+                if let Some(network) = &station.connected_network {
+                    println!("connected: {} ({})", network.name, network.netowrk_type);
+                }
+            }
+        }
+        WifiCommand::Scan => {
+            let station = scan(&wifi.session, adapter).await?;
+            if json {
+                return print_json(&station);
+            }
+            let mut networks: Vec<_> = station
+                .known_networks
+                .iter()
+                .chain(station.new_networks.iter())
+                .collect();
+            networks.sort_by_key(|(_, signal)| std::cmp::Reverse(*signal));
+            for (network, signal) in networks {
+                println!(
+                    "{:>4} dBm  {:<6} {}{}",
+                    signal / 100,
+                    network.netowrk_type,
+                    network.name,
+                    if network.known_network.is_some() { " (known)" } else { "" },
+                );
+            }
+        }
+        WifiCommand::Connect { ssid, hidden, passphrase, passphrase_stdin } => {
+            let passphrase = if passphrase_stdin { Some(read_stdin_line()?) } else { passphrase };
+            if hidden {
+                wifi.connect_hidden(&ssid, passphrase).await?;
+                println!("Connected to {ssid}");
+            } else if wifi.connect(&ssid, passphrase).await? {
+                println!("Connected to {ssid}");
+            } else {
+                println!("Already connected to {ssid}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A session with iwd that answers passphrase requests for the network being connected.
+pub(crate) struct Wifi {
+    session: Arc<Session>,
+    adapter: Adapter,
+    passphrase: Arc<Mutex<Option<String>>>,
+    _agent: AgentManager,
+}
+
+impl Wifi {
+    pub async fn new() -> Result<Self> {
+        rfkill::check()?;
+        let session = Session::new()
+            .await
+            .map(Arc::new)
+            .map_err(|e| CommandError::Unavailable(format!("Unable to reach iwd: {e}")))?;
+        let adapter = Adapter::new(session.clone())
+            .await
+            .map_err(|e| CommandError::Unavailable(e.to_string()))?;
+
+        // iwd asks the agent for the passphrase only when it does not know the network yet.
+        let passphrase: Arc<Mutex<Option<String>>> = Arc::default();
+        let answer = passphrase.clone();
+        let agent = Agent {
+            request_passphrase_fn: Box::new(move || {
+                let passphrase = answer.lock().ok().and_then(|p| p.clone());
+                async move {
+                    passphrase.ok_or_else(|| "A passphrase is required, pass --passphrase".into())
+                }
+                .boxed()
+            }),
+        };
+        let agent = session
+            .register_agent(agent)
+            .await
+            .map_err(|e| eyre!("Unable to register the passphrase agent: {e}"))?;
+
+        Ok(Wifi { session, adapter, passphrase, _agent: agent })
+    }
+
+    /// Names of the networks iwd has credentials for.
+    pub async fn known_networks(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for network in self.session.known_networks().await {
+            if let Ok(name) = network.name().await {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Connect to `ssid`, returning false when it already was the connected network.
+    pub async fn connect(&self, ssid: &str, passphrase: Option<String>) -> Result<bool> {
+        if let Some(passphrase) = &passphrase {
+            validate_passphrase(passphrase).map_err(|e| eyre!("{e}"))?;
+        }
+        let station = scan(&self.session, &self.adapter).await?;
+        let network = find_network(&station, ssid)
+            .ok_or_else(|| CommandError::NotFound(format!("No network named {ssid} in range")))?;
+        if network.is_connected {
+            return Ok(false);
+        }
+
+        *self.passphrase.lock().map_err(|e| eyre!("{e}"))? = passphrase;
+        network
+            .connect()
+            .await
+            .map_err(|e| eyre!("Unable to connect to {ssid}: {e}"))?;
+        Ok(true)
+    }
+
+    /// Connect to `ssid`, which does not broadcast its name.
+    pub async fn connect_hidden(&self, ssid: &str, passphrase: Option<String>) -> Result<()> {
+        validate_ssid(ssid).map_err(|e| eyre!("{e}"))?;
+        if let Some(passphrase) = &passphrase {
+            validate_passphrase(passphrase).map_err(|e| eyre!("{e}"))?;
+        }
+        *self.passphrase.lock().map_err(|e| eyre!("{e}"))? = passphrase;
+        station::connect_hidden_network(ssid)
+            .await
+            .map_err(|e| eyre!("Unable to connect to {ssid}: {e}"))
+    }
+}
+
+/// Trigger a scan, wait for it to finish and return the refreshed station.
+async fn scan(session: &Arc<Session>, adapter: &Adapter) -> Result<Station> {
+    let station = adapter
+        .device
+        .station
+        .as_ref()
+        .ok_or_else(|| CommandError::Unavailable(String::from("The WiFi device is not in station mode")))?;
+    station.scan().await.map_err(|e| eyre!("{e}"))?;
+
+    let iwd_station = session
+        .station()
+        .ok_or_else(|| CommandError::Unavailable(String::from("The WiFi device is not in station mode")))?;
+    let _ = tokio::time::timeout(SCAN_TIMEOUT, async {
+        // Give iwd a moment to flag the scan as started.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        while iwd_station.is_scanning().await.unwrap_or(false) {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    })
+    .await;
+
+    Station::new(session.clone()).await.map_err(|e| eyre!("{e}"))
+}
+
+fn find_network<'a>(station: &'a Station, ssid: &str) -> Option<&'a Network> {
+    station
+        .known_networks
+        .iter()
+        .chain(station.new_networks.iter())
+        .map(|(network, _)| network)
+        .find(|network| network.name == ssid)
+}
@@ -11,6 +11,7 @@ use crate::{action::Action, config::Config, tui::Event};
 pub mod fps;
 pub mod home;
 pub mod views;
+pub mod wizard;
 
 /// `Component` is a trait that represents a visual and interactive element of the user interface.
 ///
@@ -69,6 +70,7 @@ pub trait Component {
         let action = match event {
             Some(Event::Key(key_event)) => self.handle_key_event(key_event)?,
             Some(Event::Mouse(mouse_event)) => self.handle_mouse_event(mouse_event)?,
+            Some(Event::Paste(text)) => self.handle_paste(text)?,
             _ => None,
         };
         Ok(action)
@@ -99,6 +101,19 @@ pub trait Component {
         let _ = mouse; // to appease clippy
         Ok(None)
     }
+    /// Handle text pasted into the terminal and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The pasted text, delivered in one piece.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        let _ = text; // to appease clippy
+        Ok(None)
+    }
     /// Update the state of the component based on a received action. (REQUIRED)
     ///
     /// # Arguments
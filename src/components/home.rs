@@ -3,17 +3,21 @@ use crossterm::event::KeyEventKind;
 use ratatui::{prelude::*, style::palette::tailwind::SLATE, widgets::*};
 use tokio::sync::mpsc::UnboundedSender;
 
-use super::{views::{LocaleView, PasswordView, PinOut, SshView, TestViewComponent, ViewComponent, WifiView}, Component};
-use crate::{action::Action, config::Config, widgets::{ButtonState, TextButtonWidget}};
+use super::{views::{AboutView, HostnameView, KeyboardView, LocaleView, OverlaysView, PasswordView, PinOut, SshSecurityView, SshView, TestViewComponent, TimeView, UsersView, ViewComponent, WifiView}, Component};
+use crate::{action::Action, boards::identity::Board, config::Config, system::privilege::{is_root, SudoSession}, widgets::{ButtonState, SudoPrompt, TextButtonWidget}};
 
 // #[derive(Default)]
 pub struct Home {
     command_tx: Option<UnboundedSender<Action>>,
     config: Config,
+    board: Board,
     menu_list: Vec<MenuGroup>,
     menu_state: ListState,
     button_state: ButtonState,
     active: bool,
+    session: SudoSession,
+    /// Asks for the sudo password before a view that needs it opens.
+    sudo_prompt: Option<SudoPrompt>,
 }
 
 struct MenuGroup {
@@ -23,51 +27,66 @@ struct MenuGroup {
 }
 
 impl Home {
-    pub async fn new(sender: UnboundedSender<Action>) -> Self {
+    pub async fn new(sender: UnboundedSender<Action>, session: SudoSession) -> Self {
+        let board = Board::detect();
+
+        let mut menu_list = vec![
+            MenuGroup {
+                name: String::from("System"),
+                component: vec![
+                    Box::new(PasswordView::init()),
+                    Box::new(UsersView::init(&session)),
+                    Box::new(SshView::init(sender.clone(), &session)),
+                    Box::new(SshSecurityView::init(&session)),
+                    Box::new(HostnameView::init(&board, &session)),
+                    Box::new(LocaleView::init(&session)),
+                    Box::new(TimeView::init(&session)),
+                    Box::new(KeyboardView::init(&session)),
+                ],
+                state: ListState::default(),
+            },
+            MenuGroup {
+                name: String::from("Interface"),
+                component: vec![
+                    // Box::new(WifiView::init(sender).await),
+                    Box::new(PinOut::init(&board, &session)),
+                    Box::new(OverlaysView::init(&session)),
+                    // Box::new(TestViewComponent::new("Item6")),
+                ],
+                state: ListState::default(),
+            },
+        ];
+        if board.kind.has_wifi() {
+            menu_list.push(MenuGroup {
+                name: String::from("Network"),
+                component: vec![
+                    Box::new(WifiView::init(sender).await),
+                    // Box::new(TestViewComponent::new("Item8")),
+                    // Box::new(TestViewComponent::new("Item9")),
+                ],
+                state: ListState::default(),
+            });
+        }
+        menu_list.push(MenuGroup {
+            name: String::from("About"),
+            component: vec![
+                Box::new(AboutView::init(board.clone())),
+                Box::new(TestViewComponent::new("Item11")),
+                Box::new(TestViewComponent::new("Item12")),
+            ],
+            state: ListState::default(),
+        });
+
         Self {
             command_tx: None, 
             config: Config::default(),
-            menu_list: vec![
-                MenuGroup {
-                    name: String::from("System"),
-                    component: vec![
-                        Box::new(PasswordView::init()),
-                        Box::new(SshView::init()),
-                        Box::new(LocaleView::init()),
-                    ],
-                    state: ListState::default(),
-                },
-                MenuGroup {
-                    name: String::from("Interface"),
-                    component: vec![
-                        // Box::new(WifiView::init(sender).await),
-                        Box::new(PinOut::init()),
-                        // Box::new(TestViewComponent::new("Item6")),
-                    ],
-                    state: ListState::default(),
-                },
-                MenuGroup {
-                    name: String::from("Network"),
-                    component: vec![
-                        Box::new(WifiView::init(sender).await),
-                        // Box::new(TestViewComponent::new("Item8")),
-                        // Box::new(TestViewComponent::new("Item9")),
-                    ],
-                    state: ListState::default(),
-                },
-                MenuGroup {
-                    name: String::from("About"),
-                    component: vec![
-                        Box::new(TestViewComponent::new("Item10")),
-                        Box::new(TestViewComponent::new("Item11")),
-                        Box::new(TestViewComponent::new("Item12")),
-                    ],
-                    state: ListState::default(),
-                }
-            ],
+            board,
+            menu_list,
             menu_state: ListState::default(),
             button_state: ButtonState::Selected,
             active: false,
+            session,
+            sudo_prompt: None,
         }
     }
 
@@ -154,6 +173,11 @@ impl Component for Home {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        for group in self.menu_list.iter_mut() {
+            for view in group.component.iter_mut() {
+                view.register_config_handler(&config);
+            }
+        }
         self.config = config;
         Ok(())
     }
@@ -169,9 +193,38 @@ impl Component for Home {
             Action::BackToMenu => {
                 self.active = false;
             }
+            Action::ForcePasswordChange { .. } => {
+                let position = self.menu_list.iter().enumerate().find_map(|(group, menu)| {
+                    menu.component.iter().position(|view| view.is_password()).map(|item| (group, item))
+                });
+                if let Some((group, item)) = position {
+                    self.reset_select();
+                    self.menu_state.select(Some(group));
+                    self.menu_list[group].state.select(Some(item));
+                    self.button_state = ButtonState::Normal;
+                    self.active = true;
+                }
+            }
             _ => {}
         }
 
+        // Results of background work reach the view that started it, even after it was left.
+        let background = matches!(
+            action,
+            Action::WifiConnected { .. }
+                | Action::KnownNetworkChanged(_)
+                | Action::UpdateWifiState(_)
+                | Action::SshStatus(_)
+        );
+        if background {
+            for view in self.menu_list.iter_mut().flat_map(|group| group.component.iter_mut()) {
+                if let Some(action) = view.update(action.clone())? {
+                    return Ok(Some(action));
+                }
+            }
+            return Ok(None);
+        }
+
         if self.active {
             if let Some(selected_group) = self.menu_state.selected() {
                 if let Some(selected_item) = self.menu_list[selected_group].state.selected() {
@@ -188,6 +241,14 @@ impl Component for Home {
         if key.kind != KeyEventKind::Press {
             return Ok(None);
         }
+        if let Some(prompt) = self.sudo_prompt.as_mut() {
+            // The view opens either way, without the password it reports what it cannot change.
+            if prompt.handle_key(key.code) {
+                self.sudo_prompt = None;
+                self.active = true;
+            }
+            return Ok(None);
+        }
         if self.active {
             if let Some(selected_group) = self.menu_state.selected() {
                 if let Some(selected_item) = self.menu_list[selected_group].state.selected() {
@@ -203,8 +264,13 @@ impl Component for Home {
                 // Handle menu activation first
                 if let Some(selected_group) = self.menu_state.selected() {
                     let group = &mut self.menu_list[selected_group];
-                    if group.state.selected().is_some() {
-                        self.active = true;
+                    if let Some(selected_item) = group.state.selected() {
+                        let view = &group.component[selected_item];
+                        if view.needs_root() && !is_root() && !self.session.is_authenticated() {
+                            self.sudo_prompt = Some(SudoPrompt::new(self.session.clone()));
+                        } else {
+                            self.active = true;
+                        }
                         return Ok(None);
                     }
                 }
@@ -221,6 +287,22 @@ impl Component for Home {
         Ok(None)
     }
 
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        if let Some(prompt) = self.sudo_prompt.as_mut() {
+            prompt.handle_paste(&text);
+            return Ok(None);
+        }
+        if self.active {
+            if let Some(selected_group) = self.menu_state.selected() {
+                if let Some(selected_item) = self.menu_list[selected_group].state.selected() {
+                    return self.menu_list[selected_group].component[selected_item]
+                        .handle_paste(text);
+                }
+            }
+        }
+        Ok(None)
+    }
+
     fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
         let [header_area, main_area, footer_area] = Layout::default()
             .direction(Direction::Vertical)
@@ -233,7 +315,8 @@ impl Component for Home {
         let header = Paragraph::new(
             Line::from_iter([
                 "Beagle-Config ".into(),
-                VERSION_MESSAGE,
+                VERSION_MESSAGE.into(),
+                format!(" - {}", self.board.name()),
             ]).centered());
         frame.render_widget(&header, header_area);
 
@@ -321,6 +404,9 @@ impl Component for Home {
                 .render(c_header_area, frame.buffer_mut());
             component.draw(frame, c_main_area)?;
         }
+        if let Some(prompt) = &self.sudo_prompt {
+            prompt.draw(frame, area);
+        }
         
         Ok(())  
     }
@@ -1,26 +1,75 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
+
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
 use color_eyre::Result;
 use ratatui::{layout::Rect, Frame};
 
-use crate::action::Action;
+use crate::{action::Action, config::Config, provision::Change};
 
 pub mod test;
+#[cfg(test)]
+mod test_util;
 pub mod password;
 pub mod ssh;
+pub mod ssh_security;
+pub mod users;
+pub mod hostname;
 pub mod locale;
+pub mod time;
+pub mod keyboard;
 pub mod wifi;
 pub mod pinout;
+pub mod about;
+pub mod overlays;
 
 pub use password::PasswordView;
 pub use ssh::SshView;
+pub use ssh_security::SshSecurityView;
+pub use users::UsersView;
+pub use hostname::HostnameView;
 pub use locale::LocaleView;
+pub use time::TimeView;
+pub use keyboard::KeyboardView;
 pub use wifi::WifiView;
 pub use test::TestViewComponent;
 pub use pinout::PinOut;
+pub use about::AboutView;
+pub use overlays::OverlaysView;
+
+/// Changes kept by the views of the setup wizard instead of being made, so its summary can make
+/// them all at once. Clones share the same list.
+#[derive(Clone, Default)]
+pub struct Deferred(Arc<Mutex<Vec<Change>>>);
+
+impl Deferred {
+    /// Keep `change`, in place of an earlier change of the same setting.
+    pub fn push(&self, change: Change) {
+        let mut changes = self.0.lock().unwrap();
+        match changes.iter_mut().find(|c| mem::discriminant(*c) == mem::discriminant(&change)) {
+            Some(earlier) => *earlier = change,
+            None => changes.push(change),
+        }
+    }
+
+    /// Drop the kept change of the same setting as `change`, once it was made or is no longer wanted.
+    pub fn remove(&self, change: &Change) {
+        self.0.lock().unwrap().retain(|c| mem::discriminant(c) != mem::discriminant(change));
+    }
+
+    /// The kept changes, in the order they were first made.
+    pub fn changes(&self) -> Vec<Change> {
+        self.0.lock().unwrap().clone()
+    }
+}
 
 pub trait ViewComponent {
     fn title(&self) -> &str;
     #[allow(unused_variables)]
+    fn register_config_handler(&mut self, config: &Config) {}
+    #[allow(unused_variables)]
     fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
         if key.kind != KeyEventKind::Press {
             return Ok(None);
@@ -34,9 +83,24 @@ pub trait ViewComponent {
         Ok(None)
     }
     #[allow(unused_variables)]
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        Ok(None)
+    }
+    #[allow(unused_variables)]
     #[allow(dead_code)]
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
         Ok(None)
     }
     fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()>;
+    /// Whether this is the view that changes passwords, which a stale password forces open.
+    fn is_password(&self) -> bool {
+        false
+    }
+    /// Whether the view changes the system through sudo, so the password is asked for before it opens.
+    fn needs_root(&self) -> bool {
+        false
+    }
+    /// Keep the changes the view would make in `deferred` from now on, instead of making them.
+    #[allow(unused_variables)]
+    fn defer_changes(&mut self, deferred: Deferred) {}
 }
\ No newline at end of file
@@ -0,0 +1,69 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use color_eyre::Result;
+use ratatui::{
+    layout::Rect,
+    style::{palette::tailwind, Style},
+    text::{Line, Span},
+    widgets::{Block, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{action::Action, boards::identity::Board};
+
+use super::ViewComponent;
+
+pub struct AboutView {
+    title: String,
+    board: Board,
+}
+
+impl AboutView {
+    pub fn init(board: Board) -> Self {
+        AboutView {
+            title: String::from("Board"),
+            board,
+        }
+    }
+}
+
+fn field<'a>(name: &'a str, value: impl Into<String>) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(format!("{name:<12}"), Style::new().fg(tailwind::SLATE.c300)),
+        Span::raw(value.into()),
+    ])
+}
+
+impl ViewComponent for AboutView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        match key.code {
+            KeyCode::Backspace | KeyCode::Enter => Ok(Some(Action::BackToMenu)),
+            _ => Ok(None),
+        }
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let board = &self.board;
+        let mut lines = vec![
+            field("Model", board.name()),
+            field("Board", format!("{:?}", board.kind)),
+            field("Pinout", board.kind.description_id()),
+            field("Compatible", board.compatible.join(", ")),
+        ];
+        match &board.eeprom {
+            Some(eeprom) => {
+                lines.push(field("EEPROM", eeprom.board_name.clone()));
+                lines.push(field("Revision", eeprom.version.clone()));
+                lines.push(field("Serial", eeprom.serial.clone()));
+            }
+            None => lines.push(field("EEPROM", "-")),
+        }
+
+        let info = Paragraph::new(lines).block(Block::new().padding(Padding::horizontal(2)));
+        f.render_widget(info, area);
+        Ok(())
+    }
+}
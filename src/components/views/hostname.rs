@@ -0,0 +1,350 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    boards::identity::Board,
+    provision::Change,
+    system::{
+        hostname::{self, Hostnames, Names},
+        privilege::{self, Privilege, SudoSession},
+    },
+};
+
+use super::{Deferred, ViewComponent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Static,
+    Pretty,
+    Derive,
+}
+
+impl Focus {
+    const ALL: [Focus; 3] = [Focus::Static, Focus::Pretty, Focus::Derive];
+
+    fn next(self, step: isize) -> Self {
+        let i = Self::ALL.iter().position(|f| *f == self).unwrap_or_default() as isize;
+        Self::ALL[(i + step).rem_euclid(Self::ALL.len() as isize) as usize]
+    }
+}
+
+pub struct HostnameView {
+    title: String,
+    root: PathBuf,
+    hostnames: Box<dyn Hostnames>,
+    /// Writes `/etc/hosts`, `None` leaves it as it is.
+    privilege: Option<Box<dyn Privilege>>,
+    /// The name made from the EEPROM serial number, if the board has one.
+    derived: Option<String>,
+    current: Result<Names, String>,
+    static_input: String,
+    pretty_input: String,
+    focus: Focus,
+    message: Option<Span<'static>>,
+    /// Where the names go instead of being set, in the setup wizard.
+    deferred: Option<Deferred>,
+}
+
+impl HostnameView {
+    pub fn init(board: &Board, session: &SudoSession) -> Self {
+        let root = PathBuf::from("/");
+        let hostnames = hostname::detect(&root, privilege::detect_interactive(session));
+        Self::with_backend(root, hostnames, privilege::detect_interactive(session), hostname::from_serial(board))
+    }
+
+    /// Name the system below `root` through `hostnames`, offering `derived` as a unique name.
+    pub fn with_backend(
+        root: PathBuf,
+        hostnames: Box<dyn Hostnames>,
+        privilege: Option<Box<dyn Privilege>>,
+        derived: Option<String>,
+    ) -> Self {
+        let mut view = HostnameView {
+            title: String::from("Hostname"),
+            root,
+            hostnames,
+            privilege,
+            derived,
+            current: Ok(Names::default()),
+            static_input: String::new(),
+            pretty_input: String::new(),
+            focus: Focus::Static,
+            message: None,
+            deferred: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.current = self.hostnames.names().map_err(|e| e.to_string());
+        let names = self.current.clone().unwrap_or_default();
+        self.static_input = names.static_name;
+        self.pretty_input = names.pretty;
+    }
+
+    fn input(&mut self) -> Option<&mut String> {
+        match self.focus {
+            Focus::Static => Some(&mut self.static_input),
+            Focus::Pretty => Some(&mut self.pretty_input),
+            Focus::Derive => None,
+        }
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.message = Some(Span::styled(message, Style::new().fg(tailwind::RED.c400)));
+    }
+
+    /// Point `/etc/hosts` at the new static name, then set whichever names changed.
+    fn apply(&mut self) {
+        let (name, pretty) = (self.static_input.trim().to_string(), self.pretty_input.trim().to_string());
+        if let Err(e) = hostname::validate(&name) {
+            return self.set_error(e.to_string());
+        }
+        if let Some(deferred) = self.deferred.clone() {
+            return self.defer(&deferred, name, pretty);
+        }
+        let result = self.set_names(&name, &pretty);
+        self.reload();
+        match result {
+            Ok(()) => {
+                let done = format!("The hostname is {name}, new SSH and mDNS sessions use it");
+                self.message = Some(Span::styled(done, Style::new().fg(tailwind::LIME.c500)));
+            }
+            Err(e) => self.set_error(e.to_string()),
+        }
+    }
+
+    /// Keep the names that differ from the current ones in `deferred`, and forget the others.
+    fn defer(&mut self, deferred: &Deferred, name: String, pretty: String) {
+        let current = self.current.clone().unwrap_or_default();
+        let same_name = name == current.static_name && hostname::hosts_resolves(&self.root, &name);
+        let same_pretty = pretty == current.pretty;
+        let changes = [(Change::Hostname(name.clone()), same_name), (Change::PrettyHostname(pretty), same_pretty)];
+        for (change, same) in changes {
+            if same {
+                deferred.remove(&change);
+            } else {
+                deferred.push(change);
+            }
+        }
+        let done = format!("The hostname becomes {name} when the summary is confirmed");
+        self.message = Some(Span::styled(done, Style::new().fg(tailwind::LIME.c500)));
+    }
+
+    fn set_names(&self, name: &str, pretty: &str) -> Result<()> {
+        let current = self.current.clone().unwrap_or_default();
+        hostname::update_hosts(&self.root, &current.static_name, name, self.privilege.as_deref())?;
+        if name != current.static_name {
+            self.hostnames.set_static(name)?;
+        }
+        if pretty != current.pretty {
+            self.hostnames.set_pretty(pretty)?;
+        }
+        Ok(())
+    }
+
+    fn field_line(&self, label: &str, value: &str, focus: Focus) -> Line<'static> {
+        let focused = self.focus == focus;
+        let style = if focused { Style::new().fg(tailwind::BLUE.c400) } else { Style::new() };
+        Line::from(vec![
+            Span::styled(format!("{label:<14}"), style),
+            Span::raw(value.to_string()),
+            Span::raw(if focused { "█" } else { "" }),
+        ])
+    }
+}
+
+impl ViewComponent for HostnameView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn defer_changes(&mut self, deferred: Deferred) {
+        self.deferred = Some(deferred);
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        self.message = None;
+        match key.code {
+            KeyCode::Esc => {
+                self.reload();
+                return Ok(Some(Action::BackToMenu));
+            }
+            KeyCode::Tab | KeyCode::Down => self.focus = self.focus.next(1),
+            KeyCode::BackTab | KeyCode::Up => self.focus = self.focus.next(-1),
+            KeyCode::Enter if self.focus == Focus::Derive => match self.derived.clone() {
+                Some(derived) => {
+                    self.static_input = derived;
+                    self.focus = Focus::Static;
+                }
+                None => self.set_error(String::from("This board has no serial number in its EEPROM")),
+            },
+            KeyCode::Enter => self.apply(),
+            KeyCode::Char(c) => {
+                if let Some(input) = self.input() {
+                    input.push(c);
+                }
+            }
+            KeyCode::Backspace => {
+                if let Some(input) = self.input() {
+                    input.pop();
+                }
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        if let Some(input) = self.input() {
+            input.push_str(text.trim());
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let area = Block::new().padding(Padding::horizontal(2)).inner(area);
+        let [current_area, form_area, message_area, help_area] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let label = |text: &'static str| Span::raw(format!("{text:<14}")).bold();
+        let current = match &self.current {
+            Ok(names) => {
+                let pretty = if names.pretty.is_empty() { String::from("(none)") } else { names.pretty.clone() };
+                let hosts = if hostname::hosts_resolves(&self.root, &names.static_name) {
+                    Span::styled(format!("127.0.1.1 {} ✓", names.static_name), Style::new().fg(tailwind::LIME.c500))
+                } else {
+                    Span::styled("does not resolve the hostname, Enter fixes it", Style::new().fg(tailwind::AMBER.c400))
+                };
+                vec![
+                    Line::from(vec![label("Hostname"), Span::raw(names.static_name.clone())]),
+                    Line::from(vec![label("Pretty name"), Span::raw(pretty)]),
+                    Line::from(vec![label("/etc/hosts"), hosts]),
+                ]
+            }
+            Err(e) => vec![Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400)))],
+        };
+        f.render_widget(Paragraph::new(current), current_area);
+
+        let mut lines = vec![self.field_line("New hostname", &self.static_input, Focus::Static)];
+        lines.push(match hostname::validate(self.static_input.trim()) {
+            Err(e) => Line::from(e.to_string()).fg(tailwind::RED.c400),
+            Ok(()) => Line::default(),
+        });
+        lines.push(self.field_line("Pretty name", &self.pretty_input, Focus::Pretty));
+        lines.push(Line::default());
+        let derive = match &self.derived {
+            Some(derived) => format!("[ Derive from serial number: {derived} ]"),
+            None => String::from("[ Derive from serial number: no EEPROM serial ]"),
+        };
+        let focused = self.focus == Focus::Derive;
+        let style = if focused { Style::new().fg(tailwind::BLUE.c400).bold() } else { Style::new() };
+        lines.push(Line::from(Span::styled(derive, style)));
+        f.render_widget(Paragraph::new(lines), form_area);
+
+        if let Some(message) = &self.message {
+            f.render_widget(Paragraph::new(Line::from(message.clone())), message_area);
+        }
+        let help = "Tab/↓↑: next field  Enter: apply (on the button: fill in)  Esc: back";
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        components::views::test_util::{press, type_text, Fixture},
+        system::hostname::EtcHostname,
+    };
+
+    #[test]
+    fn test_rename() {
+        let fixture = Fixture::new(&[
+            ("etc/hostname", "BeagleBone\n"),
+            ("etc/hosts", "127.0.0.1\tlocalhost\n127.0.1.1\tBeagleBone\n"),
+        ]);
+        let mut view = HostnameView::with_backend(
+            fixture.root(),
+            Box::new(EtcHostname::new(fixture.root(), fixture.privilege())),
+            fixture.privilege(),
+            Some(String::from("beagleplay-4521p0001234")),
+        );
+        assert_eq!(view.static_input, "BeagleBone");
+
+        press(&mut view, KeyCode::Char('_'));
+        press(&mut view, KeyCode::Enter);
+        assert!(fixture.recorder.commands().is_empty());
+
+        press(&mut view, KeyCode::BackTab);
+        press(&mut view, KeyCode::Enter);
+        assert_eq!((view.focus, view.static_input.as_str()), (Focus::Static, "beagleplay-4521p0001234"));
+        press(&mut view, KeyCode::Enter);
+
+        let etc = fixture.path("etc");
+        assert_eq!(
+            fixture.recorder.commands(),
+            [
+                format!("tee {}/hosts", etc.display()),
+                format!("tee {}/hostname", etc.display()),
+                String::from("hostname beagleplay-4521p0001234"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_defer() {
+        let fixture = Fixture::new(&[
+            ("etc/hostname", "BeagleBone\n"),
+            ("etc/hosts", "127.0.0.1\tlocalhost\n127.0.1.1\tBeagleBone\n"),
+        ]);
+        let mut view = HostnameView::with_backend(
+            fixture.root(),
+            Box::new(EtcHostname::new(fixture.root(), fixture.privilege())),
+            fixture.privilege(),
+            None,
+        );
+        let deferred = Deferred::default();
+        view.defer_changes(deferred.clone());
+
+        type_text(&mut view, "-2");
+        press(&mut view, KeyCode::Tab);
+        type_text(&mut view, "Lab bench");
+        press(&mut view, KeyCode::Enter);
+        assert!(fixture.recorder.commands().is_empty());
+        assert_eq!(
+            deferred.changes(),
+            [Change::Hostname(String::from("BeagleBone-2")), Change::PrettyHostname(String::from("Lab bench"))]
+        );
+
+        // Going back to the current name drops it again.
+        press(&mut view, KeyCode::BackTab);
+        press(&mut view, KeyCode::Backspace);
+        press(&mut view, KeyCode::Backspace);
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(deferred.changes(), [Change::PrettyHostname(String::from("Lab bench"))]);
+    }
+}
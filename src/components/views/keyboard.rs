@@ -0,0 +1,303 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    system::{
+        keyboard::{self, ConsoleFont, Entry, Keyboard, KeyboardLayout, Layouts},
+        privilege::{self, Privilege, SudoSession},
+    },
+    widgets::FilterList,
+};
+
+use super::ViewComponent;
+
+/// The variant entry standing for none.
+const DEFAULT_VARIANT: &str = "(default)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Layouts,
+    Variants,
+    Fonts,
+}
+
+pub struct KeyboardView {
+    title: String,
+    root: PathBuf,
+    keyboard: Box<dyn Keyboard>,
+    /// Writes `/etc/default/console-setup`, `None` leaves the font as it is.
+    privilege: Option<Box<dyn Privilege>>,
+    current: Result<KeyboardLayout, String>,
+    font: Option<ConsoleFont>,
+    all: Layouts,
+    fonts_available: Vec<ConsoleFont>,
+    layouts: FilterList,
+    variants: FilterList,
+    fonts: FilterList,
+    /// The layout whose variants are listed.
+    layout: String,
+    focus: Focus,
+    message: Option<Span<'static>>,
+}
+
+impl KeyboardView {
+    pub fn init(session: &SudoSession) -> Self {
+        let root = PathBuf::from("/");
+        let keyboard = keyboard::detect(&root, privilege::detect_interactive(session));
+        Self::with_backend(root, keyboard, privilege::detect_interactive(session))
+    }
+
+    /// Configure the console of the system below `root`, the layout through `keyboard`.
+    pub fn with_backend(root: PathBuf, keyboard: Box<dyn Keyboard>, privilege: Option<Box<dyn Privilege>>) -> Self {
+        let mut view = KeyboardView {
+            title: String::from("Keyboard"),
+            root,
+            keyboard,
+            privilege,
+            current: Ok(KeyboardLayout::default()),
+            font: None,
+            all: Layouts::default(),
+            fonts_available: Vec::new(),
+            layouts: FilterList::default(),
+            variants: FilterList::default(),
+            fonts: FilterList::default(),
+            layout: String::new(),
+            focus: Focus::Layouts,
+            message: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.current = self.keyboard.layout().map_err(|e| e.to_string());
+        self.font = keyboard::console_font(&self.root);
+        self.all = keyboard::layouts(&self.root).unwrap_or_default();
+        self.fonts_available = keyboard::console_fonts(&self.root).unwrap_or_default();
+
+        self.layouts.set_items(self.all.layouts.iter().map(item).collect());
+        self.fonts.set_items(self.fonts_available.iter().map(ToString::to_string).collect());
+        let current = self.current.clone().unwrap_or_default();
+        if let Some(entry) = self.all.layouts.iter().find(|e| e.name == current.layout) {
+            self.layouts.select(&item(entry));
+        }
+        self.show_variants();
+        let variant = self.all.variants.get(&current.layout).and_then(|v| v.iter().find(|e| e.name == current.variant));
+        if let Some(entry) = variant {
+            self.variants.select(&item(entry));
+        }
+        if let Some(font) = &self.font {
+            self.fonts.select(&font.to_string());
+        }
+    }
+
+    /// List the variants of the selected layout, if it changed.
+    fn show_variants(&mut self) {
+        let layout = self.layouts.selected().map(name).unwrap_or_default();
+        if layout != self.layout {
+            let variants = self.all.variants.get(&layout).into_iter().flatten().map(item);
+            self.variants = FilterList::new(std::iter::once(String::from(DEFAULT_VARIANT)).chain(variants).collect());
+            self.layout = layout;
+        }
+    }
+
+    fn report(&mut self, result: Result<()>, done: String) {
+        self.reload();
+        self.message = Some(match result {
+            Ok(()) => Span::styled(done, Style::new().fg(tailwind::LIME.c500)),
+            Err(e) => Span::styled(e.to_string(), Style::new().fg(tailwind::RED.c400)),
+        });
+    }
+
+    fn set_layout(&mut self) {
+        let variant = self.variants.selected().map(name).unwrap_or_default();
+        let layout = KeyboardLayout { layout: self.layout.clone(), variant };
+        let result = self.keyboard.set_layout(&layout);
+        self.report(result, format!("The keyboard layout is {}", describe(&layout)));
+    }
+
+    fn set_font(&mut self) {
+        let Some(selected) = self.fonts.selected() else { return };
+        let Some(font) = self.fonts_available.iter().find(|f| f.to_string() == selected).cloned() else { return };
+        let result = keyboard::set_console_font(&self.root, &font, self.privilege.as_deref());
+        self.report(result, format!("The console font is {font}"));
+    }
+
+    fn list(&mut self) -> &mut FilterList {
+        match self.focus {
+            Focus::Layouts => &mut self.layouts,
+            Focus::Variants => &mut self.variants,
+            Focus::Fonts => &mut self.fonts,
+        }
+    }
+}
+
+/// A list entry, searchable by name and description, e.g. `de        German`.
+fn item(entry: &Entry) -> String {
+    format!("{:<10}{}", entry.name, entry.description)
+}
+
+/// The name at the start of a list entry, empty for the default variant.
+fn name(item: &str) -> String {
+    if item == DEFAULT_VARIANT {
+        return String::new();
+    }
+    item.split_whitespace().next().unwrap_or_default().to_string()
+}
+
+fn describe(layout: &KeyboardLayout) -> String {
+    if layout.variant.is_empty() {
+        layout.layout.clone()
+    } else {
+        format!("{} ({})", layout.layout, layout.variant)
+    }
+}
+
+impl ViewComponent for KeyboardView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        self.message = None;
+        if self.list().handle_key(key.code) {
+            self.show_variants();
+            return Ok(None);
+        }
+        match (self.focus, key.code) {
+            (_, KeyCode::Esc | KeyCode::Backspace) => return Ok(Some(Action::BackToMenu)),
+            (Focus::Layouts, KeyCode::Tab | KeyCode::Right | KeyCode::Enter) => self.focus = Focus::Variants,
+            (Focus::Variants, KeyCode::Tab) => self.focus = Focus::Fonts,
+            (Focus::Variants, KeyCode::BackTab | KeyCode::Left) => self.focus = Focus::Layouts,
+            (Focus::Variants, KeyCode::Enter) => self.set_layout(),
+            (Focus::Fonts, KeyCode::Tab) => self.focus = Focus::Layouts,
+            (Focus::Fonts, KeyCode::BackTab) => self.focus = Focus::Variants,
+            (Focus::Fonts, KeyCode::Enter) => self.set_font(),
+            (Focus::Layouts, KeyCode::BackTab) => self.focus = Focus::Fonts,
+            (_, KeyCode::Char('r')) => self.reload(),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        self.list().handle_paste(&text);
+        self.show_variants();
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let area = Block::new().padding(Padding::horizontal(2)).inner(area);
+        let [header_area, lists_area, message_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let label = |text: &'static str| Span::raw(format!("{text:<14}")).bold();
+        let layout = match &self.current {
+            Ok(layout) if layout.layout.is_empty() => Span::styled("not set", Style::new().fg(tailwind::SLATE.c400)),
+            Ok(layout) => Span::raw(describe(layout)),
+            Err(e) => Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400)),
+        };
+        let font = match &self.font {
+            Some(font) => Span::raw(font.to_string()),
+            None => Span::styled("kernel default", Style::new().fg(tailwind::SLATE.c400)),
+        };
+        let header = vec![
+            Line::from(vec![label("Layout"), layout]),
+            Line::from(vec![label("Console font"), font]),
+        ];
+        f.render_widget(Paragraph::new(header), header_area);
+
+        let [layouts_area, variants_area, fonts_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1), Constraint::Length(24)]).areas(lists_area);
+        let block = |title: &'static str, focused: bool| {
+            let border = if focused { Style::new().fg(tailwind::BLUE.c400) } else { Style::new() };
+            Block::default().borders(Borders::ALL).border_style(border).title(title)
+        };
+        self.layouts.draw(f, layouts_area, block("Layout", self.focus == Focus::Layouts), Line::from);
+        self.variants.draw(f, variants_area, block("Variant", self.focus == Focus::Variants), Line::from);
+        self.fonts.draw(f, fonts_area, block("Console font", self.focus == Focus::Fonts), Line::from);
+
+        if let Some(message) = &self.message {
+            f.render_widget(Paragraph::new(Line::from(message.clone())), message_area);
+        }
+        let help = match self.focus {
+            Focus::Layouts => "Enter/Tab: variants  /: search  r: reload  Esc: back",
+            Focus::Variants => "Enter: set layout  Tab: fonts  /: search  Esc: back",
+            Focus::Fonts => "Enter: set font  Tab: layouts  /: search  Esc: back",
+        };
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        components::views::test_util::{press, type_text, Fixture},
+        system::keyboard::DefaultKeyboard,
+    };
+
+    #[test]
+    fn test_pick_layout_and_font() {
+        let fixture = Fixture::new(&[
+            (
+                "usr/share/X11/xkb/rules/base.lst",
+                "! layout\n  us              English (US)\n  de              German\n  fr              French\n\n\
+                 ! variant\n  nodeadkeys      de: German (no dead keys)\n  neo             de: German (Neo 2)\n",
+            ),
+            ("usr/share/consolefonts/Lat15-Fixed16.psf.gz", ""),
+            ("usr/share/consolefonts/Lat15-Terminus32x16.psf.gz", ""),
+            ("etc/default/keyboard", "XKBLAYOUT=\"us\"\n"),
+        ]);
+        let keyboard = DefaultKeyboard::new(fixture.root(), fixture.privilege());
+        let mut view = KeyboardView::with_backend(fixture.root(), Box::new(keyboard), fixture.privilege());
+        assert_eq!(view.layout, "us");
+
+        // Descriptions are searchable too.
+        type_text(&mut view, "/german");
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(view.layout, "de");
+        press(&mut view, KeyCode::Enter);
+        press(&mut view, KeyCode::End);
+        press(&mut view, KeyCode::Enter);
+
+        press(&mut view, KeyCode::Tab);
+        press(&mut view, KeyCode::End);
+        press(&mut view, KeyCode::Enter);
+
+        let etc = fixture.path("etc/default");
+        assert_eq!(
+            fixture.recorder.commands(),
+            [
+                format!("tee {}/keyboard", etc.display()),
+                String::from("setupcon --keyboard-only"),
+                format!("tee {}/console-setup", etc.display()),
+                String::from("setupcon --font-only"),
+            ]
+        );
+        assert_eq!(name("neo       German (Neo 2)"), "neo");
+        assert_eq!(name(DEFAULT_VARIANT), "");
+    }
+}
@@ -1,21 +1,170 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::{layout::{Constraint, Direction, Layout, Rect}, widgets::{Block, Borders, Widget}, Frame};
-use color_eyre::{eyre::Ok, Result};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Padding, Paragraph},
+    Frame,
+};
 
-use crate::{action::Action, widgets::{Switch, SwitchState}};
+use crate::{
+    action::Action,
+    provision::Change,
+    system::{
+        locale::{self, LocaleGen, Locales, Supported, SystemLocales},
+        privilege::{self, Privilege, SudoSession},
+    },
+    widgets::FilterList,
+};
 
-use super::ViewComponent;
+use super::{Deferred, ViewComponent};
 
 pub struct LocaleView {
     title: String,
-    switch_focus: SwitchState,
+    root: PathBuf,
+    locales: Box<dyn Locales>,
+    /// How to become root, `None` leaves the locales read-only.
+    privilege: Option<Box<dyn Privilege>>,
+    /// Charset of every supported locale, by name.
+    charsets: Result<HashMap<String, String>, String>,
+    /// Names as `locale -a` prints them.
+    generated: Vec<String>,
+    default: Option<String>,
+    list: FilterList,
+    /// Locales to generate with the next `g`.
+    marked: Vec<String>,
+    status: Option<Span<'static>>,
+    /// Where the default goes instead of being set, in the setup wizard. The locale is still
+    /// generated right away.
+    deferred: Option<Deferred>,
 }
 
 impl LocaleView {
-    pub fn init() -> Self {
-        LocaleView {
-            title: String::from("locale"),
-            switch_focus: SwitchState::Off,
+    pub fn init(session: &SudoSession) -> Self {
+        Self::with_root(PathBuf::from("/"), Box::new(SystemLocales), privilege::detect_interactive(session))
+    }
+
+    /// Manage the locales of the system below `root` instead of `/`, the generated and default
+    /// ones through `locales`.
+    pub fn with_root(root: PathBuf, locales: Box<dyn Locales>, privilege: Option<Box<dyn Privilege>>) -> Self {
+        let mut view = LocaleView {
+            title: String::from("Locale"),
+            root,
+            locales,
+            privilege,
+            charsets: Ok(HashMap::new()),
+            generated: Vec::new(),
+            default: None,
+            list: FilterList::default(),
+            marked: Vec::new(),
+            status: None,
+            deferred: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        let supported =
+            locale::supported(&self.root).map_err(|e| format!("Unable to list the supported locales: {e}"));
+        let names = supported.as_ref().map(|s| s.iter().map(|l| l.name.clone()).collect()).unwrap_or_default();
+        self.charsets = supported.map(|s| s.into_iter().map(|l| (l.name, l.charset)).collect());
+        self.generated = self.locales.generated().unwrap_or_default();
+        self.default = locale::default_locale(&self.root);
+        self.list.set_items(names);
+        if self.list.selected().is_none() {
+            if let Some(default) = self.default.clone() {
+                self.list.select(&default);
+            }
+        }
+    }
+
+    fn is_generated(&self, name: &str) -> bool {
+        self.generated.iter().any(|g| locale::same_locale(g, name))
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::RED.c400)));
+    }
+
+    fn set_done(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::LIME.c500)));
+    }
+
+    fn toggle_mark(&mut self, name: String) {
+        if self.is_generated(&name) {
+            return self.set_error(format!("{name} is already generated"));
+        }
+        match self.marked.iter().position(|m| *m == name) {
+            Some(i) => {
+                self.marked.remove(i);
+            }
+            None => self.marked.push(name),
+        }
+    }
+
+    /// Add the marked locales and `extra` to `/etc/locale.gen` and generate them.
+    fn generate(&mut self, extra: Option<&str>) -> Result<Vec<String>, String> {
+        let privilege =
+            self.privilege.as_deref().ok_or("Changing the locale needs root, run beagle-config with sudo")?;
+        let charsets = self.charsets.as_ref().map_err(Clone::clone)?;
+        let mut names = self.marked.clone();
+        names.extend(extra.filter(|e| !names.iter().any(|n| n == e)).map(String::from));
+        if names.is_empty() {
+            return Ok(names);
+        }
+
+        let mut locale_gen = LocaleGen::load(&self.root).map_err(|e| e.to_string())?;
+        for name in &names {
+            let charset = charsets.get(name).cloned().unwrap_or_default();
+            locale_gen.enable(&Supported { name: name.clone(), charset });
+        }
+        locale_gen.save_and_generate(privilege).map_err(|e| e.to_string())?;
+        self.marked.clear();
+        Ok(names)
+    }
+
+    fn generate_marked(&mut self) {
+        if self.marked.is_empty() {
+            return self.set_error(String::from("Mark the locales to generate with Space first"));
+        }
+        let result = self.generate(None);
+        self.reload();
+        match result {
+            Ok(names) => self.set_done(format!("Generated {}", names.join(", "))),
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    /// Make `name` the default, generating it first if needed.
+    fn set_default(&mut self, name: String) {
+        let missing = (!self.is_generated(&name)).then_some(name.as_str());
+        let result = self
+            .generate(missing)
+            .and_then(|_| {
+                if let Some(deferred) = &self.deferred {
+                    let change = Change::Locale(name.clone());
+                    if self.default.as_ref().is_some_and(|default| locale::same_locale(default, &name)) {
+                        deferred.remove(&change);
+                    } else {
+                        deferred.push(change);
+                    }
+                    return Ok(());
+                }
+                let privilege =
+                    self.privilege.as_deref().ok_or("Changing the locale needs root, run beagle-config with sudo")?;
+                self.locales.set_locale(&name, privilege).map_err(|e| e.to_string())
+            });
+        self.reload();
+        match result {
+            Ok(()) if self.deferred.is_some() => {
+                self.set_done(format!("{name} becomes the default locale when the summary is confirmed"))
+            }
+            Ok(()) => self.set_done(format!("{name} is the default locale, it applies from the next login")),
+            Err(e) => self.set_error(e),
         }
     }
 }
@@ -25,36 +174,172 @@ impl ViewComponent for LocaleView {
         &self.title
     }
 
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn defer_changes(&mut self, deferred: Deferred) {
+        self.deferred = Some(deferred);
+    }
+
     fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
-        match key.code {
-            KeyCode::Up => self.switch_focus = SwitchState::Off,
-            KeyCode::Down => self.switch_focus = SwitchState::On,
-            KeyCode::Backspace => return Ok(Some(Action::BackToMenu)),
-            _ => {},
+        self.status = None;
+        if self.list.handle_key(key.code) {
+            return Ok(None);
+        }
+        let selected = self.list.selected().map(String::from);
+        match (key.code, selected) {
+            (KeyCode::Esc | KeyCode::Backspace, _) => return Ok(Some(Action::BackToMenu)),
+            (KeyCode::Char('j'), _) => {
+                self.list.handle_key(KeyCode::Down);
+            }
+            (KeyCode::Char('k'), _) => {
+                self.list.handle_key(KeyCode::Up);
+            }
+            (KeyCode::Char('r'), _) => self.reload(),
+            (KeyCode::Char('g'), _) => self.generate_marked(),
+            (KeyCode::Char(' '), Some(name)) => self.toggle_mark(name),
+            (KeyCode::Enter, Some(name)) => self.set_default(name),
+            _ => {}
         }
+        Ok(None)
+    }
 
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        self.list.handle_paste(&text);
         Ok(None)
     }
 
     fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3),
-                Constraint::Min(0),
-            ]).split(area);
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Length(15),
-                Constraint::Min(0),
-            ]).split(chunks[0]);
-
-        Switch::new(self.switch_focus)
-            .labels("ON", "OFF")
-            .block(Block::default().borders(Borders::ALL))
-            .render(chunks[0], f.buffer_mut());
+        let area = Block::new().padding(Padding::horizontal(2)).inner(area);
+        let [header_area, list_area, status_area, help_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let charsets = match &self.charsets {
+            Ok(charsets) => charsets,
+            Err(e) => {
+                let message = Paragraph::new(vec![
+                    Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400))),
+                    Line::from("Press r to retry."),
+                ]);
+                f.render_widget(message, area);
+                return Ok(());
+            }
+        };
 
+        let header = Line::from(vec![
+            Span::raw("Default: ").bold(),
+            Span::raw(self.default.clone().unwrap_or_else(|| String::from("not set"))),
+            Span::styled(format!("   {} generated", self.generated.len()), Style::new().fg(tailwind::SLATE.c400)),
+            Span::styled(
+                if self.marked.is_empty() { String::new() } else { format!(", {} marked", self.marked.len()) },
+                Style::new().fg(tailwind::AMBER.c400),
+            ),
+        ]);
+        f.render_widget(Paragraph::new(header), header_area);
+
+        let (generated, marked, default) = (&self.generated, &self.marked, self.default.as_deref());
+        let line = |name: &str| {
+            let (mark, style) = if generated.iter().any(|g| locale::same_locale(g, name)) {
+                ("✓", Style::new().fg(tailwind::LIME.c500))
+            } else if marked.iter().any(|m| m == name) {
+                ("+", Style::new().fg(tailwind::AMBER.c400))
+            } else {
+                (" ", Style::new())
+            };
+            let mut spans = vec![
+                Span::styled(format!("{mark} "), style),
+                Span::raw(format!("{name:<32} ")),
+                Span::styled(charsets.get(name).cloned().unwrap_or_default(), Style::new().fg(tailwind::SLATE.c400)),
+            ];
+            if default.is_some_and(|d| locale::same_locale(d, name)) {
+                spans.push(Span::styled("  default", Style::new().fg(tailwind::BLUE.c400)));
+            }
+            Line::from(spans)
+        };
+        let block = Block::default().borders(Borders::TOP | Borders::BOTTOM).title("Supported locales");
+        self.list.draw(f, list_area, block, line);
+
+        if let Some(status) = &self.status {
+            f.render_widget(Paragraph::new(Line::from(status.clone())), status_area);
+        }
+        let help = if self.list.is_searching() {
+            "Type to search  Enter: done  Esc: clear"
+        } else {
+            "/: search  Space: mark  g: generate marked  Enter: set as default  r: reload  Esc: back"
+        };
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crossterm::event::KeyModifiers;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::components::views::test_util::{press, type_text, Fixture};
+
+    /// Lists `generated` and records the default locales it is asked to set.
+    #[derive(Default, Clone)]
+    struct FakeLocales {
+        generated: Vec<String>,
+        set: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Locales for FakeLocales {
+        fn generated(&self) -> Result<Vec<String>> {
+            Ok(self.generated.clone())
+        }
+
+        fn set_locale(&self, lang: &str, _privilege: &dyn Privilege) -> Result<()> {
+            self.set.lock().unwrap().push(lang.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_generate_and_set_default() {
+        let fixture = Fixture::new(&[
+            ("usr/share/i18n/SUPPORTED", "de_AT.UTF-8 UTF-8\nde_DE.UTF-8 UTF-8\nde_DE ISO-8859-1\nfr_FR.UTF-8 UTF-8\n"),
+            ("etc/locale.gen", "# de_DE.UTF-8 UTF-8\n"),
+        ]);
+        let locales = FakeLocales {
+            generated: vec![String::from("C.utf8"), String::from("de_AT.utf8")],
+            ..Default::default()
+        };
+        let mut view = LocaleView::with_root(fixture.root(), Box::new(locales.clone()), fixture.privilege());
+        assert!(view.is_generated("de_AT.UTF-8"));
+
+        // Letters only search after `/`.
+        press(&mut view, KeyCode::Char('g'));
+        assert!(view.status.is_some());
+        type_text(&mut view, "/FR");
+        assert_eq!(view.list.selected(), Some("fr_FR.UTF-8"));
+        press(&mut view, KeyCode::Enter);
+        press(&mut view, KeyCode::Char(' '));
+        press(&mut view, KeyCode::Esc);
+        assert_eq!(view.list.selected(), Some("fr_FR.UTF-8"));
+        assert_eq!(view.marked, ["fr_FR.UTF-8"]);
+
+        press(&mut view, KeyCode::Home);
+        press(&mut view, KeyCode::Char('j'));
+        assert_eq!(view.list.selected(), Some("de_DE.UTF-8"));
+        press(&mut view, KeyCode::Enter);
+        assert!(view.marked.is_empty());
+
+        let path = fixture.path("etc/locale.gen");
+        assert_eq!(fixture.recorder.commands(), [format!("tee {}", path.display()), String::from("locale-gen ")]);
+        assert_eq!(*locales.set.lock().unwrap(), ["de_DE.UTF-8"]);
+        let back = view.handle_key_events(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)).unwrap();
+        assert_eq!(back, Some(Action::BackToMenu));
+    }
+}
@@ -0,0 +1,327 @@
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use color_eyre::Result;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph},
+    Frame,
+};
+use tracing::error;
+
+use crate::{
+    action::Action,
+    boot::{
+        diff::DiffLine,
+        overlays::{available_overlays, entry_file_name, BootConfig},
+    },
+    provision::Change,
+    system::{
+        privilege::{self, Privilege, SudoSession},
+        require,
+    },
+};
+
+use super::{Deferred, ViewComponent};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Browse,
+    /// Reviewing the pending change before it is written, scrolled by the given number of lines.
+    Review(u16),
+}
+
+pub struct OverlaysView {
+    title: String,
+    root: PathBuf,
+    config: Result<BootConfig, String>,
+    /// How to become root to write the boot configuration.
+    privilege: Option<Box<dyn Privilege>>,
+    available: Vec<String>,
+    state: ListState,
+    mode: Mode,
+    status: Option<Span<'static>>,
+    /// Where the overlay list goes instead of being saved, in the setup wizard.
+    deferred: Option<Deferred>,
+}
+
+impl OverlaysView {
+    pub fn init(session: &SudoSession) -> Self {
+        Self::with_root(PathBuf::from("/"), privilege::detect_interactive(session))
+    }
+
+    /// Manage the boot configuration found under `root` instead of `/`.
+    pub fn with_root(root: PathBuf, privilege: Option<Box<dyn Privilege>>) -> Self {
+        let mut view = OverlaysView {
+            title: String::from("Overlays"),
+            root,
+            config: Err(String::new()),
+            privilege,
+            available: Vec::new(),
+            state: ListState::default().with_selected(Some(0)),
+            mode: Mode::Browse,
+            status: None,
+            deferred: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.config = BootConfig::load(&self.root).map_err(|e| {
+            error!("{e}");
+            e.to_string()
+        });
+        self.available = available_overlays(&self.root);
+        self.mode = Mode::Browse;
+    }
+
+    /// Enabled overlays in boot order, followed by the remaining available ones.
+    fn rows(&self) -> Vec<(String, Option<usize>)> {
+        let Ok(config) = &self.config else { return Vec::new() };
+        let mut rows: Vec<_> = config
+            .overlays
+            .iter()
+            .enumerate()
+            .map(|(i, o)| (entry_file_name(o).to_string(), Some(i)))
+            .collect();
+        rows.extend(
+            self.available
+                .iter()
+                .filter(|name| !config.is_enabled(name))
+                .map(|name| (name.clone(), None)),
+        );
+        rows
+    }
+
+    fn selected(&self) -> Option<String> {
+        self.state
+            .selected()
+            .and_then(|i| self.rows().get(i).map(|(name, _)| name.clone()))
+    }
+
+    fn toggle(&mut self) {
+        let Some(name) = self.selected() else { return };
+        let Ok(config) = &mut self.config else { return };
+        if config.is_enabled(&name) {
+            config.disable(&name);
+        } else {
+            config.enable(&name);
+        }
+        // Keep the cursor on the overlay that just moved between the two groups.
+        let index = self.rows().iter().position(|(n, _)| *n == name);
+        self.state.select(index);
+    }
+
+    fn reorder(&mut self, up: bool) {
+        let Some(name) = self.selected() else { return };
+        let Ok(config) = &mut self.config else { return };
+        config.reorder(&name, up);
+        if let Some(i) = config.position(&name) {
+            self.state.select(Some(i));
+        }
+    }
+
+    fn review(&mut self) {
+        let Ok(config) = &self.config else { return };
+        match config.render() {
+            Ok(_) if !config.is_modified() => {
+                if let Some(deferred) = &self.deferred {
+                    deferred.remove(&Change::Overlays(Vec::new()));
+                }
+                self.status = Some(Span::styled("No changes to save", Style::new().fg(tailwind::SLATE.c400)));
+            }
+            Ok(_) => self.mode = Mode::Review(0),
+            Err(e) => self.status = Some(Span::styled(e.to_string(), Style::new().fg(tailwind::RED.c400))),
+        }
+    }
+
+    fn save(&mut self) {
+        self.mode = Mode::Browse;
+        let Ok(config) = &mut self.config else { return };
+        if let Some(deferred) = &self.deferred {
+            deferred.push(Change::Overlays(config.overlays.iter().map(|o| entry_file_name(o).to_string()).collect()));
+            let done = "The overlays are saved when the summary is confirmed";
+            self.status = Some(Span::styled(done, Style::new().fg(tailwind::LIME.c500)));
+            return;
+        }
+        let saved = require(self.privilege.as_deref(), "Changing the boot configuration")
+            .and_then(|privilege| config.save(privilege));
+        self.status = Some(match saved {
+            Ok(backup) => Span::styled(
+                format!("Saved {}, backup in {}", config.path.display(), backup.display()),
+                Style::new().fg(tailwind::LIME.c500),
+            ),
+            Err(e) => {
+                error!("{e}");
+                Span::styled(e.to_string(), Style::new().fg(tailwind::RED.c400))
+            }
+        });
+    }
+
+    fn draw_list(&mut self, f: &mut Frame<'_>, area: Rect, config: &BootConfig) {
+        let rows = self.rows();
+        let items: Vec<ListItem> = rows
+            .iter()
+            .map(|(name, position)| match position {
+                Some(i) => ListItem::new(Line::from(vec![
+                    Span::styled("[x] ", Style::new().fg(tailwind::LIME.c500)),
+                    Span::styled(format!("{:>2}. ", i + 1), Style::new().fg(tailwind::SLATE.c400)),
+                    Span::raw(name.clone()),
+                ])),
+                None => ListItem::new(Line::from(vec![
+                    Span::raw("[ ]     "),
+                    Span::styled(name.clone(), Style::new().fg(tailwind::SLATE.c300)),
+                ])),
+            })
+            .collect();
+
+        let modified = if config.is_modified() { " (modified)" } else { "" };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::BOTTOM)
+                    .title(format!("{}{modified}", config.path.display())),
+            )
+            .highlight_style(Style::new().bg(tailwind::SLATE.c700).add_modifier(Modifier::BOLD))
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(list, area, &mut self.state);
+    }
+
+    fn draw_review(&self, f: &mut Frame<'_>, area: Rect, config: &BootConfig, scroll: u16) -> Result<()> {
+        let lines: Vec<Line> = config
+            .diff()?
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Same(l) => Line::from(format!("  {l}")).fg(tailwind::SLATE.c400),
+                DiffLine::Added(l) => Line::from(format!("+ {l}")).fg(tailwind::LIME.c500),
+                DiffLine::Removed(l) => Line::from(format!("- {l}")).fg(tailwind::RED.c400),
+            })
+            .collect();
+        let diff = Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(Block::default().borders(Borders::BOTTOM).title(format!("Changes to {}", config.path.display())));
+        f.render_widget(diff, area);
+        Ok(())
+    }
+}
+
+impl ViewComponent for OverlaysView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn defer_changes(&mut self, deferred: Deferred) {
+        self.deferred = Some(deferred);
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if let Mode::Review(scroll) = self.mode {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => self.save(),
+                KeyCode::Char('n') | KeyCode::Esc | KeyCode::Backspace => self.mode = Mode::Browse,
+                KeyCode::Char('j') | KeyCode::Down => self.mode = Mode::Review(scroll.saturating_add(1)),
+                KeyCode::Char('k') | KeyCode::Up => self.mode = Mode::Review(scroll.saturating_sub(1)),
+                _ => {}
+            }
+            return Ok(None);
+        }
+
+        self.status = None;
+        match key.code {
+            KeyCode::Backspace => return Ok(Some(Action::BackToMenu)),
+            KeyCode::Char('j') | KeyCode::Down => self.state.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.state.select_previous(),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle(),
+            KeyCode::Char('K') => self.reorder(true),
+            KeyCode::Char('J') => self.reorder(false),
+            KeyCode::Char('s') => self.review(),
+            KeyCode::Char('r') => self.reload(),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let area = Block::new().padding(Padding::horizontal(1)).inner(area);
+        let [main_area, status_area, help_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let config = match &self.config {
+            Ok(config) => config.clone(),
+            Err(e) => {
+                let message = Paragraph::new(vec![
+                    Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400))),
+                    Line::from("Press r to retry."),
+                ]);
+                f.render_widget(message, main_area);
+                return Ok(());
+            }
+        };
+
+        let help = match self.mode {
+            Mode::Browse => {
+                self.draw_list(f, main_area, &config);
+                "Space: enable/disable  K/J: move up/down  s: review and save  r: reload"
+            }
+            Mode::Review(scroll) => {
+                self.draw_review(f, main_area, &config, scroll)?;
+                "y/Enter: write (a .bak copy is kept)  n/Esc: back  ↓↑: scroll"
+            }
+        };
+
+        if let Some(status) = &self.status {
+            f.render_widget(Paragraph::new(Line::from(status.clone())), status_area);
+        }
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        components::views::test_util::{press, Fixture},
+        system::privilege::AsRoot,
+    };
+
+    #[test]
+    fn test_enable_reorder_and_save() {
+        let fixture = Fixture::new(&[
+            ("boot/uEnv.txt", "uname_r=5.10\nenable_uboot_overlays=1\n"),
+            ("lib/firmware/BB-I2C2-00A0.dtbo", ""),
+            ("lib/firmware/BB-UART1-00A0.dtbo", ""),
+        ]);
+
+        let mut view = OverlaysView::with_root(fixture.root(), Some(Box::new(AsRoot)));
+        press(&mut view, KeyCode::Char(' '));
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Char(' '));
+        press(&mut view, KeyCode::Char('K'));
+        press(&mut view, KeyCode::Char('s'));
+        assert_eq!(view.mode, Mode::Review(0));
+        press(&mut view, KeyCode::Char('y'));
+
+        assert_eq!(
+            fixture.read("boot/uEnv.txt"),
+            "uname_r=5.10\nenable_uboot_overlays=1\n\
+             uboot_overlay_addr0=/lib/firmware/BB-UART1-00A0.dtbo\n\
+             uboot_overlay_addr1=/lib/firmware/BB-I2C2-00A0.dtbo\n"
+        );
+        assert!(fixture.path("boot/uEnv.txt.bak").exists());
+    }
+}
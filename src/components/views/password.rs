@@ -1,19 +1,17 @@
-use std::process::{Command, Stdio};
-
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::{layout::*, style::{palette::tailwind::SLATE, Color, Style, Stylize}, text::*, widgets::{Block, BorderType, Padding, Paragraph, Widget}, Frame};
+use ratatui::{layout::*, style::{palette::tailwind::{self, SLATE}, Color, Style, Stylize}, text::*, widgets::{Block, BorderType, Padding, Paragraph, Widget, Wrap}, Frame};
 use color_eyre::eyre::Result;
-use std::io::Write;
 
-use crate::{action::Action, widgets::{ButtonState, ButtonWidget}};
+use crate::{action::Action, config::Config, provision::Change, system::{password::{change_own_password, change_password, PasswordError, PasswordPolicy}, privilege::{self, is_root}, secret::Secret}, widgets::{ButtonState, ButtonWidget}};
 
-use super::ViewComponent;
+use super::{Deferred, ViewComponent};
 
 #[derive(Debug, PartialEq, Eq)]
 enum Focus {
     None,
     OldPassword,
     NewPassword,
+    ConfirmPassword,
     ShowPassword,
     ApplyButton,
     BackButton,
@@ -22,8 +20,10 @@ enum Focus {
 pub struct PasswordView {
     title: String,
     username: String,
-    old_input: String,
-    new_input: String,
+    old_input: Secret,
+    new_input: Secret,
+    confirm_input: Secret,
+    policy: PasswordPolicy,
     focus: Focus,
     editing: bool,
     button_state: ButtonState,
@@ -31,6 +31,12 @@ pub struct PasswordView {
     back_button_state: ButtonState,
     update_active: bool,
     error_message: Option<String>,
+    /// What the last change did, shown in place of an error.
+    success: Option<String>,
+    /// Why the password has to be changed before anything else, shown as a banner.
+    mandatory: Option<String>,
+    /// Where the new password goes instead of being set, in the setup wizard.
+    deferred: Option<Deferred>,
 }
 
 #[allow(unused)]
@@ -46,8 +52,10 @@ impl PasswordView {
         Self {
             title: String::from("Password"),
             username: user,
-            old_input: String::new(),
-            new_input: String::new(),
+            old_input: Secret::default(),
+            new_input: Secret::default(),
+            confirm_input: Secret::default(),
+            policy: PasswordPolicy::default(),
             focus: Focus::None,
             editing: false,
             button_state: ButtonState::Normal,
@@ -55,6 +63,9 @@ impl PasswordView {
             back_button_state: ButtonState::Normal,
             update_active: false,
             error_message: None,
+            success: None,
+            mandatory: None,
+            deferred: None,
         }
     }
 
@@ -65,7 +76,8 @@ impl PasswordView {
         self.focus = match self.focus {
             Focus::BackButton => Focus::ApplyButton,
             Focus::ApplyButton => Focus::ShowPassword,
-            Focus::ShowPassword => Focus::NewPassword,
+            Focus::ShowPassword => Focus::ConfirmPassword,
+            Focus::ConfirmPassword => Focus::NewPassword,
             Focus::NewPassword => Focus::OldPassword,
             Focus::OldPassword => Focus::OldPassword, // Wrap around
             Focus::None => Focus::OldPassword,
@@ -80,7 +92,8 @@ impl PasswordView {
         self.focus = match self.focus {
             Focus::None => Focus::OldPassword,
             Focus::OldPassword => Focus::NewPassword,
-            Focus::NewPassword => Focus::ShowPassword,
+            Focus::NewPassword => Focus::ConfirmPassword,
+            Focus::ConfirmPassword => Focus::ShowPassword,
             Focus::ShowPassword => Focus::ApplyButton,
             Focus::ApplyButton => Focus::BackButton, // Wrap around
             Focus::BackButton => Focus::BackButton
@@ -88,7 +101,7 @@ impl PasswordView {
         self.update_states();
     }
     fn update_states(&mut self) {
-        self.editing = matches!(self.focus, Focus::OldPassword | Focus::NewPassword);
+        self.editing = matches!(self.focus, Focus::OldPassword | Focus::NewPassword | Focus::ConfirmPassword);
         self.button_state = if self.focus == Focus::ApplyButton {
             ButtonState::Selected
         } else {
@@ -106,6 +119,62 @@ impl PasswordView {
     fn clear_input(&mut self) {
         self.new_input.clear();
         self.old_input.clear();
+        self.confirm_input.clear();
+    }
+    /// A labelled, masked password field, bordered while it has focus.
+    fn draw_input(&self, f: &mut Frame<'_>, area: Rect, label: &str, input: &Secret, focus: Focus) {
+        if self.focus == focus {
+            Block::bordered().render(area, f.buffer_mut());
+        } else {
+            Block::default().render(area, f.buffer_mut());
+        }
+        let area = Block::bordered().inner(area);
+        let [text_area, input_area] = Layout::horizontal([
+            Constraint::Length(14),
+            Constraint::Min(1),
+        ]).areas(area);
+        Paragraph::new(Line::raw(label)
+            .style(Style::new().bold()))
+            .render(text_area, f.buffer_mut());
+        let display = if self.show_password {
+            input.expose()
+        } else {
+            &"*".repeat(input.len())
+        };
+        let input = Paragraph::new(display)
+            .style(if self.editing && self.focus == focus {
+                Style::default().bg(SLATE.c200).fg(Color::Green)
+            } else {
+                Style::default().bg(SLATE.c300)
+            });
+        f.render_widget(input, input_area);
+    }
+    /// What is wrong with the new password, shown while it is typed.
+    fn feedback(&self) -> Vec<String> {
+        if self.new_input.is_empty() {
+            return Vec::new();
+        }
+        let mut problems = self.policy.check(&self.username, self.new_input.expose());
+        if !self.confirm_input.is_empty() && self.confirm_input != self.new_input {
+            problems.push(String::from("The passwords do not match"));
+        }
+        problems
+    }
+    /// The first reason the form cannot be applied yet.
+    fn validate(&self) -> Option<String> {
+        if self.old_input.is_empty() && !is_root() {
+            return Some(String::from("Enter your current password"));
+        }
+        if self.new_input.is_empty() {
+            return Some(String::from("Enter a new password"));
+        }
+        if let Some(problem) = self.feedback().into_iter().next() {
+            return Some(problem);
+        }
+        if self.confirm_input != self.new_input {
+            return Some(String::from("Confirm the new password"));
+        }
+        None
     }
 }
 
@@ -113,6 +182,26 @@ impl ViewComponent for PasswordView {
     fn title(&self) -> &str {
         &self.title
     }
+    fn is_password(&self) -> bool {
+        true
+    }
+    fn register_config_handler(&mut self, config: &Config) {
+        self.policy = config.password_policy.clone();
+    }
+    fn defer_changes(&mut self, deferred: Deferred) {
+        self.deferred = Some(deferred);
+    }
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        if let Action::ForcePasswordChange { user, reason } = action {
+            self.username = user;
+            self.mandatory = Some(reason);
+            self.clear_input();
+            // Root does not need to know the old password.
+            self.focus = if is_root() { Focus::NewPassword } else { Focus::OldPassword };
+            self.update_states();
+        }
+        Ok(None)
+    }
     fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
         match key.code {
             KeyCode::Up => self.move_focus_up(),
@@ -123,92 +212,62 @@ impl ViewComponent for PasswordView {
                 Focus::None => {
                     if self.update_active {
                         self.update_active = false;
+                        self.success = None;
+                        // A failed mandatory change goes back to the form for another try.
+                        if self.mandatory.is_some() {
+                            self.error_message = None;
+                            self.focus = Focus::NewPassword;
+                            self.update_states();
+                            return Ok(None);
+                        }
                         return Ok(Some(Action::BackToMenu));
                     }
                 },
                 Focus::ShowPassword => self.toggle_show_password(),
-                Focus::OldPassword | Focus::NewPassword => {
+                Focus::OldPassword | Focus::NewPassword | Focus::ConfirmPassword => {
                     self.editing = !self.editing;
                 }
                 Focus::ApplyButton => {
+                    // Problems with the form are shown next to it, without leaving it
+                    self.error_message = self.validate();
+                    if self.error_message.is_some() {
+                        return Ok(None);
+                    }
                     self.update_active = true;
                     self.focus = Focus::None;
-                
-                    // Clear previous errors
-                    self.error_message = None;
-                
-                    // Validate inputs
-                    if self.old_input.is_empty() || self.new_input.is_empty() {
-                        self.error_message = Some("Passwords cannot be empty".to_string());
+
+                    if let Some(deferred) = &self.deferred {
+                        let password = self.new_input.clone();
+                        deferred.push(Change::Password { user: self.username.clone(), password });
+                        self.mandatory = None;
+                        self.success = Some(String::from("The password changes when the summary is confirmed"));
                         self.clear_input();
                         return Ok(None);
                     }
 
-                    // Create sudo command
-                    let _ = match Command::new("sudo")
-                        .arg("-k")  // Read password from stdin
-                        .stdin(Stdio::null())
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .spawn() 
-                    {
-                        Ok(child) => child,
-                        Err(e) => {
-                            self.error_message = Some(format!("Failed to start sudo: {}", e));
-                            self.clear_input();
-                            return Ok(None);
-                        }
+                    // Without root, passwd changes the user's own password, checking the current one.
+                    let result = if is_root() {
+                        privilege::detect(None).ok_or(PasswordError::PermissionDenied).and_then(|privilege| {
+                            change_password(&self.username, &self.new_input, &self.policy, privilege.as_ref())
+                        })
+                    } else {
+                        change_own_password(&self.username, &self.old_input, &self.new_input, &self.policy)
                     };
-
-                    // Create sudo command
-                    let mut child = match Command::new("sudo")
-                        .arg("-S")  // Read password from stdin
-                        .arg("chpasswd")
-                        .stdin(Stdio::piped())
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .spawn() 
-                    {
-                        Ok(child) => child,
-                        Err(e) => {
-                            self.error_message = Some(format!("Failed to start sudo: {}", e));
-                            self.clear_input();
-                            return Ok(None);
+                    match result {
+                        Ok(()) => {
+                            self.update_active = true;
+                            self.mandatory = None;
+                            self.success = Some(format!("The password of {} is changed", self.username));
                         }
-                    };
-                
-                    // Prepare input with proper newline separation
-                    let input = format!(
-                        "{}\n{}:{}\n",  // Note: \n after sudo password AND after chpasswd input
-                        self.old_input,
-                        self.username,
-                        self.new_input
-                    );
-                
-                    // Write to stdin
-                    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
-                    if let Err(e) = stdin.write_all(input.as_bytes()) {
-                        self.error_message = Some(format!("Failed to write input: {}", e));
-                        self.clear_input();
-                        return Ok(None);
-                    }
-                
-                    // Get command result
-                    let output = child.wait_with_output().expect("Failed to wait for command");
-                    
-                    // Handle output
-                    if output.status.success() {
-                        self.clear_input();
-                        self.update_active = true;
-                        self.error_message = Some(String::from("Success"));
-                    } else {
-                        let error_msg = String::from_utf8_lossy(&output.stderr);
-                        self.error_message = Some(format!("Error: {}", error_msg.trim()));
+                        Err(e) => self.error_message = Some(format!("Error: {e}")),
                     }
                     
                     self.clear_input();
                     return Ok(None);
                 }
+                Focus::BackButton if self.mandatory.is_some() => {
+                    self.error_message = Some(String::from("Set a new password first"));
+                }
                 Focus::BackButton => {
                     self.clear_input();
                     self.focus = Focus::None;
@@ -220,10 +279,14 @@ impl ViewComponent for PasswordView {
                 self.editing = false;
                 self.button_state = ButtonState::Normal;
             }
-            KeyCode::Char(c) if self.editing => match self.focus {
-                Focus::OldPassword => self.old_input.push(c),
-                Focus::NewPassword => self.new_input.push(c),
-                _ => {}
+            KeyCode::Char(c) if self.editing => {
+                self.error_message = None;
+                match self.focus {
+                    Focus::OldPassword => self.old_input.push(c),
+                    Focus::NewPassword => self.new_input.push(c),
+                    Focus::ConfirmPassword => self.confirm_input.push(c),
+                    _ => {}
+                }
             },
             KeyCode::Backspace if self.editing => match self.focus {
                 Focus::OldPassword => {
@@ -232,6 +295,9 @@ impl ViewComponent for PasswordView {
                 Focus::NewPassword => {
                     self.new_input.pop();
                 }
+                Focus::ConfirmPassword => {
+                    self.confirm_input.pop();
+                }
                 _ => {
                     // self.command_tx.as_ref().unwrap().send(Action::BackToMenu);
                     return Ok(Some(Action::BackToMenu));
@@ -254,22 +320,38 @@ impl ViewComponent for PasswordView {
         if !self.update_active {
             let area = Block::new().padding(Padding::horizontal(2)).inner(area);
             let [
+                banner_area,
                 user_area, 
                 old_area, 
                 new_area, 
+                feedback_area,
+                confirm_area,
                 tick_area, 
                 button_area, 
             ] = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
+                    Constraint::Length(if self.mandatory.is_some() { 2 } else { 0 }),
                     Constraint::Length(1),
                     Constraint::Length(3),
                     Constraint::Length(3),
+                    Constraint::Length(2),
+                    Constraint::Length(3),
                     Constraint::Length(1),
                     Constraint::Length(3),
                 ])
                 .areas(area);
     
+            if let Some(reason) = &self.mandatory {
+                let banner = Paragraph::new(vec![
+                    Line::from(reason.as_str()).bold(),
+                    Line::from("Set a new password to continue."),
+                ])
+                .fg(Color::Yellow)
+                .block(Block::new().padding(Padding::horizontal(1)));
+                f.render_widget(banner, banner_area);
+            }
+
             // User display
             let user_block = Paragraph::new(Line::from_iter([
                 Span::styled("User: ", Style::new().bold()),
@@ -277,59 +359,27 @@ impl ViewComponent for PasswordView {
             ])).block(Block::new().padding(Padding::horizontal(1)));
             f.render_widget(user_block, user_area);
     
-            // Update the password display in draw()
-            let old_display = if self.show_password {
-                &self.old_input
-            } else {
-                &"*".repeat(self.old_input.len())
-            };
+            self.draw_input(f, old_area, "Old Password", &self.old_input, Focus::OldPassword);
+            self.draw_input(f, new_area, "New Password", &self.new_input, Focus::NewPassword);
+            self.draw_input(f, confirm_area, "Confirm", &self.confirm_input, Focus::ConfirmPassword);
 
-            let new_display = if self.show_password {
-                &self.new_input
+            // Feedback on the new password while typing it
+            let feedback = if let Some(error) = &self.error_message {
+                Line::from(error.as_str()).fg(Color::Red)
+            } else if self.new_input.is_empty() {
+                Line::default()
             } else {
-                &"*".repeat(self.new_input.len())
-            };
-            match self.focus {
-                Focus::OldPassword => Block::bordered().render(old_area, f.buffer_mut()),
-                _ => Block::default().render(old_area, f.buffer_mut())
-            }
-            let old_area = Block::bordered().inner(old_area);
-            let [text_area, input_area] = Layout::horizontal([
-                Constraint::Length(14),
-                Constraint::Min(1),
-            ]).areas(old_area);
-            Paragraph::new(Line::raw("Old Password")
-                .style(Style::new().bold()))
-                .render(text_area, f.buffer_mut());
-            // Old password input
-            let old_input = Paragraph::new(&**old_display)
-                .style(if self.editing && self.focus == Focus::OldPassword {
-                    Style::default().bg(SLATE.c200).fg(Color::Green)
+                let problems = self.feedback();
+                if problems.is_empty() {
+                    Line::from("Strong enough").fg(Color::Green)
                 } else {
-                    Style::default().bg(SLATE.c300)
-                });
-            f.render_widget(old_input, input_area);
-            
-            match self.focus {
-                Focus::NewPassword => Block::bordered().render(new_area, f.buffer_mut()),
-                _ => Block::default().render(new_area, f.buffer_mut())
-            }
-            let new_area = Block::bordered().inner(new_area);
-            let [text_area, input_area] = Layout::horizontal([
-                Constraint::Length(14),
-                Constraint::Min(1),
-            ]).areas(new_area);
-            Paragraph::new(Line::raw("New Password")
-                .style(Style::new().bold()))
-                .render(text_area, f.buffer_mut());
-            // New password input
-            let new_input = Paragraph::new(&**new_display)
-                .style(if self.editing && self.focus == Focus::NewPassword {
-                    Style::default().bg(SLATE.c200).fg(Color::Green)
-                } else {
-                    Style::default().bg(SLATE.c300)
-                }); 
-            f.render_widget(new_input, input_area);
+                    Line::from(problems.join(" · ")).fg(Color::Red)
+                }
+            };
+            f.render_widget(
+                Paragraph::new(feedback).wrap(Wrap { trim: true }).block(Block::new().padding(Padding::horizontal(1))),
+                feedback_area,
+            );
             
             let [checkbox_area, label_area] = Layout::horizontal([
                 Constraint::Length(3),
@@ -403,15 +453,16 @@ impl ViewComponent for PasswordView {
                     Constraint::Min(0),
                     Constraint::Length(3),
                 ]).areas(main_area);
-            let content = Paragraph::new(Line::raw("Success")
-                .centered())
-                .block(Block::default().padding(Padding::vertical(1)));
-            let button = ButtonWidget::new("Back to Menu").state(ButtonState::Selected);
-            // f.render_widget(content, con_area);
+            let label = if self.mandatory.is_some() { "Try again" } else { "Back to Menu" };
+            let button = ButtonWidget::new(label).state(ButtonState::Selected);
             f.render_widget(button, but_area);
 
-            // Render error message
-            if let Some(err) = &self.error_message {
+            if let Some(success) = &self.success {
+                let content = Paragraph::new(Line::raw(success.as_str()).centered())
+                    .fg(tailwind::LIME.c500)
+                    .block(Block::default().padding(Padding::vertical(1)));
+                f.render_widget(content, con_area);
+            } else if let Some(err) = &self.error_message {
                 let error = Paragraph::new(err.clone())
                     .style(Style::default().fg(Color::Red));
                 f.render_widget(error, con_area);
@@ -420,4 +471,56 @@ impl ViewComponent for PasswordView {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyModifiers;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::components::views::test_util::{press, type_text};
+
+    fn type_into(view: &mut PasswordView, focus: Focus, text: &str) {
+        view.focus = focus;
+        view.update_states();
+        type_text(view, text);
+    }
+
+    #[test]
+    fn test_policy_and_confirmation() {
+        let mut view = PasswordView::init();
+        view.username = String::from("debian");
+        view.old_input = Secret::new("temppwd");
+        type_into(&mut view, Focus::NewPassword, "debian");
+        assert!(view.feedback().contains(&String::from("Do not use the user name")));
+
+        view.new_input.clear();
+        type_into(&mut view, Focus::NewPassword, "Correct-Horse-7");
+        assert_eq!(view.feedback(), Vec::<String>::new());
+        type_into(&mut view, Focus::ConfirmPassword, "Correct-Horse-8");
+        assert_eq!(view.feedback(), ["The passwords do not match"]);
+
+        // Applying is refused without leaving the form.
+        view.focus = Focus::ApplyButton;
+        press(&mut view, KeyCode::Enter);
+        assert!(!view.update_active);
+        assert_eq!(view.error_message.as_deref(), Some("The passwords do not match"));
+    }
+
+    #[test]
+    fn test_mandatory_change() {
+        let mut view = PasswordView::init();
+        let action = Action::ForcePasswordChange {
+            user: String::from("debian"),
+            reason: String::from("debian still has the default password"),
+        };
+        view.update(action).unwrap();
+        assert_eq!(view.username, "debian");
+
+        // There is no way back to the menu before the password was changed.
+        view.focus = Focus::BackButton;
+        let action = view.handle_key_events(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).unwrap();
+        assert_eq!(action, None);
+        assert_eq!(view.error_message.as_deref(), Some("Set a new password first"));
+    }
+}
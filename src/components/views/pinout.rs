@@ -1,13 +1,39 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
 use crossterm::event::KeyCode;
 use ratatui::{
     layout::*, prelude::{Style, Stylize}, style::{palette::tailwind, *}, text::*, widgets::*, Frame
 };
 use color_eyre::Result;
 
-use crate::action::Action;
+use tracing::error;
+
+use crate::{
+    action::Action,
+    boards::{description::{BoardDescription, PinInfo, PinType, DEFAULT_BOARD}, identity::Board, pinmux::{staging_dir, Pinmux}},
+    boot::overlays::install_overlay,
+    gpio::{cdev::CdevBackend, line::{Direction as LineDirection, Edge, EdgeEvent, GpioBackend, LineId, LineInfo}},
+    system::privilege::{self, is_root, Privilege, SudoSession},
+    widgets::SudoPrompt,
+};
 
 use super::ViewComponent;
 
+/// Number of edge events kept per watched line.
+const MAX_EVENTS: usize = 6;
+/// Ticks between two reads of the line info, about every two seconds.
+const REFRESH_TICKS: u8 = 8;
+
+/// Live state of the GPIO line behind the selected pin.
+struct LiveLine {
+    line: LineId,
+    info: Option<LineInfo>,
+    value: Option<bool>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum SelectedTable {
     Left,
@@ -18,36 +44,249 @@ pub struct PinOut {
     title: String,
     state_l: TableState,
     state_r: TableState,
-    items: Vec<PinInfo>,
+    board: BoardDescription,
+    header: usize,
     selected_table: SelectedTable, // Add this
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum PinType {
-    Power3v3,
-    Power5v,
-    Ground,
-    GPIO,
-    SPI,
-    I2C,
-    UART,
-    PCM,
-    Special,
+    gpio: Box<dyn GpioBackend>,
+    live: Option<LiveLine>,
+    events: HashMap<LineId, VecDeque<EdgeEvent>>,
+    status: Option<Span<'static>>,
+    pinmux: Pinmux,
+    /// Function picker for the selected pin, open while `Some`.
+    picker: Option<ListState>,
+    tick_count: u8,
+    /// Installs the generated overlay, `None` leaves it staged in the data directory.
+    privilege: Option<Box<dyn Privilege>>,
+    /// Asked for the sudo password once an overlay is to be installed, `None` never asks.
+    session: Option<SudoSession>,
+    /// Asks for the sudo password before installing the compiled overlay at the path.
+    sudo_prompt: Option<(SudoPrompt, PathBuf)>,
 }
 
 impl PinOut {
-    pub fn init() -> Self {
+    pub fn init(board: &Board, session: &SudoSession) -> Self {
+        let mut view = Self::with_backend(board, Box::new(CdevBackend::new()), privilege::detect_interactive(session));
+        view.session = Some(session.clone());
+        view
+    }
+
+    /// Show the headers of `board`, reaching its GPIO lines through `gpio`.
+    pub fn with_backend(board: &Board, gpio: Box<dyn GpioBackend>, privilege: Option<Box<dyn Privilege>>) -> Self {
+        let board = match board.description() {
+            Ok(board) => board,
+            Err(e) => {
+                error!("Falling back to built-in board description: {e}");
+                BoardDescription::builtin(board.kind.description_id())
+                    .or_else(|_| BoardDescription::builtin(DEFAULT_BOARD))
+                    .expect("built-in board descriptions are valid")
+            }
+        };
+
         PinOut {
             title: String::from("pinout"),
             state_l: TableState::default().with_selected(0),
             state_r: TableState::default().with_selected(None),
-            items: load_pin_data(),
+            board,
+            header: 0,
             selected_table: SelectedTable::Left, // Initial selection
+            gpio,
+            live: None,
+            events: HashMap::new(),
+            status: None,
+            pinmux: Pinmux::default(),
+            picker: None,
+            tick_count: 0,
+            privilege,
+            session: None,
+            sudo_prompt: None,
+        }
+    }
+
+    fn selected_pin(&self) -> Option<&PinInfo> {
+        match self.selected_table {
+            SelectedTable::Left => self.left_items().get(self.state_l.selected().unwrap_or(0)).copied(),
+            SelectedTable::Right => self.right_items().get(self.state_r.selected().unwrap_or(0)).copied(),
+        }
+    }
+
+    /// Re-read the line behind the selected pin and collect pending edge events.
+    ///
+    /// Only the line info is read for lines this view has not requested: requesting a line just
+    /// to show its value could change its muxing on some boards.
+    fn refresh(&mut self) {
+        let header = self.board.headers.get(self.header).map_or("", |h| h.name.as_str());
+        let line = self.selected_pin().and_then(|pin| self.gpio.resolve(header, pin));
+
+        self.live = line.map(|line| {
+            let info = self.gpio.line_info(&line).map_err(|e| error!("{e}")).ok();
+            let value = if self.gpio.is_requested(&line) {
+                self.gpio.read_value(&line).map_err(|e| error!("{e}")).ok()
+            } else {
+                None
+            };
+            LiveLine { line, info, value }
+        });
+        self.poll_events();
+    }
+
+    /// Collect the edge events of the watched lines, which this view holds already.
+    fn poll_events(&mut self) {
+        for (line, events) in self.events.iter_mut() {
+            match self.gpio.read_events(line) {
+                Ok(new) => {
+                    events.extend(new);
+                    while events.len() > MAX_EVENTS {
+                        events.pop_front();
+                    }
+                }
+                Err(e) => error!("Unable to read events: {e}"),
+            }
+        }
+    }
+
+    fn toggle_output(&mut self) {
+        let Some(live) = &self.live else { return };
+        let line = live.line.clone();
+        let value = !live.value.unwrap_or(false);
+        self.events.remove(&line);
+        let result = self.gpio.drive(&line, value);
+        self.report(result);
+    }
+
+    fn watch_input(&mut self) {
+        let Some(live) = &self.live else { return };
+        let line = live.line.clone();
+        let result = self.gpio.watch(&line);
+        if self.report(result) {
+            self.events.insert(line, VecDeque::new());
+        }
+    }
+
+    fn release_line(&mut self) {
+        let Some(live) = &self.live else { return };
+        let line = live.line.clone();
+        self.gpio.release(&line);
+        self.events.remove(&line);
+        self.status = None;
+        self.refresh();
+    }
+
+    /// Keep the outcome of a line request for the details panel, returning whether it succeeded.
+    fn report(&mut self, result: Result<()>) -> bool {
+        let ok = result.is_ok();
+        self.status = result.err().map(|e| {
+            error!("{e}");
+            Self::error_span(e.to_string())
+        });
+        self.refresh();
+        ok
+    }
+
+    fn error_span(text: String) -> Span<'static> {
+        Span::styled(text, Style::new().fg(tailwind::RED.c400))
+    }
+
+    fn header_name(&self) -> &str {
+        self.board.headers.get(self.header).map_or("", |h| h.name.as_str())
+    }
+
+    fn open_picker(&mut self) {
+        let Some(pin) = self.selected_pin() else { return };
+        if pin.modes.is_empty() {
+            self.status = Some(Self::error_span(format!("No alternate functions known for {}", pin.name)));
+            return;
+        }
+        let current = self.pinmux.function_of(self.header_name(), pin);
+        let selected = pin.modes.iter().position(|m| m.function == current).unwrap_or(0);
+        self.picker = Some(ListState::default().with_selected(Some(selected)));
+    }
+
+    fn handle_picker_key(&mut self, code: KeyCode) {
+        let Some(picker) = self.picker.as_mut() else { return };
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => picker.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => picker.select_previous(),
+            KeyCode::Esc | KeyCode::Backspace => self.picker = None,
+            KeyCode::Enter => {
+                let index = picker.selected();
+                self.picker = None;
+                self.stage_function(index.unwrap_or(0));
+            }
+            _ => {}
+        }
+    }
+
+    fn stage_function(&mut self, index: usize) {
+        let header = self.header_name().to_string();
+        let Some(pin) = self.selected_pin().cloned() else { return };
+        let Some(mode) = pin.modes.get(index.min(pin.modes.len().saturating_sub(1))) else { return };
+        let result = self.pinmux.stage(&self.board, &header, &pin, mode);
+        self.report(result);
+    }
+
+    /// Write the overlay for every staged function change into the data directory and install
+    /// the compiled overlay. Only installing needs root, so the sudo password is asked for here
+    /// rather than when the view opens.
+    fn generate_overlay(&mut self) {
+        let staged = match self.pinmux.write_overlay(&self.board, &staging_dir()) {
+            Ok(staged) => staged,
+            Err(e) => return self.show_result(Err(e)),
+        };
+        let Some(blob) = staged.blob else {
+            let text = format!("Overlay source written to {}, install dtc to compile it", staged.source.display());
+            return self.show_result(Ok(text));
+        };
+        match &self.session {
+            Some(session) if !is_root() && !session.is_authenticated() => {
+                self.sudo_prompt = Some((SudoPrompt::new(session.clone()), blob));
+            }
+            _ => self.install(&blob),
+        }
+    }
+
+    /// Install the compiled overlay `blob` next to the others, where the Overlays view can enable
+    /// it. Without the sudo password it stays staged.
+    fn install(&mut self, blob: &Path) {
+        let authenticated = is_root() || self.session.as_ref().is_none_or(SudoSession::is_authenticated);
+        let result = match self.privilege.as_deref().filter(|_| authenticated) {
+            Some(privilege) => install_overlay(Path::new("/"), blob, privilege)
+                .map(|target| format!("Overlay installed as {}, enable it in Overlays", target.display())),
+            None => Ok(format!("Overlay staged at {}, installing it needs root", blob.display())),
+        };
+        self.show_result(result);
+    }
+
+    fn show_result(&mut self, result: Result<String>) {
+        match result {
+            Ok(text) => self.status = Some(Span::styled(text, Style::new().fg(tailwind::LIME.c500))),
+            Err(e) => {
+                error!("{e}");
+                self.status = Some(Self::error_span(e.to_string()));
+            }
+        }
+    }
+
+    fn items(&self) -> &[PinInfo] {
+        self.board
+            .headers
+            .get(self.header)
+            .map(|h| h.pins.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn selected_len(&self) -> usize {
+        match self.selected_table {
+            SelectedTable::Left => self.left_items().len(),
+            SelectedTable::Right => self.right_items().len(),
         }
     }
 
     // Update navigation methods to handle both tables
     pub fn next_row(&mut self) {
+        let len = self.selected_len();
+        if len == 0 {
+            return;
+        }
         let state = match self.selected_table {
             SelectedTable::Left => &self.state_l,
             SelectedTable::Right => &self.state_r,
@@ -55,7 +294,7 @@ impl PinOut {
 
         let i = match state.selected() {
             Some(i) => {
-                if i >= self.items.len() - 1 {
+                if i >= len - 1 {
                     0
                 } else {
                     i + 1
@@ -73,6 +312,10 @@ impl PinOut {
     }
 
     pub fn previous_row(&mut self) {
+        let len = self.selected_len();
+        if len == 0 {
+            return;
+        }
         let state = match self.selected_table {
             SelectedTable::Left => &self.state_l,
             SelectedTable::Right => &self.state_r,
@@ -81,7 +324,7 @@ impl PinOut {
         let i = match state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.items.len() - 1
+                    len - 1
                 } else {
                     i - 1
                 }
@@ -97,6 +340,16 @@ impl PinOut {
         // self.scroll_state = self.scroll_state.position(i * ITEM_HEIGHT);
     }
 
+    fn next_header(&mut self) {
+        if self.board.headers.len() < 2 {
+            return;
+        }
+        self.header = (self.header + 1) % self.board.headers.len();
+        self.state_l.select(Some(0));
+        self.state_r.select(None);
+        self.selected_table = SelectedTable::Left;
+    }
+
     // Helper methods to get filtered items
     fn switch_left(&mut self) {
         if self.selected_table == SelectedTable::Right {
@@ -128,18 +381,22 @@ impl PinOut {
 
     // Helper methods to get filtered items
     fn left_items(&self) -> Vec<&PinInfo> {
-        self.items.iter()
+        let mut pins: Vec<_> = self.items().iter()
             .filter(|data| data.number % 2 != 0)
-            .collect()
+            .collect();
+        pins.sort_by_key(|d| d.number);
+        pins
     }
 
     fn right_items(&self) -> Vec<&PinInfo> {
-        self.items.iter()
+        let mut pins: Vec<_> = self.items().iter()
             .filter(|data| data.number % 2 == 0)
-            .collect()
+            .collect();
+        pins.sort_by_key(|d| d.number);
+        pins
     }
     fn render_info(&self, frame: &mut Frame, area: Rect, pin: &PinInfo) -> Result<()> {
-        let info = Paragraph::new(vec![
+        let mut lines = vec![
             Line::from(vec![
                 Span::styled("Pin Number: ", Style::new().fg(tailwind::SLATE.c300)),
                 Span::styled(pin.number.to_string(), Style::new().fg(tailwind::SLATE.c100)),
@@ -150,7 +407,12 @@ impl PinOut {
             ]),
             Line::from(vec![
                 Span::styled("Function: ", Style::new().fg(tailwind::SLATE.c300)),
-                Span::styled(&pin.function, Style::new().fg(tailwind::SLATE.c100)),
+                Span::styled(self.pinmux.function_of(self.header_name(), pin), Style::new().fg(tailwind::SLATE.c100)),
+                if self.pinmux.staged(self.header_name(), pin.number).is_some() {
+                    Span::styled(" (staged)", Style::new().fg(tailwind::AMBER.c500).italic())
+                } else {
+                    Span::raw("")
+                },
             ]),
             Line::from(vec![
                 Span::styled("Type: ", Style::new().fg(tailwind::SLATE.c300)),
@@ -165,7 +427,10 @@ impl PinOut {
                     Span::styled(note, Style::new().fg(tailwind::SLATE.c100).italic()),
                 ]))
             ),
-        ])
+        ];
+        lines.extend(self.live_lines());
+
+        let info = Paragraph::new(lines)
         .block(
             Block::default()
                 .title(" Pin Details ")
@@ -177,6 +442,74 @@ impl PinOut {
         frame.render_widget(info, area);
         Ok(())
     }
+
+    fn live_lines(&self) -> Vec<Line<'_>> {
+        let label = |text: &'static str| Span::styled(text, Style::new().fg(tailwind::SLATE.c300));
+        let value = |text: String| Span::styled(text, Style::new().fg(tailwind::SLATE.c100));
+
+        let Some(live) = &self.live else {
+            let mut lines = vec![Line::default(), Line::from(label("No GPIO line for this pin"))];
+            if let Some(status) = &self.status {
+                lines.push(Line::from(status.clone()));
+            }
+            lines.push(Line::default());
+            lines.push(Line::from(label("f: change function  g: generate overlay")));
+            return lines;
+        };
+
+        let mut lines = vec![
+            Line::default(),
+            Line::from(vec![
+                label("Line: "),
+                value(format!("{} {}", live.line.chip.display(), live.line.offset)),
+            ]),
+        ];
+        if let Some(info) = &live.info {
+            let direction = match info.direction {
+                LineDirection::Input => "Input",
+                LineDirection::Output => "Output",
+            };
+            lines.push(Line::from(vec![
+                label("Direction: "),
+                value(format!("{direction}{}", if info.active_low { " (active low)" } else { "" })),
+            ]));
+            lines.push(Line::from(vec![
+                label("Consumer: "),
+                value(if info.consumer.is_empty() { String::from("-") } else { info.consumer.clone() }),
+            ]));
+        }
+        let level = match live.value {
+            Some(true) => Span::styled("High", Style::new().fg(tailwind::LIME.c500).bold()),
+            Some(false) => Span::styled("Low", Style::new().fg(tailwind::SLATE.c100).bold()),
+            None => value(String::from("-")),
+        };
+        lines.push(Line::from(vec![label("Value: "), level]));
+
+        if let Some(events) = self.events.get(&live.line) {
+            lines.push(Line::from(label("Edges:")));
+            if events.is_empty() {
+                lines.push(Line::from(value(String::from("  waiting..."))));
+            }
+            for event in events.iter().rev() {
+                let edge = match event.edge {
+                    Edge::Rising => "rising",
+                    Edge::Falling => "falling",
+                };
+                lines.push(Line::from(value(format!(
+                    "  {:>12.3}s {edge}",
+                    event.timestamp_ns as f64 / 1e9
+                ))));
+            }
+        }
+
+        if let Some(status) = &self.status {
+            lines.push(Line::from(status.clone()));
+        }
+        lines.push(Line::default());
+        lines.push(Line::from(label("o: toggle output  w: watch edges  x: release  r: refresh")));
+        lines.push(Line::from(label("f: change function  g: generate overlay")));
+        lines
+    }
 }
 
 impl ViewComponent for PinOut {
@@ -185,18 +518,58 @@ impl ViewComponent for PinOut {
     }
 
     fn handle_key_events(&mut self, key: crossterm::event::KeyEvent) -> Result<Option<Action>> {
+        if let Some((prompt, _)) = self.sudo_prompt.as_mut() {
+            if prompt.handle_key(key.code) {
+                if let Some((_, blob)) = self.sudo_prompt.take() {
+                    self.install(&blob);
+                }
+            }
+            return Ok(None);
+        }
+        if self.picker.is_some() {
+            self.handle_picker_key(key.code);
+            return Ok(None);
+        }
         match key.code {
             KeyCode::Backspace => { return Ok(Some(Action::BackToMenu)); },
             KeyCode::Char('j') | KeyCode::Down => self.next_row(),
             KeyCode::Char('k') | KeyCode::Up => self.previous_row(),
             KeyCode::Char('h') | KeyCode::Left => self.switch_left(),
             KeyCode::Char('l') | KeyCode::Right => self.switch_right(),
-            _ => {}
+            KeyCode::Tab => self.next_header(),
+            KeyCode::Char('o') => self.toggle_output(),
+            KeyCode::Char('w') => self.watch_input(),
+            KeyCode::Char('x') => self.release_line(),
+            KeyCode::Char('f') => self.open_picker(),
+            KeyCode::Char('g') => self.generate_overlay(),
+            KeyCode::Char('r') => {}
+            _ => return Ok(None),
         }
+        self.refresh();
 
         Ok(None)
     }
 
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        if let Some((prompt, _)) = self.sudo_prompt.as_mut() {
+            prompt.handle_paste(&text);
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        if action == Action::Tick {
+            self.tick_count += 1;
+            if self.tick_count >= REFRESH_TICKS {
+                self.tick_count = 0;
+                self.refresh();
+            } else if !self.events.is_empty() {
+                self.poll_events();
+            }
+        }
+        Ok(None)
+    }
+
     fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
         let area = area.inner(Margin { horizontal: 1, vertical: 0 });
 
@@ -207,18 +580,26 @@ impl ViewComponent for PinOut {
             Constraint::Min(1),
         ]).areas(area);
         
+        let rows = self.left_items().len().max(self.right_items().len()) as u16;
         let [pin_area, lengend_area] = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(22),
+            Constraint::Length(rows + 2),
             Constraint::Min(1),
         ]).areas(pin_area);
 
-        let pin_block = Block::bordered().title("Pin");
+        let header_name = self.board.headers.get(self.header).map_or("Pin", |h| h.name.as_str());
+        let pin_block = if self.board.headers.len() > 1 {
+            Block::bordered()
+                .title(format!("{} ", header_name))
+                .title_bottom(Line::from(" Tab: next header ").right_aligned())
+        } else {
+            Block::bordered().title(header_name)
+        };
         f.render_widget(&pin_block, pin_area);
         // let lengen_block = Block::bordered().title("Legend");
         // f.render_widget(&lengen_block, lengend_area);
-        let info_block = Block::bordered().title("Info");
+        let info_block = Block::bordered().title(format!("Info - {}", self.board.name));
         f.render_widget(&info_block, info_area);
 
         let [left_area, right_area, _] = Layout::default()
@@ -233,16 +614,13 @@ impl ViewComponent for PinOut {
         self.draw_table_right(f, right_area)?;
         self.render_legend(f, lengend_area)?;
 
-        let binding_left = self.left_items();
-        let binding_right = self.right_items();
-        let selected_pin = match self.selected_table {
-            SelectedTable::Left => binding_left.get(self.state_l.selected().unwrap_or(0)),
-            SelectedTable::Right => binding_right.get(self.state_r.selected().unwrap_or(0)),
-        };
-    
-        if let Some(pin) = selected_pin {
+        if let Some(pin) = self.selected_pin() {
             self.render_info(f, info_area, pin)?;
         }
+        self.render_picker(f, info_area)?;
+        if let Some((prompt, _)) = &self.sudo_prompt {
+            prompt.draw(f, area);
+        }
 
         Ok(())
     }
@@ -251,7 +629,7 @@ impl ViewComponent for PinOut {
 impl PinOut {
     fn draw_table_left(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
         // Filter and sort odd-numbered pins
-        let mut odd_pins: Vec<_> = self.items.iter()
+        let mut odd_pins: Vec<_> = self.board.headers[self.header].pins.iter()
             .filter(|data| data.number % 2 != 0)
             .collect();
         odd_pins.sort_by_key(|d| d.number);
@@ -285,7 +663,7 @@ impl PinOut {
     
     fn draw_table_right(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
         // Filter and sort even-numbered pins
-        let mut even_pins: Vec<_> = self.items.iter()
+        let mut even_pins: Vec<_> = self.board.headers[self.header].pins.iter()
             .filter(|data| data.number % 2 == 0)
             .collect();
         even_pins.sort_by_key(|d| d.number);
@@ -317,6 +695,43 @@ impl PinOut {
         Ok(())
     }
 
+    fn render_picker(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if self.picker.is_none() {
+            return Ok(());
+        }
+        let Some(pin) = self.selected_pin() else { return Ok(()) };
+        let items: Vec<ListItem> = pin
+            .modes
+            .iter()
+            .map(|m| {
+                let color = type_color(m.pin_type);
+                ListItem::new(Line::from(vec![
+                    Span::styled("◉ ", Style::new().fg(color)),
+                    Span::raw(format!("{} ", m.function)),
+                    Span::styled(format!("mode {}", m.mode), Style::new().fg(tailwind::SLATE.c400)),
+                ]))
+            })
+            .collect();
+
+        let height = items.len() as u16 + 2;
+        let [_, popup_area] = Layout::vertical([Constraint::Fill(1), Constraint::Length(height)])
+            .areas(area.inner(Margin { horizontal: 2, vertical: 1 }));
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!(" Function for {} ", pin.name))
+                    .title_bottom(Line::from(" Enter: stage  Esc: cancel ").right_aligned()),
+            )
+            .highlight_style(Style::new().bg(tailwind::SLATE.c700).add_modifier(Modifier::BOLD))
+            .highlight_symbol(">");
+
+        frame.render_widget(Clear, popup_area);
+        if let Some(state) = self.picker.as_mut() {
+            frame.render_stateful_widget(list, popup_area, state);
+        }
+        Ok(())
+    }
+
     fn render_legend(&mut self, frame: &mut Frame, area: Rect) -> Result<()>{
         let legend = Paragraph::new(vec![
             Line::from(vec![
@@ -339,6 +754,10 @@ impl PinOut {
                 Span::styled("◉ PCM", Style::new().fg(tailwind::TEAL.c500)),
                 Span::raw("(Pulse Code Modulation)"),
             ]),
+            Line::from(vec![
+                Span::styled("◉ PWM", Style::new().fg(tailwind::ORANGE.c500)),
+                Span::raw("(Pulse Width Modulation)"),
+            ]),
             Line::from(vec![
                 Span::styled("◉ Ground", Style::new().fg(tailwind::WHITE)),
                 Span::raw(""),
@@ -351,6 +770,10 @@ impl PinOut {
                 Span::styled("◉ 3.3v", Style::new().fg(tailwind::AMBER.c500)),
                 Span::raw("((Power)"),
             ]),
+            Line::from(vec![
+                Span::styled("◉ Other", Style::new().fg(tailwind::STONE.c400)),
+                Span::raw("(Analog, reset, USB, ...)"),
+            ]),
         ])
         .block(Block::default().title(" Legend ").borders(Borders::ALL).padding(Padding::horizontal(2)))
         .alignment(Alignment::Left);
@@ -360,328 +783,83 @@ impl PinOut {
     }
 }
 
-// Sample data implementation
-fn load_pin_data() -> Vec<PinInfo> {
-    vec![
-        // Bottom row (left side)
-        PinInfo {
-            number: 1,
-            name: "3v3 Power".to_string(),
-            function: "Buck 1 (3.5A max)".to_string(),
-            pin_type: PinType::Power3v3,
-            note: Some("Shared rail - 500mA usable".to_string()),
-        },
-        PinInfo {
-            number: 3,
-            name: "GPIO 2".to_string(),
-            function: "I2C1 SDA".to_string(),
-            pin_type: PinType::I2C,
-            note: Some("SoC pin E11".to_string()),
-        },
-        PinInfo {
-            number: 5,
-            name: "GPIO 3".to_string(),
-            function: "I2C1 SCL".to_string(),
-            pin_type: PinType::I2C,
-            note: Some("SoC pin B13".to_string()),
-        },
-        PinInfo {
-            number: 7,
-            name: "GPIO 4".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin W26".to_string()),
-        },
-        PinInfo {
-            number: 9,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 11,
-            name: "GPIO 17".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin A26".to_string()),
-        },
-        PinInfo {
-            number: 13,
-            name: "GPIO 27".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin N22".to_string()),
-        },
-        PinInfo {
-            number: 15,
-            name: "GPIO 22".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin R27".to_string()),
-        },
-        PinInfo {
-            number: 17,
-            name: "3v3 Power".to_string(),
-            function: String::new(),
-            pin_type: PinType::Power3v3,
-            note: None,
-        },
-        PinInfo {
-            number: 19,
-            name: "GPIO 10".to_string(),
-            function: "SPI0 MOSI".to_string(),
-            pin_type: PinType::SPI,
-            note: Some("SoC pin B12".to_string()),
-        },
-        PinInfo {
-            number: 21,
-            name: "GPIO 9".to_string(),
-            function: "SPI0 MISO".to_string(),
-            pin_type: PinType::SPI,
-            note: Some("SoC pin C11".to_string()),
-        },
-        PinInfo {
-            number: 23,
-            name: "GPIO 11".to_string(),
-            function: "SPI0 SCLK".to_string(),
-            pin_type: PinType::SPI,
-            note: Some("SoC pin A9".to_string()),
-        },
-        PinInfo {
-            number: 25,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 27,
-            name: "GPIO 0".to_string(),
-            function: "EEPROM SDA".to_string(),
-            pin_type: PinType::I2C,
-            note: Some("SoC pin D11".to_string()),
-        },
-        PinInfo {
-            number: 29,
-            name: "GPIO 5".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin B20".to_string()),
-        },
-        PinInfo {
-            number: 31,
-            name: "GPIO 6".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin D20".to_string()),
-        },
-        PinInfo {
-            number: 33,
-            name: "GPIO 13".to_string(),
-            function: "PWM1".to_string(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin E19".to_string()),
-        },
-        PinInfo {
-            number: 35,
-            name: "GPIO 19".to_string(),
-            function: "PCM FS".to_string(),
-            pin_type: PinType::PCM,
-            note: Some("SoC pin C26".to_string()),
-        },
-        PinInfo {
-            number: 37,
-            name: "GPIO 26".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin P26".to_string()),
-        },
-        PinInfo {
-            number: 39,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        // Top row (right side)
-        PinInfo {
-            number: 2,
-            name: "5v Power".to_string(),
-            function: String::new(),
-            pin_type: PinType::Power5v,
-            note: None,
-        },
-        PinInfo {
-            number: 4,
-            name: "5v Power".to_string(),
-            function: String::new(),
-            pin_type: PinType::Power5v,
-            note: None,
-        },
-        PinInfo {
-            number: 6,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 8,
-            name: "GPIO 14".to_string(),
-            function: "UART TX".to_string(),
-            pin_type: PinType::UART,
-            note: Some("SoC pin F24".to_string()),
-        },
-        PinInfo {
-            number: 10,
-            name: "GPIO 15".to_string(),
-            function: "UART RX".to_string(),
-            pin_type: PinType::UART,
-            note: Some("SoC pin C27".to_string()),
-        },
-        PinInfo {
-            number: 12,
-            name: "GPIO 18".to_string(),
-            function: "PCM CLK".to_string(),
-            pin_type: PinType::PCM,
-            note: Some("SoC pin D25".to_string()),
-        },
-        PinInfo {
-            number: 14,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 16,
-            name: "GPIO 23".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin B5".to_string()),
-        },
-        PinInfo {
-            number: 18,
-            name: "GPIO 24".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin C8".to_string()),
-        },
-        PinInfo {
-            number: 20,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 22,
-            name: "GPIO 25".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin P21".to_string()),
-        },
-        PinInfo {
-            number: 24,
-            name: "GPIO 8".to_string(),
-            function: "SPI0 CE0".to_string(),
-            pin_type: PinType::SPI,
-            note: Some("SoC pin C12".to_string()),
-        },
-        PinInfo {
-            number: 26,
-            name: "GPIO 7".to_string(),
-            function: "SPI0 CE1".to_string(),
-            pin_type: PinType::SPI,
-            note: Some("SoC pin B3".to_string()),
-        },
-        PinInfo {
-            number: 28,
-            name: "GPIO 1".to_string(),
-            function: "EEPROM SCL".to_string(),
-            pin_type: PinType::I2C,
-            note: Some("SoC pin B9".to_string()),
-        },
-        PinInfo {
-            number: 30,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 32,
-            name: "GPIO 12".to_string(),
-            function: "PWM0".to_string(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin C20".to_string()),
-        },
-        PinInfo {
-            number: 34,
-            name: "Ground".to_string(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        },
-        PinInfo {
-            number: 36,
-            name: "GPIO 16".to_string(),
-            function: String::new(),
-            pin_type: PinType::GPIO,
-            note: Some("SoC pin A25".to_string()),
-        },
-        PinInfo {
-            number: 38,
-            name: "GPIO 20".to_string(),
-            function: "PCM DIN".to_string(),
-            pin_type: PinType::PCM,
-            note: Some("SoC pin F23".to_string()),
-        },
-        PinInfo {
-            number: 40,
-            name: "GPIO 21".to_string(),
-            function: "PCM DOUT".to_string(),
-            pin_type: PinType::PCM,
-            note: Some("SoC pin B25".to_string()),
-        },
-    ]
+impl PinInfo {
+    fn ref_info(&self) -> (u16, &str, Color) {
+        (self.number, &self.name, self.type_color())
+    }
+
+    const fn type_color(&self) -> Color {
+        type_color(self.pin_type)
+    }
 }
 
-struct PinInfo {
-    number: u16,
-    name: String,
-    function: String,
-    pin_type: PinType,
-    note: Option<String>,
+const fn type_color(pin_type: PinType) -> Color {
+    match pin_type {
+        PinType::Power3v3 => tailwind::AMBER.c500,
+        PinType::Power5v => tailwind::RED.c500,
+        PinType::Ground => tailwind::WHITE,
+        PinType::GPIO => tailwind::LIME.c500,
+        PinType::SPI => tailwind::PINK.c500,
+        PinType::I2C => tailwind::SKY.c500,
+        PinType::UART => tailwind::VIOLET.c500,
+        PinType::PCM => tailwind::TEAL.c500,
+        PinType::PWM => tailwind::ORANGE.c500,
+        PinType::Special => tailwind::STONE.c400,
+    }
 }
 
-impl PinInfo {
-    fn empty() -> Self {
-        Self {
-            number: 0,
-            name: String::new(),
-            function: String::new(),
-            pin_type: PinType::Ground,
-            note: None,
-        }
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{boards::identity::BoardKind, components::views::test_util::press, gpio::mock::MockBackend};
+
+    fn beagleplay() -> Board {
+        Board { kind: BoardKind::BeaglePlay, model: None, compatible: Vec::new(), eeprom: None }
     }
 
-    fn ref_info(&self) -> (u16, &str, Color) {
-        (self.number, &self.name, self.type_color())
+    #[test]
+    fn test_board_of_detected_kind() {
+        let view = PinOut::with_backend(&beagleplay(), Box::new(MockBackend::new("gpiochip0", &[])), None);
+        assert_eq!(view.board.id, "beagleplay");
     }
 
-    const fn type_color(&self) -> Color {
-        match self.pin_type {
-            PinType::Power3v3 => tailwind::AMBER.c500,
-            PinType::Power5v => tailwind::RED.c500,
-            PinType::Ground => tailwind::WHITE,
-            PinType::GPIO => tailwind::LIME.c500,
-            PinType::SPI => tailwind::PINK.c500,
-            PinType::I2C => tailwind::SKY.c500,
-            PinType::UART => tailwind::VIOLET.c500,
-            PinType::PCM => tailwind::TEAL.c500,
-            PinType::Special => tailwind::SKY.c500,
+    #[test]
+    fn test_drive_watch_and_release() {
+        // mikroBUS pins 1 (AN) and 3 (RST) are the first two of the left column.
+        let mut gpio = MockBackend::new("gpiochip0", &["AN", "RST"]);
+        gpio.claim(1, "reset-gpios");
+        let mut view = PinOut::with_backend(&beagleplay(), Box::new(gpio), None);
+        press(&mut view, KeyCode::Char('r'));
+        let line = view.live.as_ref().map(|live| live.line.clone()).unwrap();
+        assert_eq!(line.offset, 0);
+
+        // Browsing leaves the line alone, whatever the ticks.
+        for _ in 0..2 * REFRESH_TICKS {
+            view.update(Action::Tick).unwrap();
         }
+        assert!(!view.gpio.is_requested(&line));
+        assert_eq!(view.live.as_ref().unwrap().value, None);
+
+        press(&mut view, KeyCode::Char('o'));
+        let live = view.live.as_ref().unwrap();
+        assert_eq!((live.value, live.info.as_ref().map(|i| i.direction)), (Some(true), Some(LineDirection::Output)));
+        press(&mut view, KeyCode::Char('o'));
+        assert_eq!(view.live.as_ref().unwrap().value, Some(false));
+
+        press(&mut view, KeyCode::Char('w'));
+        assert_eq!(view.live.as_ref().unwrap().info.as_ref().map(|i| i.direction), Some(LineDirection::Input));
+        assert!(view.events.contains_key(&line));
+
+        press(&mut view, KeyCode::Char('x'));
+        assert!(!view.gpio.is_requested(&line));
+        assert!(view.events.is_empty());
+
+        // RST belongs to a driver, so driving it fails and says why.
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Char('o'));
+        assert!(view.status.as_ref().is_some_and(|s| s.content.contains("busy")));
+        assert_eq!(view.live.as_ref().unwrap().info.as_ref().map(|i| i.consumer.as_str()), Some("reset-gpios"));
     }
-}
\ No newline at end of file
+}
@@ -1,17 +1,50 @@
-use crate::{action::Action, widgets::{ButtonState, ButtonWidget}};
-use color_eyre::{eyre::Ok, Result};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{action::Action, provision::Change, system::{host_keys::{self, HostKey}, privilege::{self, Privilege, SudoSession}, require, ssh, systemd::{self, Systemd}}, widgets::{ButtonState, ButtonWidget}};
+use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::{layout::{Constraint, Direction, Layout, Rect}, text::{Line, Span}, widgets::{Block, Padding, Paragraph}};
+use ratatui::{layout::{Constraint, Direction, Layout, Rect}, style::{palette::tailwind, Style, Stylize}, text::{Line, Span}, widgets::{Block, Padding, Paragraph}};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Deferred, ViewComponent};
+
+mod keys;
+
+use keys::KeysPanel;
 
-use super::ViewComponent;
+/// Ticks between two status reads, about every ten seconds.
+const STATUS_TICKS: u8 = 40;
 
 pub struct SshView {
     title: String,
+    /// Shared with the background status reads.
+    systemd: Arc<Mutex<Box<dyn Systemd>>>,
+    /// How to become root to replace the host keys.
+    privilege: Option<Box<dyn Privilege>>,
+    sender: UnboundedSender<Action>,
     status: String,
+    /// Error of the last action, kept until the next one.
+    error: Option<String>,
+    /// Where enabling or disabling the server goes instead of being done, in the setup wizard.
+    /// The host keys and authorized keys still change right away.
+    deferred: Option<Deferred>,
+    /// What the wizard will do with the server, once enabling or disabling it was kept.
+    pending: Option<String>,
+    host_keys: Result<Vec<HostKey>, String>,
+    /// Asking before the host keys are replaced.
+    confirm_regenerate: bool,
     focus: Focus,
+    /// The authorized_keys panel, while it is open.
+    keys: Option<KeysPanel>,
     en_button_state: ButtonState,
     di_button_state: ButtonState,
+    ke_button_state: ButtonState,
+    rg_button_state: ButtonState,
     ba_button_state: ButtonState,
+    tick_count: u8,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -19,24 +52,97 @@ enum Focus {
     None,
     Enable,
     Disable,
+    Keys,
+    Regenerate,
     Back,
 }
 
 impl SshView {
-    pub fn init() -> Self {
-        let status = String::from("Active");
-        SshView {
+    pub fn init(sender: UnboundedSender<Action>, session: &SudoSession) -> Self {
+        let systemd = systemd::detect(privilege::detect_interactive(session));
+        Self::with_systemd(systemd, privilege::detect_interactive(session), sender)
+    }
+
+    /// Manage the server through `systemd`, sending status reads through `sender`.
+    pub fn with_systemd(
+        systemd: Box<dyn Systemd>,
+        privilege: Option<Box<dyn Privilege>>,
+        sender: UnboundedSender<Action>,
+    ) -> Self {
+        let mut view = SshView {
             title: String::from("SSH"),
-            status,
+            systemd: Arc::new(Mutex::new(systemd)),
+            privilege,
+            sender,
+            status: String::from("Reading…"),
+            error: None,
+            deferred: None,
+            pending: None,
+            host_keys: Ok(Vec::new()),
+            confirm_regenerate: false,
             focus: Focus::None,
+            keys: None,
             en_button_state: ButtonState::Normal,
             di_button_state: ButtonState::Normal,
+            ke_button_state: ButtonState::Normal,
+            rg_button_state: ButtonState::Normal,
             ba_button_state: ButtonState::Normal,
+            tick_count: 0,
+        };
+        view.refresh_status();
+        view.load_host_keys();
+        view
+    }
+    fn systemd(&self) -> MutexGuard<'_, Box<dyn Systemd>> {
+        self.systemd.lock().unwrap()
+    }
+    /// Read the status in the background, as asking systemd blocks. It comes back as
+    /// [`Action::SshStatus`].
+    fn refresh_status(&mut self) {
+        self.tick_count = 0;
+        let systemd = self.systemd.clone();
+        let sender = self.sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let status = ssh::status(systemd.lock().unwrap().as_ref()).map_or_else(
+                |e| format!("Unknown ({e})"),
+                |status| format!(
+                    "{}, {}",
+                    if status.active { "Active" } else { "Inactive" },
+                    if status.enabled { "starts at boot" } else { "disabled at boot" },
+                ),
+            );
+            let _ = sender.send(Action::SshStatus(status));
+        });
+    }
+    fn load_host_keys(&mut self) {
+        self.host_keys = host_keys::host_keys(Path::new("/")).map_err(|e| e.to_string());
+    }
+    fn regenerate_host_keys(&mut self) {
+        self.confirm_regenerate = false;
+        let result = require(self.privilege.as_deref(), "Replacing the host keys")
+            .and_then(|privilege| host_keys::regenerate(Path::new("/"), self.systemd().as_ref(), privilege));
+        self.error = result.err().map(|e| e.to_string());
+        self.load_host_keys();
+        self.refresh_status();
+    }
+    fn apply(&mut self, enable: bool) {
+        if let Some(deferred) = &self.deferred {
+            deferred.push(Change::Ssh(enable));
+            let state = if enable { "enabled" } else { "disabled" };
+            self.pending = Some(format!("The server is {state} when the summary is confirmed"));
+            return;
         }
+        let systemd = self.systemd();
+        let result = if enable { ssh::enable(systemd.as_ref()) } else { ssh::disable(systemd.as_ref()) };
+        drop(systemd);
+        self.error = result.err().map(|e| e.to_string());
+        self.refresh_status();
     }
     fn move_focus_up(&mut self) {
         self.focus = match self.focus {
-            Focus::Back => Focus::Disable,
+            Focus::Back => Focus::Regenerate,
+            Focus::Regenerate => Focus::Keys,
+            Focus::Keys => Focus::Disable,
             Focus::Disable => Focus::Enable,
             Focus::Enable => Focus::Enable,
             Focus::None => Focus::Enable,
@@ -47,7 +153,9 @@ impl SshView {
         self.focus = match self.focus {
             Focus::None => Focus::Enable,
             Focus::Enable => Focus::Disable,
-            Focus::Disable => Focus::Back,
+            Focus::Disable => Focus::Keys,
+            Focus::Keys => Focus::Regenerate,
+            Focus::Regenerate => Focus::Back,
             Focus::Back => Focus::Back,
         };
         self.update_states();
@@ -63,6 +171,16 @@ impl SshView {
         } else {
             ButtonState::Normal
         };
+        self.ke_button_state = if self.focus == Focus::Keys {
+            ButtonState::Selected
+        } else {
+            ButtonState::Normal
+        };
+        self.rg_button_state = if self.focus == Focus::Regenerate {
+            ButtonState::Selected
+        } else {
+            ButtonState::Normal
+        };
         self.ba_button_state = if self.focus == Focus::Back {
             ButtonState::Selected
         } else {
@@ -75,16 +193,41 @@ impl ViewComponent for SshView {
     fn title(&self) -> &str {
         &self.title
     }
+    fn needs_root(&self) -> bool {
+        true
+    }
+    fn defer_changes(&mut self, deferred: Deferred) {
+        self.deferred = Some(deferred);
+    }
     fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if let Some(keys) = &mut self.keys {
+            if keys.handle_key_event(key) {
+                self.keys = None;
+            }
+            return Ok(None);
+        }
+        if self.confirm_regenerate {
+            match key.code {
+                KeyCode::Char('y') => self.regenerate_host_keys(),
+                _ => self.confirm_regenerate = false,
+            }
+            return Ok(None);
+        }
         match key.code {
             KeyCode::Up => self.move_focus_up(),
             KeyCode::Down => self.move_focus_down(),
             KeyCode::Left => self.move_focus_up(),
             KeyCode::Right => self.move_focus_down(),
+            KeyCode::Char('r') => {
+                self.refresh_status();
+                self.load_host_keys();
+            }
             KeyCode::Enter => match self.focus {
                 Focus::None => {},
-                Focus::Enable => {},
-                Focus::Disable => {},
+                Focus::Enable => self.apply(true),
+                Focus::Disable => self.apply(false),
+                Focus::Keys => self.keys = Some(KeysPanel::init()),
+                Focus::Regenerate => self.confirm_regenerate = true,
                 Focus::Back => {
                     self.focus = Focus::None;
                     self.update_states();
@@ -101,12 +244,44 @@ impl ViewComponent for SshView {
         Ok(None)
     }
 
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        if let Some(keys) = &mut self.keys {
+            keys.handle_paste(text);
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Tick => {
+                self.tick_count += 1;
+                if self.tick_count >= STATUS_TICKS {
+                    self.refresh_status();
+                }
+            }
+            Action::SshStatus(status) => self.status = status,
+            _ => {}
+        }
+        Ok(None)
+    }
+
     fn draw(&mut self, f: &mut ratatui::Frame<'_>, area: Rect) -> Result<()> {
         let area = Block::new().padding(Padding::horizontal(2)).inner(area);
-        let [status_area, button_area, back_area] = Layout::default()
+        if let Some(keys) = &mut self.keys {
+            keys.draw(f, area);
+            return Ok(());
+        }
+        let host_key_lines = match &self.host_keys {
+            Ok(keys) => keys.len().max(1) as u16 + 1,
+            Err(_) => 2,
+        };
+        let [status_area, error_area, host_keys_area, button_area, keys_area, back_area] = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(host_key_lines + 1),
+                Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Length(3),
             ]).areas(area);
@@ -116,6 +291,27 @@ impl ViewComponent for SshView {
         ]));
 
         f.render_widget(status, status_area);
+        if self.confirm_regenerate {
+            let prompt = "Replace the host keys? Clients will warn that the board changed. y: replace  n: keep";
+            f.render_widget(Paragraph::new(prompt).fg(tailwind::AMBER.c400), error_area);
+        } else if let Some(error) = &self.error {
+            f.render_widget(Paragraph::new(error.as_str()).fg(tailwind::RED.c400), error_area);
+        } else if let Some(pending) = &self.pending {
+            f.render_widget(Paragraph::new(pending.as_str()).fg(tailwind::LIME.c500), error_area);
+        }
+
+        let mut lines = vec![Line::from("Host keys:")];
+        match &self.host_keys {
+            Ok(keys) if keys.is_empty() => lines.push(Line::from("  none").fg(tailwind::SLATE.c400)),
+            Ok(keys) => lines.extend(keys.iter().map(|key| {
+                Line::from(vec![
+                    Span::styled(format!("  {:<20}", key.key_type), Style::new().fg(tailwind::SLATE.c400)),
+                    Span::raw(key.fingerprint.clone()),
+                ])
+            })),
+            Err(e) => lines.push(Line::from(format!("  {e}")).fg(tailwind::RED.c400)),
+        }
+        f.render_widget(Paragraph::new(lines), host_keys_area);
         
         let [en_area, di_area] = Layout::default()
             .direction(Direction::Horizontal)
@@ -126,12 +322,72 @@ impl ViewComponent for SshView {
 
         let enable = ButtonWidget::new("Enable").state(self.en_button_state);
         let disable = ButtonWidget::new("Disable").state(self.di_button_state);
+        let [ke_area, rg_area] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ]).areas(keys_area);
+
+        let keys = ButtonWidget::new("Authorized keys").state(self.ke_button_state);
+        let regenerate = ButtonWidget::new("Regenerate host keys").state(self.rg_button_state);
         let back = ButtonWidget::new("Back").state(self.ba_button_state);
         
         f.render_widget(enable, en_area);
         f.render_widget(disable, di_area);
+        f.render_widget(keys, ke_area);
+        f.render_widget(regenerate, rg_area);
         f.render_widget(back, back_area);
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    use super::*;
+    use crate::{components::views::test_util::press, system::systemd::mock::MockSystemd};
+
+    /// Feed the status read in the background back to the view.
+    async fn read_status(view: &mut SshView, receiver: &mut UnboundedReceiver<Action>) {
+        let action = receiver.recv().await.unwrap();
+        view.update(action).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_enable_disable_and_tick() {
+        let systemd = MockSystemd::new(&["ssh.service"]);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut view = SshView::with_systemd(Box::new(systemd.clone()), None, sender);
+        read_status(&mut view, &mut receiver).await;
+        assert_eq!(view.status, "Inactive, disabled at boot");
+
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Enter);
+        read_status(&mut view, &mut receiver).await;
+        assert_eq!(view.status, "Active, starts at boot");
+        assert_eq!(view.error, None);
+
+        // Changes made behind the view's back show up after a while, or at once with `r`.
+        systemd.stop("ssh.service").unwrap();
+        view.update(Action::Tick).unwrap();
+        assert!(receiver.is_empty());
+        for _ in 1..STATUS_TICKS {
+            view.update(Action::Tick).unwrap();
+        }
+        read_status(&mut view, &mut receiver).await;
+        assert_eq!(view.status, "Inactive, starts at boot");
+        systemd.start("ssh.service").unwrap();
+        press(&mut view, KeyCode::Char('r'));
+        read_status(&mut view, &mut receiver).await;
+        assert_eq!(view.status, "Active, starts at boot");
+
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Enter);
+        read_status(&mut view, &mut receiver).await;
+        assert_eq!(view.status, "Inactive, disabled at boot");
+    }
+}
@@ -0,0 +1,276 @@
+//! The `authorized_keys` panel of the SSH view.
+
+use std::path::Path;
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::system::authorized_keys::{self, AuthorizedKey, AuthorizedKeysFile};
+
+/// Where `i` starts looking for keys to import; USB sticks are mounted below it.
+const IMPORT_DIR: &str = "/media/";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Browse,
+    /// Typing or pasting keys, one per line.
+    Add(String),
+    /// Typing the path of a file to import keys from.
+    Import(String),
+    /// Asking before the selected key is removed.
+    Remove,
+}
+
+pub struct KeysPanel {
+    user: String,
+    file: Result<AuthorizedKeysFile, String>,
+    keys: Vec<AuthorizedKey>,
+    state: ListState,
+    mode: Mode,
+    status: Option<Span<'static>>,
+}
+
+impl KeysPanel {
+    pub fn init() -> Self {
+        let user = authorized_keys::target_user();
+        let file = AuthorizedKeysFile::for_user(&user).map_err(|e| e.to_string());
+        Self::with_file(user, file)
+    }
+
+    pub fn with_file(user: String, file: Result<AuthorizedKeysFile, String>) -> Self {
+        let mut panel = KeysPanel {
+            user,
+            file,
+            keys: Vec::new(),
+            state: ListState::default().with_selected(Some(0)),
+            mode: Mode::Browse,
+            status: None,
+        };
+        panel.reload();
+        panel
+    }
+
+    fn reload(&mut self) {
+        let Ok(file) = &self.file else { return };
+        match file.keys() {
+            Ok(keys) => self.keys = keys,
+            Err(e) => self.set_error(e.to_string()),
+        }
+        let last = self.keys.len().saturating_sub(1);
+        self.state.select(Some(self.state.selected().unwrap_or_default().min(last)));
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::RED.c400)));
+    }
+
+    fn set_done(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::LIME.c500)));
+    }
+
+    fn add(&mut self, keys: Result<Vec<AuthorizedKey>>) {
+        let Ok(file) = &self.file else { return };
+        match keys.and_then(|keys| file.add(&keys)) {
+            Ok(0) => self.set_done(String::from("Already authorized")),
+            Ok(added) => self.set_done(format!("Added {added} key(s) for {}", self.user)),
+            Err(e) => return self.set_error(e.to_string()),
+        }
+        self.mode = Mode::Browse;
+        self.reload();
+    }
+
+    fn remove(&mut self) {
+        self.mode = Mode::Browse;
+        let Ok(file) = &self.file else { return };
+        let Some(key) = self.state.selected().and_then(|i| self.keys.get(i)).cloned() else { return };
+        match file.remove(&key) {
+            Ok(()) => self.set_done(format!("Removed {}", key.fingerprint())),
+            Err(e) => self.set_error(e.to_string()),
+        }
+        self.reload();
+    }
+
+    /// Returns true when the panel is closed.
+    pub fn handle_key_event(&mut self, key: KeyEvent) -> bool {
+        match &mut self.mode {
+            Mode::Add(input) | Mode::Import(input) => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Enter => {
+                    let keys = match &self.mode {
+                        Mode::Import(path) => authorized_keys::import(Path::new(path.trim())),
+                        Mode::Add(input) => input
+                            .lines()
+                            .filter(|line| !line.trim().is_empty())
+                            .map(AuthorizedKey::parse)
+                            .collect(),
+                        _ => return false,
+                    };
+                    self.add(keys);
+                }
+                _ => {}
+            },
+            Mode::Remove => match key.code {
+                KeyCode::Char('y') => self.remove(),
+                KeyCode::Char('n') | KeyCode::Esc => self.mode = Mode::Browse,
+                _ => {}
+            },
+            Mode::Browse => {
+                self.status = None;
+                match key.code {
+                    KeyCode::Esc | KeyCode::Backspace => return true,
+                    KeyCode::Char('j') | KeyCode::Down => self.state.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => self.state.select_previous(),
+                    KeyCode::Char('a') if self.file.is_ok() => self.mode = Mode::Add(String::new()),
+                    KeyCode::Char('i') if self.file.is_ok() => self.mode = Mode::Import(String::from(IMPORT_DIR)),
+                    KeyCode::Char('d') | KeyCode::Delete if self.state.selected().is_some_and(|i| i < self.keys.len()) => {
+                        self.mode = Mode::Remove
+                    }
+                    KeyCode::Char('r') => self.reload(),
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+
+    /// Pasted keys open the add prompt, so a key copied from another machine needs no other key.
+    pub fn handle_paste(&mut self, text: String) {
+        match &mut self.mode {
+            Mode::Add(input) | Mode::Import(input) => input.push_str(&text),
+            Mode::Browse if self.file.is_ok() => self.mode = Mode::Add(text),
+            _ => {}
+        }
+    }
+
+    pub fn draw(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let [main_area, status_area, help_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1), Constraint::Length(1)]).areas(area);
+
+        let file = match &self.file {
+            Ok(file) => file,
+            Err(e) => {
+                let message = Paragraph::new(vec![
+                    Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400))),
+                    Line::from("Press Esc to go back."),
+                ]);
+                f.render_widget(message, main_area);
+                return;
+            }
+        };
+        let title = format!("Authorized keys of {} ({})", self.user, file.path.display());
+
+        let help = match &self.mode {
+            Mode::Browse | Mode::Remove => {
+                let items: Vec<ListItem> = self
+                    .keys
+                    .iter()
+                    .map(|key| {
+                        let comment = if key.comment.is_empty() { "(no comment)" } else { &key.comment };
+                        let mut spans = vec![
+                            Span::styled(format!("{:<12} ", key.key_type), Style::new().fg(tailwind::SLATE.c400)),
+                            Span::raw(format!("{comment} ")),
+                            Span::styled(key.fingerprint(), Style::new().fg(tailwind::SLATE.c400)),
+                        ];
+                        if key.options.is_some() {
+                            spans.push(Span::styled(" restricted", Style::new().fg(tailwind::AMBER.c400)));
+                        }
+                        ListItem::new(Line::from(spans))
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::BOTTOM).title(title))
+                    .highlight_style(Style::new().bg(tailwind::SLATE.c700).add_modifier(Modifier::BOLD))
+                    .highlight_symbol(">")
+                    .highlight_spacing(HighlightSpacing::Always);
+                f.render_stateful_widget(list, main_area, &mut self.state);
+                if self.keys.is_empty() {
+                    let [_, empty_area] = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(main_area);
+                    f.render_widget(Paragraph::new("No keys yet, password logins only").fg(tailwind::SLATE.c400), empty_area);
+                }
+                if self.mode == Mode::Remove {
+                    "Remove the selected key? y: remove  n: keep"
+                } else {
+                    "a/paste: add  i: import from file  d: remove  r: reload  Esc: back"
+                }
+            }
+            Mode::Add(input) | Mode::Import(input) => {
+                let prompt = if matches!(self.mode, Mode::Add(_)) {
+                    "Paste or type public keys, one per line"
+                } else {
+                    "Import the keys in a file, e.g. id_ed25519.pub on a USB stick"
+                };
+                let input = Paragraph::new(Line::from(vec![Span::raw(input.clone()), Span::raw("█")]))
+                    .wrap(Wrap { trim: false })
+                    .block(Block::default().borders(Borders::BOTTOM).title(prompt));
+                f.render_widget(input, main_area);
+                "Enter: add  Esc: cancel"
+            }
+        };
+
+        if let Some(status) = &self.status {
+            f.render_widget(Paragraph::new(Line::from(status.clone())), status_area);
+        }
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crossterm::event::KeyModifiers;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAII94+suHER+6M4pdT+ZDN4veoq4QKvkmBtbmXpwagTj0 admin@laptop";
+
+    fn press(panel: &mut KeysPanel, code: KeyCode) -> bool {
+        panel.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_paste_import_and_remove() {
+        let home = tempfile::tempdir().unwrap();
+        let path = home.path().join(".ssh/authorized_keys");
+        let file = AuthorizedKeysFile::new(path.clone(), None);
+        let mut panel = KeysPanel::with_file(String::from("debian"), Ok(file));
+        assert!(panel.keys.is_empty());
+
+        // Bad keys are refused and the prompt stays open for a correction.
+        panel.handle_paste(String::from("ssh-ed25519 AAAA"));
+        press(&mut panel, KeyCode::Enter);
+        assert!(matches!(panel.mode, Mode::Add(_)));
+        press(&mut panel, KeyCode::Esc);
+
+        panel.handle_paste(format!("{KEY}\n"));
+        press(&mut panel, KeyCode::Enter);
+        assert_eq!(panel.mode, Mode::Browse);
+        assert_eq!(panel.keys.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{KEY}\n"));
+
+        let import = home.path().join("id_ed25519.pub");
+        fs::write(&import, format!("{KEY}\n")).unwrap();
+        press(&mut panel, KeyCode::Char('i'));
+        panel.mode = Mode::Import(import.display().to_string());
+        press(&mut panel, KeyCode::Enter);
+        assert_eq!(panel.keys.len(), 1);
+
+        press(&mut panel, KeyCode::Char('d'));
+        press(&mut panel, KeyCode::Char('y'));
+        assert!(panel.keys.is_empty());
+        assert!(press(&mut panel, KeyCode::Esc));
+    }
+}
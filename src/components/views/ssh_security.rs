@@ -0,0 +1,352 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    system::{
+        authorized_keys::{self, AuthorizedKeysFile},
+        privilege::{self, Privilege, SudoSession},
+        require,
+        sshd_config::{SshdConfig, SshdSettings},
+        systemd::{self, Systemd},
+    },
+    widgets::{ButtonState, ButtonWidget, Switch, SwitchState},
+};
+
+use super::ViewComponent;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    PasswordAuthentication,
+    PermitRootLogin,
+    Port,
+    AllowUsers,
+    Save,
+    Back,
+}
+
+impl Focus {
+    const ORDER: [Focus; 6] = [
+        Focus::PasswordAuthentication,
+        Focus::PermitRootLogin,
+        Focus::Port,
+        Focus::AllowUsers,
+        Focus::Save,
+        Focus::Back,
+    ];
+
+    fn step(self, forward: bool) -> Self {
+        let i = Self::ORDER.iter().position(|f| *f == self).unwrap_or_default();
+        let i = if forward { (i + 1).min(Self::ORDER.len() - 1) } else { i.saturating_sub(1) };
+        Self::ORDER[i]
+    }
+}
+
+pub struct SshSecurityView {
+    title: String,
+    root: PathBuf,
+    systemd: Box<dyn Systemd>,
+    /// How to become root to write the configuration.
+    privilege: Option<Box<dyn Privilege>>,
+    config: Result<SshdConfig, String>,
+    settings: Option<SshdSettings>,
+    /// Managed keywords set in included files too, read with the configuration.
+    overrides: Vec<(String, PathBuf)>,
+    port_input: String,
+    users_input: String,
+    focus: Focus,
+    /// Typing into the focused text field.
+    editing: bool,
+    status: Option<Span<'static>>,
+}
+
+impl SshSecurityView {
+    pub fn init(session: &SudoSession) -> Self {
+        let systemd = systemd::detect(privilege::detect_interactive(session));
+        Self::with_root(PathBuf::from("/"), systemd, privilege::detect_interactive(session))
+    }
+
+    /// Edit the configuration found under `root` instead of `/`.
+    pub fn with_root(root: PathBuf, systemd: Box<dyn Systemd>, privilege: Option<Box<dyn Privilege>>) -> Self {
+        let mut view = SshSecurityView {
+            title: String::from("SSH Security"),
+            root,
+            systemd,
+            privilege,
+            config: Err(String::new()),
+            settings: None,
+            overrides: Vec::new(),
+            port_input: String::new(),
+            users_input: String::new(),
+            focus: Focus::PasswordAuthentication,
+            editing: false,
+            status: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.config = SshdConfig::load(&self.root).map_err(|e| e.to_string());
+        self.load_settings();
+    }
+
+    fn load_settings(&mut self) {
+        self.settings = self.config.as_ref().ok().map(SshdConfig::settings);
+        self.overrides = self.config.as_ref().map(SshdConfig::overrides).unwrap_or_default();
+        if let Some(settings) = &self.settings {
+            self.port_input = settings.port.to_string();
+            self.users_input = settings.allow_users.join(" ");
+        }
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::RED.c400)));
+    }
+
+    /// The settings as edited, once the text fields are valid.
+    fn edited(&self) -> Result<SshdSettings, String> {
+        let mut settings = self.settings.clone().ok_or_else(String::new)?;
+        settings.port = match self.port_input.trim().parse() {
+            Ok(port) if port > 0 => port,
+            _ => return Err(format!("Invalid port `{}`", self.port_input.trim())),
+        };
+        settings.allow_users = self.users_input.split_whitespace().map(String::from).collect();
+        Ok(settings)
+    }
+
+    fn save(&mut self) {
+        let settings = match self.edited() {
+            Ok(settings) => settings,
+            Err(e) => return self.set_error(e),
+        };
+        let Ok(config) = &mut self.config else { return };
+        if !config.is_modified(&settings) {
+            self.status = Some(Span::styled("No changes to save", Style::new().fg(tailwind::SLATE.c400)));
+            return;
+        }
+
+        let user = authorized_keys::target_user();
+        let has_keys = AuthorizedKeysFile::for_user(&user)
+            .and_then(|file| file.keys())
+            .is_ok_and(|keys| !keys.is_empty());
+        if let Some(reason) = settings.lockout(&user, has_keys) {
+            return self.set_error(reason);
+        }
+
+        let saved = require(self.privilege.as_deref(), "Changing the SSH server configuration")
+            .and_then(|privilege| config.save(&settings, self.systemd.as_ref(), privilege));
+        match saved {
+            Ok(backup) => {
+                self.status = Some(Span::styled(
+                    format!("Saved {}, backup in {}", config.path.display(), backup.display()),
+                    Style::new().fg(tailwind::LIME.c500),
+                ));
+                self.load_settings();
+            }
+            Err(e) => self.set_error(e.to_string()),
+        }
+    }
+
+    fn edit(&mut self, key: KeyEvent) {
+        let input = match self.focus {
+            Focus::Port => &mut self.port_input,
+            Focus::AllowUsers => &mut self.users_input,
+            _ => return,
+        };
+        match key.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter | KeyCode::Esc => self.editing = false,
+            _ => {}
+        }
+    }
+
+    fn field<'a>(&self, label: &'a str, value: String, focus: Focus) -> Line<'a> {
+        let style = if self.focus == focus { Style::new().fg(tailwind::BLUE.c400) } else { Style::new() };
+        let cursor = if self.focus == focus && self.editing { "█" } else { "" };
+        Line::from(vec![
+            Span::styled(format!("{label:<16}"), style),
+            Span::raw(value),
+            Span::raw(cursor),
+        ])
+    }
+}
+
+impl ViewComponent for SshSecurityView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.editing {
+            self.edit(key);
+            return Ok(None);
+        }
+        self.status = None;
+        let Some(settings) = &mut self.settings else {
+            match key.code {
+                KeyCode::Backspace | KeyCode::Esc => return Ok(Some(Action::BackToMenu)),
+                KeyCode::Char('r') => self.reload(),
+                _ => {}
+            }
+            return Ok(None);
+        };
+
+        match (self.focus, key.code) {
+            (_, KeyCode::Up) => self.focus = self.focus.step(false),
+            (_, KeyCode::Down) => self.focus = self.focus.step(true),
+            (Focus::Back, KeyCode::Enter) | (_, KeyCode::Backspace) => {
+                self.focus = Focus::PasswordAuthentication;
+                self.load_settings();
+                return Ok(Some(Action::BackToMenu));
+            }
+            (_, KeyCode::Char('r')) => self.reload(),
+            (Focus::PasswordAuthentication, code) => {
+                let state = if settings.password_authentication { SwitchState::On } else { SwitchState::Off };
+                let mut switch = Switch::new(state);
+                if switch.handle_input(code) {
+                    settings.password_authentication = switch.state() == SwitchState::On;
+                }
+            }
+            (Focus::PermitRootLogin, KeyCode::Left) => {
+                settings.permit_root_login = settings.permit_root_login.previous()
+            }
+            (Focus::PermitRootLogin, KeyCode::Right | KeyCode::Char(' ') | KeyCode::Enter) => {
+                settings.permit_root_login = settings.permit_root_login.next()
+            }
+            (Focus::Port | Focus::AllowUsers, KeyCode::Enter) => self.editing = true,
+            (Focus::Save, KeyCode::Enter) => self.save(),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let area = Block::new().padding(Padding::horizontal(2)).inner(area);
+        let [password_area, fields_area, warning_area, status_area, button_area, help_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let settings = match (&self.config, &self.settings) {
+            (Ok(_), Some(settings)) => settings,
+            (config, _) => {
+                let e = config.as_ref().err().cloned().unwrap_or_default();
+                let message = Paragraph::new(vec![
+                    Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400))),
+                    Line::from("Press r to retry."),
+                ]);
+                f.render_widget(message, area);
+                return Ok(());
+            }
+        };
+
+        let [label_area, switch_area] = Layout::horizontal([Constraint::Length(16), Constraint::Length(9)]).areas(password_area);
+        f.render_widget(
+            self.field("Password login", String::new(), Focus::PasswordAuthentication),
+            label_area,
+        );
+        let state = if settings.password_authentication { SwitchState::On } else { SwitchState::Off };
+        f.render_widget(
+            Switch::new(state).focused(self.focus == Focus::PasswordAuthentication),
+            switch_area,
+        );
+
+        let users = if self.users_input.is_empty() && !self.editing { String::from("(everyone)") } else { self.users_input.clone() };
+        let fields = Paragraph::new(vec![
+            Line::default(),
+            self.field("Root login", format!("< {} >", settings.permit_root_login), Focus::PermitRootLogin),
+            self.field("Port", self.port_input.clone(), Focus::Port),
+            self.field("Allowed users", users, Focus::AllowUsers),
+        ]);
+        f.render_widget(fields, fields_area);
+
+        let warnings: Vec<Line> = self
+            .overrides
+            .iter()
+            .map(|(keyword, file)| {
+                Line::from(format!("{keyword} is also set in {}, which takes precedence", file.display()))
+                    .fg(tailwind::AMBER.c400)
+            })
+            .collect();
+        f.render_widget(Paragraph::new(warnings), warning_area);
+
+        if let Some(status) = &self.status {
+            f.render_widget(Paragraph::new(Line::from(status.clone())), status_area);
+        }
+
+        let [save_area, back_area] = Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(button_area);
+        let button_state = |focus| if self.focus == focus { ButtonState::Selected } else { ButtonState::Normal };
+        f.render_widget(ButtonWidget::new("Check and save").state(button_state(Focus::Save)), save_area);
+        f.render_widget(ButtonWidget::new("Back").state(button_state(Focus::Back)), back_area);
+
+        let help = if self.editing {
+            "Enter/Esc: done"
+        } else {
+            "↓↑: move  Space/←→: change  Enter: edit  r: reload"
+        };
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        components::views::test_util::{press, Fixture},
+        system::{privilege::AsRoot, sshd_config::PermitRootLogin, systemd::mock::MockSystemd},
+    };
+
+    #[test]
+    fn test_edit_and_reject() {
+        let fixture = Fixture::new(&[("etc/ssh/sshd_config", "PermitRootLogin yes\n")]);
+        let systemd = Box::new(MockSystemd::new(&[]));
+        let mut view = SshSecurityView::with_root(fixture.root(), systemd, Some(Box::new(AsRoot)));
+
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Left);
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Enter);
+        press(&mut view, KeyCode::Backspace);
+        press(&mut view, KeyCode::Backspace);
+        press(&mut view, KeyCode::Char('x'));
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(view.edited().unwrap_err(), "Invalid port `x`");
+
+        let settings = view.settings.clone().unwrap();
+        assert_eq!(settings.permit_root_login, PermitRootLogin::ForcedCommandsOnly);
+        assert!(settings.password_authentication);
+
+        // A configuration sshd rejects is never written.
+        view.config = view.config.map(|config| config.with_sshd("false"));
+        view.port_input = String::from("2222");
+        view.focus = Focus::Save;
+        press(&mut view, KeyCode::Enter);
+        assert!(view.status.is_some());
+        assert_eq!(fixture.read("etc/ssh/sshd_config"), "PermitRootLogin yes\n");
+    }
+}
@@ -0,0 +1,62 @@
+//! Helpers shared by the tests of the views.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tempfile::TempDir;
+
+use super::ViewComponent;
+use crate::system::privilege::{mock::Recorder, Privilege};
+
+/// Press `code` without modifiers, ignoring the action it leads to.
+pub fn press(view: &mut dyn ViewComponent, code: KeyCode) {
+    view.handle_key_events(KeyEvent::new(code, KeyModifiers::NONE)).unwrap();
+}
+
+/// Type each character of `text`.
+pub fn type_text(view: &mut dyn ViewComponent, text: &str) {
+    text.chars().for_each(|c| press(view, KeyCode::Char(c)));
+}
+
+/// A system root in a temporary directory, with a recorder in place of root.
+pub struct Fixture {
+    dir: TempDir,
+    pub recorder: Recorder,
+}
+
+impl Fixture {
+    /// Write each file, given by its path below the root and its content.
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        let fixture = Fixture { dir: tempfile::tempdir().unwrap(), recorder: Recorder::default() };
+        for (path, content) in files {
+            fixture.write(path, content);
+        }
+        fixture
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.dir.path().to_path_buf()
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.dir.path().join(path)
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.path(path);
+        fs::create_dir_all(path.parent().unwrap_or(Path::new("/"))).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.path(path)).unwrap()
+    }
+
+    /// The recorder, boxed the way the views take it.
+    pub fn privilege(&self) -> Option<Box<dyn Privilege>> {
+        Some(Box::new(self.recorder.clone()))
+    }
+}
@@ -0,0 +1,365 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone, Utc};
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    provision::Change,
+    system::{
+        clock::{self, Clock, ClockStatus},
+        privilege::{self, SudoSession},
+    },
+    widgets::FilterList,
+};
+
+use super::{Deferred, ViewComponent};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Regions,
+    Cities,
+}
+
+pub struct TimeView {
+    title: String,
+    root: PathBuf,
+    clock: Box<dyn Clock>,
+    status: Result<ClockStatus, String>,
+    zones: BTreeMap<String, Vec<String>>,
+    regions: FilterList,
+    cities: FilterList,
+    /// The region whose cities are listed.
+    region: String,
+    focus: Focus,
+    /// The time being typed, when setting it by hand.
+    editing: Option<String>,
+    message: Option<Span<'static>>,
+    /// Where the timezone goes instead of being set, in the setup wizard. NTP and the time are
+    /// still set right away, a time typed by hand would be late by the summary.
+    deferred: Option<Deferred>,
+}
+
+impl TimeView {
+    pub fn init(session: &SudoSession) -> Self {
+        let root = PathBuf::from("/");
+        let clock = clock::detect(&root, privilege::detect_interactive(session));
+        Self::with_clock(root, clock)
+    }
+
+    /// Pick timezones from the tz database below `root` and set them with `clock`.
+    pub fn with_clock(root: PathBuf, clock: Box<dyn Clock>) -> Self {
+        let mut view = TimeView {
+            title: String::from("Time"),
+            root,
+            clock,
+            status: Ok(ClockStatus::default()),
+            zones: BTreeMap::new(),
+            regions: FilterList::default(),
+            cities: FilterList::default(),
+            region: String::new(),
+            focus: Focus::Regions,
+            editing: None,
+            message: None,
+            deferred: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.status = self.clock.status().map_err(|e| e.to_string());
+        self.zones = clock::zones(&self.root).unwrap_or_default();
+        self.regions.set_items(self.zones.keys().cloned().collect());
+        let timezone = self.status.as_ref().map(|s| s.timezone.clone()).unwrap_or_default();
+        if let Some((region, city)) = timezone.split_once('/') {
+            self.regions.select(region);
+            self.show_cities();
+            self.cities.select(city);
+        } else {
+            self.show_cities();
+        }
+    }
+
+    /// List the cities of the selected region, if it changed.
+    fn show_cities(&mut self) {
+        let region = self.regions.selected().unwrap_or_default().to_string();
+        if region != self.region {
+            self.cities = FilterList::new(self.zones.get(&region).cloned().unwrap_or_default());
+            self.region = region;
+        }
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.message = Some(Span::styled(message, Style::new().fg(tailwind::RED.c400)));
+    }
+
+    fn set_done(&mut self, message: String) {
+        self.message = Some(Span::styled(message, Style::new().fg(tailwind::LIME.c500)));
+    }
+
+    fn report(&mut self, result: Result<()>, done: String) {
+        self.reload();
+        match result {
+            Ok(()) => self.set_done(done),
+            Err(e) => self.set_error(e.to_string()),
+        }
+    }
+
+    fn set_timezone(&mut self) {
+        let Some(city) = self.cities.selected() else { return };
+        let timezone = clock::join(&self.region, city);
+        if let Some(deferred) = &self.deferred {
+            let change = Change::Timezone(timezone.clone());
+            if self.status.as_ref().is_ok_and(|status| status.timezone == timezone) {
+                deferred.remove(&change);
+            } else {
+                deferred.push(change);
+            }
+            return self.set_done(format!("The timezone becomes {timezone} when the summary is confirmed"));
+        }
+        let result = self.clock.set_timezone(&timezone);
+        self.report(result, format!("The timezone is {timezone}"));
+    }
+
+    fn toggle_ntp(&mut self) {
+        let Ok(status) = &self.status else { return };
+        if !status.can_ntp {
+            return self.set_error(String::from("NTP sync needs systemd-timesyncd, install and enable it first"));
+        }
+        let on = !status.ntp;
+        let result = self.clock.set_ntp(on);
+        self.report(result, format!("NTP sync is {}", if on { "on" } else { "off" }));
+    }
+
+    fn start_editing(&mut self) {
+        if self.status.as_ref().is_ok_and(|status| status.ntp) {
+            return self.set_error(String::from("Turn NTP sync off with n to set the time by hand"));
+        }
+        self.editing = Some(Local::now().format(TIME_FORMAT).to_string());
+    }
+
+    fn set_time(&mut self, input: &str) {
+        match parse_local(input) {
+            Ok(time) => {
+                self.editing = None;
+                let result = self.clock.set_time(time);
+                let done = format!("The time is {}", time.with_timezone(&Local).format(TIME_FORMAT));
+                self.report(result, done);
+            }
+            Err(e) => self.set_error(e),
+        }
+    }
+}
+
+/// A local date and time as typed, with or without seconds.
+fn parse_local(input: &str) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+    let naive = NaiveDateTime::parse_from_str(input, TIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M"))
+        .map_err(|_| format!("Use the format YYYY-MM-DD HH:MM:SS, not `{input}`"))?;
+    // The earlier one of the hour repeated when the clocks go back.
+    let local = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("{input} is skipped when the clocks go forward"))?;
+    Ok(local.with_timezone(&Utc))
+}
+
+impl ViewComponent for TimeView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn defer_changes(&mut self, deferred: Deferred) {
+        self.deferred = Some(deferred);
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        self.message = None;
+        if let Some(input) = &mut self.editing {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.editing = None,
+                KeyCode::Enter => {
+                    let input = input.clone();
+                    self.set_time(&input);
+                }
+                _ => {}
+            }
+            return Ok(None);
+        }
+
+        let list = match self.focus {
+            Focus::Regions => &mut self.regions,
+            Focus::Cities => &mut self.cities,
+        };
+        if list.handle_key(key.code) {
+            self.show_cities();
+            return Ok(None);
+        }
+        match (self.focus, key.code) {
+            (_, KeyCode::Esc | KeyCode::Backspace) => return Ok(Some(Action::BackToMenu)),
+            (Focus::Regions, KeyCode::Tab | KeyCode::Right | KeyCode::Enter) => self.focus = Focus::Cities,
+            (Focus::Cities, KeyCode::BackTab | KeyCode::Left | KeyCode::Tab) => self.focus = Focus::Regions,
+            (Focus::Cities, KeyCode::Enter) => self.set_timezone(),
+            (_, KeyCode::Char('n')) => self.toggle_ntp(),
+            (_, KeyCode::Char('t')) => self.start_editing(),
+            (_, KeyCode::Char('r')) => self.reload(),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        match (&mut self.editing, self.focus) {
+            (Some(input), _) => input.push_str(text.trim()),
+            (None, Focus::Regions) => self.regions.handle_paste(&text),
+            (None, Focus::Cities) => self.cities.handle_paste(&text),
+        }
+        self.show_cities();
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let area = Block::new().padding(Padding::horizontal(2)).inner(area);
+        let [header_area, lists_area, input_area, message_area, help_area] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Fill(1),
+            Constraint::Length(2),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let now = Local::now();
+        let label = |text: &'static str| Span::raw(format!("{text:<10}")).bold();
+        let time = now.format("%Y-%m-%d %H:%M:%S %Z").to_string();
+        let mut header = vec![Line::from(vec![label("Time"), Span::raw(time)])];
+        match &self.status {
+            Ok(status) => {
+                let ntp = match (status.can_ntp, status.ntp, status.synchronized, &status.server) {
+                    (false, _, _, _) => Span::styled("not available", Style::new().fg(tailwind::SLATE.c400)),
+                    (true, false, _, _) => Span::raw("off"),
+                    (true, true, true, Some(server)) => Span::raw(format!("on, synchronized with {server}")),
+                    (true, true, true, None) => Span::raw("on, synchronized"),
+                    (true, true, false, _) => {
+                        Span::styled("on, not synchronized yet", Style::new().fg(tailwind::AMBER.c400))
+                    }
+                };
+                header.push(Line::from(vec![label("Timezone"), Span::raw(status.timezone.clone())]));
+                header.push(Line::from(vec![label("NTP"), ntp]));
+            }
+            Err(e) => header.push(Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400)))),
+        }
+        // Boards without a battery backed RTC start in 1970 until something sets the clock.
+        if now.year() < 2000 {
+            header.push(
+                Line::from("The clock is not set, so TLS certificates fail to verify. Turn on NTP or press t.")
+                    .fg(tailwind::AMBER.c400),
+            );
+        }
+        f.render_widget(Paragraph::new(header), header_area);
+
+        let [regions_area, cities_area] =
+            Layout::horizontal([Constraint::Length(24), Constraint::Fill(1)]).areas(lists_area);
+        let block = |title: &'static str, focused: bool| {
+            let border = if focused { Style::new().fg(tailwind::BLUE.c400) } else { Style::new() };
+            Block::default().borders(Borders::ALL).border_style(border).title(title)
+        };
+        self.regions.draw(f, regions_area, block("Region", self.focus == Focus::Regions), Line::from);
+        self.cities.draw(f, cities_area, block("City", self.focus == Focus::Cities), Line::from);
+
+        if let Some(input) = &self.editing {
+            let line = Line::from(vec![
+                Span::raw("Local time (YYYY-MM-DD HH:MM:SS): ").bold(),
+                Span::raw(input.clone()),
+                Span::raw("█"),
+            ]);
+            f.render_widget(Paragraph::new(line), input_area);
+        }
+        if let Some(message) = &self.message {
+            f.render_widget(Paragraph::new(Line::from(message.clone())), message_area);
+        }
+        let help = match (&self.editing, self.focus) {
+            (Some(_), _) => "Enter: set the time  Esc: cancel",
+            (None, Focus::Regions) => "Enter/Tab: cities  /: search  n: NTP on/off  t: set time  r: reload  Esc: back",
+            (None, Focus::Cities) => "Enter: set timezone  Tab: regions  /: search  n: NTP on/off  t: set time  Esc: back",
+        };
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        components::views::test_util::{press, type_text, Fixture},
+        system::clock::LocaltimeLink,
+    };
+
+    #[test]
+    fn test_pick_timezone_and_set_time() {
+        let fixture = Fixture::new(&[
+            ("usr/share/zoneinfo/Africa/Lagos", "TZif2"),
+            ("usr/share/zoneinfo/Europe/Berlin", "TZif2"),
+            ("usr/share/zoneinfo/Europe/Paris", "TZif2"),
+            ("usr/share/zoneinfo/Etc/UTC", "TZif2"),
+        ]);
+        std::fs::create_dir(fixture.path("etc")).unwrap();
+        symlink("/usr/share/zoneinfo/Etc/UTC", fixture.path("etc/localtime")).unwrap();
+        let clock = LocaltimeLink::new(fixture.root(), fixture.privilege());
+        let mut view = TimeView::with_clock(fixture.root(), Box::new(clock));
+        assert_eq!((view.region.as_str(), view.cities.selected()), ("Etc", Some("UTC")));
+
+        type_text(&mut view, "/eu");
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(view.region, "Europe");
+        press(&mut view, KeyCode::Enter);
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Enter);
+
+        // Without timesyncd there is no NTP to turn on.
+        press(&mut view, KeyCode::Char('n'));
+        assert!(view.message.is_some());
+        press(&mut view, KeyCode::Char('t'));
+        view.editing = Some(String::new());
+        view.handle_paste(String::from("sometime")).unwrap();
+        press(&mut view, KeyCode::Enter);
+        assert!(view.editing.is_some());
+        view.editing = Some(String::from("2026-10-17 12:00"));
+        press(&mut view, KeyCode::Enter);
+        assert_eq!(view.editing, None);
+
+        let time = parse_local("2026-10-17 12:00:00").unwrap();
+        assert_eq!(
+            fixture.recorder.commands(),
+            [
+                format!("ln -sfn /usr/share/zoneinfo/Europe/Paris {}", fixture.path("etc/localtime").display()),
+                format!("date --utc --set @{}", time.timestamp()),
+            ]
+        );
+    }
+}
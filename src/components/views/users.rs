@@ -0,0 +1,400 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{
+    action::Action,
+    config::Config,
+    system::{
+        authorized_keys,
+        password::{change_password, PasswordPolicy},
+        privilege::{self, is_root, Privilege, SudoSession},
+        secret::Secret,
+        users::{self, User},
+    },
+};
+
+use super::ViewComponent;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Browse,
+    /// Typing the name of a new user.
+    Add(String),
+    /// Typing a new password for the selected user.
+    Password(Secret),
+    /// Asking before the selected user and their home directory are deleted.
+    Delete,
+}
+
+pub struct UsersView {
+    title: String,
+    root: PathBuf,
+    /// How to become root, `None` leaves the list read-only.
+    privilege: Option<Box<dyn Privilege>>,
+    /// Passwords of other users can only be set by root itself.
+    as_root: bool,
+    /// Who runs beagle-config, protected from being deleted, locked or losing sudo.
+    current_user: String,
+    users: Result<Vec<User>, String>,
+    state: ListState,
+    mode: Mode,
+    policy: PasswordPolicy,
+    status: Option<Span<'static>>,
+}
+
+impl UsersView {
+    pub fn init(session: &SudoSession) -> Self {
+        let privilege = privilege::detect_interactive(session);
+        Self::with_root(PathBuf::from("/"), privilege, is_root(), authorized_keys::target_user())
+    }
+
+    /// Manage the accounts found under `root` instead of `/`.
+    pub fn with_root(
+        root: PathBuf,
+        privilege: Option<Box<dyn Privilege>>,
+        as_root: bool,
+        current_user: String,
+    ) -> Self {
+        let mut view = UsersView {
+            title: String::from("Users"),
+            root,
+            privilege,
+            as_root,
+            current_user,
+            users: Ok(Vec::new()),
+            state: ListState::default().with_selected(Some(0)),
+            mode: Mode::Browse,
+            policy: PasswordPolicy::default(),
+            status: None,
+        };
+        view.reload();
+        view
+    }
+
+    fn reload(&mut self) {
+        self.users = users::users(&self.root).map_err(|e| e.to_string());
+        let last = self.users.as_ref().map_or(0, |users| users.len().saturating_sub(1));
+        self.state.select(Some(self.state.selected().unwrap_or_default().min(last)));
+    }
+
+    fn selected(&self) -> Option<User> {
+        let users = self.users.as_ref().ok()?;
+        self.state.selected().and_then(|i| users.get(i)).cloned()
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::RED.c400)));
+    }
+
+    fn set_done(&mut self, message: String) {
+        self.status = Some(Span::styled(message, Style::new().fg(tailwind::LIME.c500)));
+    }
+
+    /// Run `change` with root rights and report how it went.
+    fn apply(&mut self, done: String, change: impl FnOnce(&dyn Privilege) -> Result<()>) -> bool {
+        let Some(privilege) = &self.privilege else {
+            self.set_error(String::from("Managing users needs root, run beagle-config with sudo"));
+            return false;
+        };
+        let result = change(privilege.as_ref());
+        self.reload();
+        match result {
+            Ok(()) => {
+                self.set_done(done);
+                true
+            }
+            Err(e) => {
+                self.set_error(e.to_string());
+                false
+            }
+        }
+    }
+
+    fn add(&mut self, name: String) {
+        let groups = users::default_groups(&self.root, false);
+        let done = format!("Created {name} in {}", groups.join(", "));
+        if !self.apply(done, |privilege| users::create(&name, &groups, privilege)) {
+            return;
+        }
+        let Some(i) = self.users.as_ref().ok().and_then(|users| users.iter().position(|u| u.name == name)) else {
+            // Asking for a password now would set it on whoever is selected.
+            self.mode = Mode::Browse;
+            return self.set_error(format!("{name} was created but is not in the user list, set its password there"));
+        };
+        self.state.select(Some(i));
+        // A new account cannot log in with a password until it has one.
+        self.mode = if self.as_root { Mode::Password(Secret::default()) } else { Mode::Browse };
+    }
+
+    fn set_password(&mut self, user: &User, password: &Secret) {
+        let done = format!("Changed the password of {}", user.name);
+        let policy = self.policy.clone();
+        if self.apply(done, |privilege| Ok(change_password(&user.name, password, &policy, privilege)?)) {
+            self.mode = Mode::Browse;
+        }
+    }
+
+    /// Refuse changes that would lock the current user out.
+    fn protect(&mut self, user: &User, action: &str) -> bool {
+        if user.name == self.current_user {
+            self.set_error(format!("You cannot {action} {}, the account you are using", user.name));
+            return true;
+        }
+        false
+    }
+
+    fn handle_browse_key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.status = None;
+        let selected = self.selected();
+        match (key.code, selected) {
+            (KeyCode::Esc | KeyCode::Backspace, _) => {
+                self.reload();
+                return Some(Action::BackToMenu);
+            }
+            (KeyCode::Char('j') | KeyCode::Down, _) => self.state.select_next(),
+            (KeyCode::Char('k') | KeyCode::Up, _) => self.state.select_previous(),
+            (KeyCode::Char('r'), _) => self.reload(),
+            (KeyCode::Char('a'), _) => self.mode = Mode::Add(String::new()),
+            (KeyCode::Enter | KeyCode::Char('p'), Some(_)) if !self.as_root => self.set_error(String::from(
+                "Only root can set other users' passwords, use the Password view for your own",
+            )),
+            (KeyCode::Enter | KeyCode::Char('p'), Some(_)) => self.mode = Mode::Password(Secret::default()),
+            (KeyCode::Char('l'), Some(user)) => {
+                let locked = user.locked != Some(true);
+                if locked && self.protect(&user, "lock") {
+                    return None;
+                }
+                let done = format!("{} {}", if locked { "Locked" } else { "Unlocked" }, user.name);
+                self.apply(done, |privilege| users::set_locked(&user.name, locked, privilege));
+            }
+            (KeyCode::Char('s'), Some(user)) => {
+                let sudo = !user.is_sudoer();
+                if !sudo && self.protect(&user, "take sudo rights from") {
+                    return None;
+                }
+                let done = format!("{} can {}use sudo", user.name, if sudo { "" } else { "no longer " });
+                self.apply(done, |privilege| users::set_sudo(&user.name, sudo, privilege));
+            }
+            (KeyCode::Char('d') | KeyCode::Delete, Some(user)) => {
+                if self.protect(&user, "delete") {
+                    return None;
+                }
+                self.mode = Mode::Delete;
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl ViewComponent for UsersView {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn needs_root(&self) -> bool {
+        true
+    }
+
+    fn register_config_handler(&mut self, config: &Config) {
+        self.policy = config.password_policy.clone();
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        match &mut self.mode {
+            Mode::Browse => return Ok(self.handle_browse_key(key)),
+            Mode::Add(input) => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Enter => {
+                    let name = input.trim().to_string();
+                    self.add(name);
+                }
+                _ => {}
+            },
+            Mode::Password(input) => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Enter => {
+                    let password = input.clone();
+                    if let Some(user) = self.selected() {
+                        self.set_password(&user, &password);
+                    }
+                }
+                _ => {}
+            },
+            Mode::Delete => match key.code {
+                KeyCode::Char('y') => {
+                    self.mode = Mode::Browse;
+                    if let Some(user) = self.selected() {
+                        let done = format!("Deleted {} and {}", user.name, user.home.display());
+                        self.apply(done, |privilege| users::delete(&user.name, privilege));
+                    }
+                }
+                KeyCode::Char('n') | KeyCode::Esc => self.mode = Mode::Browse,
+                _ => {}
+            },
+        }
+        Ok(None)
+    }
+
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        match &mut self.mode {
+            Mode::Add(input) => input.push_str(text.trim()),
+            Mode::Password(input) => text.trim_end_matches('\n').chars().for_each(|c| input.push(c)),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let area = Block::new().padding(Padding::horizontal(2)).inner(area);
+        let [list_area, input_area, status_area, help_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(2),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let users = match &self.users {
+            Ok(users) => users,
+            Err(e) => {
+                let message = Paragraph::new(vec![
+                    Line::from(Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400))),
+                    Line::from("Press r to retry."),
+                ]);
+                f.render_widget(message, area);
+                return Ok(());
+            }
+        };
+
+        let items: Vec<ListItem> = users
+            .iter()
+            .map(|user| {
+                let mut spans = vec![
+                    Span::raw(format!("{:<16} ", user.name)),
+                    Span::styled(format!("{:<20} ", user.full_name), Style::new().fg(tailwind::SLATE.c400)),
+                ];
+                if user.is_sudoer() {
+                    spans.push(Span::styled("sudo ", Style::new().fg(tailwind::AMBER.c400)));
+                }
+                if user.locked == Some(true) {
+                    spans.push(Span::styled("locked ", Style::new().fg(tailwind::RED.c400)));
+                }
+                let groups: Vec<&str> =
+                    user.groups.iter().map(String::as_str).filter(|g| *g != users::SUDO_GROUP).collect();
+                spans.push(Span::styled(groups.join(","), Style::new().fg(tailwind::SLATE.c500)));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::BOTTOM).title("Users"))
+            .highlight_style(Style::new().bg(tailwind::SLATE.c700).add_modifier(Modifier::BOLD))
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(list, list_area, &mut self.state);
+
+        let name = self.selected().map(|user| user.name).unwrap_or_default();
+        let (input, help) = match &self.mode {
+            Mode::Browse => (
+                Line::default(),
+                "a: add  Enter/p: password  l: lock/unlock  s: sudo  d: delete  r: reload  Esc: back",
+            ),
+            Mode::Add(input) => (
+                Line::from(vec![Span::raw("New user name: ").bold(), Span::raw(input.clone()), Span::raw("█")]),
+                "Enter: create  Esc: cancel",
+            ),
+            Mode::Password(input) => {
+                let mut spans = vec![
+                    Span::raw(format!("New password for {name}: ")).bold(),
+                    Span::raw("*".repeat(input.len())),
+                    Span::raw("█  "),
+                ];
+                if !input.is_empty() {
+                    let problems = self.policy.check(&name, input.expose());
+                    spans.push(match problems.first() {
+                        Some(problem) => Span::styled(problem.clone(), Style::new().fg(tailwind::RED.c400)),
+                        None => Span::styled("Strong enough", Style::new().fg(tailwind::LIME.c500)),
+                    });
+                }
+                (Line::from(spans), "Enter: set  Esc: cancel")
+            }
+            Mode::Delete => (
+                Line::from(format!("Delete {name} and their home directory? y: delete  n: keep"))
+                    .fg(tailwind::AMBER.c400),
+                "",
+            ),
+        };
+        f.render_widget(Paragraph::new(input), input_area);
+
+        if let Some(status) = &self.status {
+            f.render_widget(Paragraph::new(Line::from(status.clone())), status_area);
+        }
+        f.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::components::views::test_util::{press, Fixture};
+
+    #[test]
+    fn test_manage_users() {
+        let fixture = Fixture::new(&[
+            (
+                "etc/passwd",
+                "debian:x:1000:1000:Debian:/home/debian:/bin/bash\nalice:x:1001:1001::/home/alice:/bin/bash\n",
+            ),
+            ("etc/group", "sudo:x:27:debian\ngpio:x:997:debian\n"),
+        ]);
+        let mut view = UsersView::with_root(fixture.root(), fixture.privilege(), true, String::from("debian"));
+
+        // The current user keeps their rights.
+        press(&mut view, KeyCode::Char('s'));
+        press(&mut view, KeyCode::Char('d'));
+        assert_eq!(view.mode, Mode::Browse);
+
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Char('s'));
+        press(&mut view, KeyCode::Char('l'));
+
+        press(&mut view, KeyCode::Char('a'));
+        view.handle_paste(String::from("bob\n")).unwrap();
+        press(&mut view, KeyCode::Enter);
+        // The recorder creates nobody, so no password is asked for, least of all alice's.
+        assert_eq!(view.mode, Mode::Browse);
+        assert!(view.status.as_ref().is_some_and(|s| s.content.contains("not in the user list")));
+
+        press(&mut view, KeyCode::Char('d'));
+        press(&mut view, KeyCode::Char('y'));
+        assert_eq!(
+            fixture.recorder.commands(),
+            [
+                "gpasswd --add alice sudo",
+                "usermod --lock -- alice",
+                "useradd --create-home --shell /bin/bash --groups gpio -- bob",
+                "userdel --remove -- alice",
+            ]
+        );
+    }
+}
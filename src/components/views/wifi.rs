@@ -1,16 +1,29 @@
 use std::sync::{atomic::AtomicBool, Arc};
 
+use chrono::Local;
+
 use anyhow::anyhow;
-use async_channel::Receiver;
+use async_channel::{Receiver, Sender};
 use crossterm::event::{KeyCode, KeyEvent};
 use futures::{future::ok, FutureExt};
 use iwdrs::{agent::Agent, modes::Mode, session::Session};
-use ratatui::{layout::*, style::*, text::*, widgets::*};
+use ratatui::{layout::*, style::{palette::tailwind, *}, text::*, widgets::*};
 use color_eyre::Result;
 use strum::Display;
 use tokio::sync::mpsc::{self, UnboundedSender};
 
-use crate::{action::Action, app::AppResult, networks::{adaptor::Adapter, network::Network, rfkill}, widgets::{ButtonState, ButtonWidget}};
+use crate::{
+    action::Action,
+    app::AppResult,
+    networks::{
+        adaptor::Adapter,
+        network::{validate_passphrase, validate_ssid, Network},
+        rfkill,
+        station::connect_hidden_network,
+    },
+    system::secret::Secret,
+    widgets::{ButtonState, ButtonWidget},
+};
 
 use super::ViewComponent;
 
@@ -25,13 +38,95 @@ pub struct WifiView {
     scan_button_state: ButtonState,
     list_state: ListState,
     sorted_networks: Vec<(Network, i16)>,
+    known_state: ListState,
+    sorted_known_networks: Vec<(Network, i16)>,
+    /// The known network `f` was pressed on once, a second press forgets it.
+    confirm_forget: Option<String>,
     tick_count: u8,
+    /// Answers the passphrase request of the agent.
+    passkey_sender: Option<Sender<String>>,
+    /// Makes the agent turn the passphrase request down.
+    cancel_signal_sender: Option<Sender<()>>,
+    /// Set by the agent while iwd waits for a passphrase.
+    authentication_required: Arc<AtomicBool>,
+    /// The network a connection attempt is running for.
+    connecting: Option<String>,
+    /// Whether the passphrase request of the running attempt was canceled.
+    canceled: bool,
+    prompt: Option<PassphrasePrompt>,
+    hidden_form: Option<HiddenForm>,
+    /// The passphrase given with a hidden network, handed to the agent when iwd asks for it.
+    pending_passphrase: Option<Secret>,
+    status: Option<Span<'static>>,
+}
+
+/// The passphrase asked for while connecting to `ssid`.
+#[derive(Debug, Clone)]
+struct PassphrasePrompt {
+    ssid: String,
+    passphrase: Secret,
+    show: bool,
+}
+
+/// The network that does not broadcast its name to join, asked for by "Connect to hidden network…".
+#[derive(Debug, Clone, Default)]
+struct HiddenForm {
+    ssid: String,
+    security: Security,
+    passphrase: Secret,
+    show: bool,
+    field: HiddenField,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Security {
+    Open,
+    #[default]
+    Personal,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum HiddenField {
+    #[default]
+    Ssid,
+    Security,
+    Passphrase,
+    Show,
+}
+
+impl HiddenForm {
+    fn fields(&self) -> &'static [HiddenField] {
+        match self.security {
+            Security::Open => &[HiddenField::Ssid, HiddenField::Security],
+            Security::Personal => {
+                &[HiddenField::Ssid, HiddenField::Security, HiddenField::Passphrase, HiddenField::Show]
+            }
+        }
+    }
+
+    fn move_field(&mut self, step: isize) {
+        let fields = self.fields();
+        let i = fields.iter().position(|f| *f == self.field).unwrap_or_default() as isize;
+        self.field = fields[(i + step).rem_euclid(fields.len() as isize) as usize];
+    }
+
+    /// What keeps the form from being submitted, if anything.
+    fn problem(&self) -> Option<String> {
+        if let Err(e) = validate_ssid(&self.ssid) {
+            return Some(e.to_string());
+        }
+        match self.security {
+            Security::Personal => validate_passphrase(self.passphrase.expose()).err().map(|e| e.to_string()),
+            Security::Open => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Focus {
     None,
     Scan,
+    Known,
     List,
 }
 
@@ -98,7 +193,6 @@ impl WifiView {
         let current_mode = adapter.device.mode.clone();
 
         let (passkey_sender, passkey_receiver) = async_channel::unbounded();
-        let show_password = false;
         let (cancel_signal_sender, cancel_signal_receiver) = async_channel::unbounded();
 
         let authentication_required = Arc::new(AtomicBool::new(false));
@@ -130,14 +224,9 @@ impl WifiView {
             agent_manager,
         };
 
-        // Generate sorted network list
-        let sorted_networks = impl_wi_fi.adapter.device.station.as_ref()
-            .map(|station| {
-                let mut networks = station.new_networks.clone();
-                networks.sort_by(|a, b| b.1.cmp(&a.1)); // Descending sort by signal
-                networks
-            })
-            .unwrap_or_default();
+        let station = impl_wi_fi.adapter.device.station.as_ref();
+        let sorted_networks = station.map(|station| by_signal(&station.new_networks)).unwrap_or_default();
+        let sorted_known_networks = station.map(|station| by_signal(&station.known_networks)).unwrap_or_default();
 
         WifiView {
             title: String::from("WiFi"),
@@ -149,7 +238,19 @@ impl WifiView {
             list_state: ListState::default(),
             sender: Some(sender),
             sorted_networks,
+            known_state: ListState::default(),
+            sorted_known_networks,
+            confirm_forget: None,
             tick_count: 0_u8,
+            passkey_sender: Some(passkey_sender),
+            cancel_signal_sender: Some(cancel_signal_sender),
+            authentication_required,
+            connecting: None,
+            canceled: false,
+            prompt: None,
+            hidden_form: None,
+            pending_passphrase: None,
+            status: None,
         }
     }
 
@@ -164,7 +265,19 @@ impl WifiView {
             list_state: ListState::default(),
             sender: None,
             sorted_networks: Vec::new(),
+            known_state: ListState::default(),
+            sorted_known_networks: Vec::new(),
+            confirm_forget: None,
             tick_count: 0_u8,
+            passkey_sender: None,
+            cancel_signal_sender: None,
+            authentication_required: Arc::default(),
+            connecting: None,
+            canceled: false,
+            prompt: None,
+            hidden_form: None,
+            pending_passphrase: None,
+            status: None,
         }
     }
 
@@ -172,14 +285,21 @@ impl WifiView {
         self.focus = match self.focus {
             Focus::List => {
                 if let Some(0) = self.list_state.selected() {
-                    // Top of list, move to Scan button
-                    Focus::Scan
+                    // Top of list, move to the known networks or the Scan button
+                    if self.sorted_known_networks.is_empty() { Focus::Scan } else { Focus::Known }
                 } else {
                     // Move up in list
                     self.move_list_selection(-1);
                     Focus::List
                 }
             }
+            Focus::Known => match self.known_state.selected() {
+                Some(0) | None => Focus::Scan,
+                Some(selected) => {
+                    self.known_state.select(Some(selected - 1));
+                    Focus::Known
+                }
+            },
             Focus::Scan => Focus::None,
             Focus::None => Focus::None,
         };
@@ -189,16 +309,34 @@ impl WifiView {
     fn move_focus_down(&mut self) {
         self.focus = match self.focus {
             Focus::None => Focus::Scan,
+            Focus::Scan if !self.sorted_known_networks.is_empty() => {
+                if self.known_state.selected().is_none() {
+                    self.known_state.select(Some(0));
+                }
+                Focus::Known
+            }
+            Focus::Known => match self.known_state.selected() {
+                Some(selected) if selected + 1 < self.sorted_known_networks.len() => {
+                    self.known_state.select(Some(selected + 1));
+                    Focus::Known
+                }
+                _ => {
+                    if self.list_state.selected().is_none() {
+                        self.list_state.select(Some(0));
+                    }
+                    Focus::List
+                }
+            },
             Focus::Scan => {
                 // Initialize list selection if empty
-                if self.list_state.selected().is_none() && !self.sorted_networks.is_empty() {
+                if self.list_state.selected().is_none() {
                     self.list_state.select(Some(0));
                 }
                 Focus::List
             }
             Focus::List => {
                 if let Some(selected) = self.list_state.selected() {
-                    if selected < self.sorted_networks.len() - 1 {
+                    if selected < self.sorted_networks.len() {
                         self.move_list_selection(1);
                     }
                 }
@@ -212,11 +350,197 @@ impl WifiView {
         let current = self.list_state.selected().unwrap_or(0) as i32;
         let new_index = current + offset;
         
-        if new_index >= 0 && new_index < self.sorted_networks.len() as i32 {
+        // The entry for hidden networks follows the networks in range.
+        if new_index >= 0 && new_index <= self.sorted_networks.len() as i32 {
             self.list_state.select(Some(new_index as usize));
         }
     }
 
+    /// The network selected in the focused list.
+    fn selected_network(&self) -> Option<&Network> {
+        let (state, networks) = match self.focus {
+            Focus::Known => (&self.known_state, &self.sorted_known_networks),
+            Focus::List => (&self.list_state, &self.sorted_networks),
+            Focus::None | Focus::Scan => return None,
+        };
+        state.selected().and_then(|i| networks.get(i)).map(|(network, _)| network)
+    }
+
+    /// Connect to the selected network. The agent asks for the passphrase if iwd needs one.
+    fn connect_selected(&mut self) {
+        let Some(network) = self.selected_network() else { return };
+        if network.is_connected {
+            let ssid = network.name.clone();
+            self.set_status(format!("Already connected to {ssid}"), tailwind::SLATE.c400);
+            return;
+        }
+        if let Some(ssid) = &self.connecting {
+            self.set_status(format!("Still connecting to {ssid}…"), tailwind::AMBER.c400);
+            return;
+        }
+        let Some(sender) = self.sender.clone() else { return };
+        let network = network.clone();
+        let ssid = network.name.clone();
+        self.set_status(format!("Connecting to {ssid}…"), tailwind::AMBER.c400);
+        self.connecting = Some(ssid.clone());
+        self.canceled = false;
+        tokio::spawn(async move {
+            let error = network.connect().await.err().map(|e| e.to_string());
+            let _ = sender.send(Action::WifiConnected { ssid, error });
+        });
+    }
+
+    fn handle_hidden_key(&mut self, code: KeyCode) {
+        let Some(form) = self.hidden_form.as_mut() else { return };
+        match (form.field, code) {
+            (_, KeyCode::Esc) => self.hidden_form = None,
+            (_, KeyCode::Tab | KeyCode::Down) => form.move_field(1),
+            (_, KeyCode::BackTab | KeyCode::Up) => form.move_field(-1),
+            (_, KeyCode::Enter) if form.problem().is_none() => self.connect_hidden(),
+            (_, KeyCode::Enter) => {}
+            (HiddenField::Security, KeyCode::Left | KeyCode::Right | KeyCode::Char(' ')) => {
+                form.security = match form.security {
+                    Security::Open => Security::Personal,
+                    Security::Personal => Security::Open,
+                };
+            }
+            (HiddenField::Show, KeyCode::Char(' ')) => form.show = !form.show,
+            (HiddenField::Ssid, KeyCode::Char(c)) => form.ssid.push(c),
+            (HiddenField::Ssid, KeyCode::Backspace) => {
+                form.ssid.pop();
+            }
+            (HiddenField::Passphrase, KeyCode::Char(c)) => form.passphrase.push(c),
+            (HiddenField::Passphrase, KeyCode::Backspace) => {
+                form.passphrase.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Join the network of the hidden network form. Its passphrase is kept for the agent, which
+    /// only asks when the network turns out to be protected.
+    fn connect_hidden(&mut self) {
+        let Some(form) = self.hidden_form.take() else { return };
+        if let Some(ssid) = &self.connecting {
+            self.set_status(format!("Still connecting to {ssid}…"), tailwind::AMBER.c400);
+            return;
+        }
+        let Some(sender) = self.sender.clone() else { return };
+        let ssid = form.ssid.clone();
+        self.pending_passphrase = (form.security == Security::Personal).then(|| form.passphrase.clone());
+        self.set_status(format!("Looking for the hidden network {ssid}…"), tailwind::AMBER.c400);
+        self.connecting = Some(ssid.clone());
+        self.canceled = false;
+        tokio::spawn(async move {
+            let error = connect_hidden_network(&ssid).await.err().map(|e| e.to_string());
+            let _ = sender.send(Action::WifiConnected { ssid, error });
+        });
+    }
+
+    /// Forget the selected known network, after `f` was pressed on it a second time.
+    fn forget_selected(&mut self) {
+        let Some(network) = self.selected_network().cloned() else { return };
+        let Some(known) = network.known_network else { return };
+        if self.confirm_forget.as_ref() != Some(&known.name) {
+            let message = format!("Press f again to forget {} and its passphrase", known.name);
+            self.set_status(message, tailwind::AMBER.c400);
+            self.confirm_forget = Some(known.name);
+            return;
+        }
+        self.confirm_forget = None;
+        self.change_known(known.name.clone(), async move {
+            known.forget().await?;
+            Ok(format!("Forgot {}", known.name))
+        });
+    }
+
+    fn toggle_autoconnect_selected(&mut self) {
+        let Some(known) = self.selected_network().and_then(|network| network.known_network.clone()) else {
+            return;
+        };
+        self.change_known(known.name.clone(), async move {
+            known.toggle_autoconnect().await?;
+            let state = if known.is_autoconnect { "off" } else { "on" };
+            Ok(format!("Autoconnect for {} is {state}", known.name))
+        });
+    }
+
+    /// Run `change` on the known network `name` in the background. Both success and failure come
+    /// back as `Action::KnownNetworkChanged`, which reaches the view even when another one is open.
+    fn change_known(
+        &self,
+        name: String,
+        change: impl std::future::Future<Output = AppResult<String>> + Send + 'static,
+    ) {
+        let Some(sender) = self.sender.clone() else { return };
+        tokio::spawn(async move {
+            let result = change.await.map_err(|e| format!("Unable to change {name}: {e}"));
+            let _ = sender.send(Action::KnownNetworkChanged(result));
+        });
+    }
+
+    /// Reload the adapter state in the background, it comes back as `Action::UpdateWifiState`.
+    fn refresh(&self) {
+        let (Some(iwd_wifi), Some(sender)) = (self.iwd_wifi.clone(), self.sender.clone()) else {
+            return;
+        };
+        let mut adapter = iwd_wifi.adapter.clone();
+        let current_mode = adapter.device.mode.clone();
+        tokio::spawn(async move {
+            match adapter.refresh().await {
+                Ok(_) => {
+                    let new_impl = ImplWiFi {
+                        session: iwd_wifi.session,
+                        adapter,
+                        current_mode,
+                        agent_manager: iwd_wifi.agent_manager,
+                    };
+                    let _ = sender.send(Action::UpdateWifiState(new_impl));
+                }
+                Err(e) => {
+                    let _ = sender.send(Action::Error(format!("Refresh failed: {}", e)));
+                }
+            }
+        });
+    }
+
+    fn handle_prompt_key(&mut self, code: KeyCode) {
+        let Some(prompt) = self.prompt.as_mut() else { return };
+        match code {
+            KeyCode::Char(c) => prompt.passphrase.push(c),
+            KeyCode::Backspace => {
+                prompt.passphrase.pop();
+            }
+            KeyCode::Tab => prompt.show = !prompt.show,
+            KeyCode::Enter => {
+                if validate_passphrase(prompt.passphrase.expose()).is_err() {
+                    return;
+                }
+                let passphrase = prompt.passphrase.expose().to_string();
+                let ssid = prompt.ssid.clone();
+                self.prompt = None;
+                self.authentication_required.store(false, std::sync::atomic::Ordering::Relaxed);
+                if let Some(sender) = &self.passkey_sender {
+                    let _ = sender.try_send(passphrase);
+                }
+                self.set_status(format!("Authenticating with {ssid}…"), tailwind::AMBER.c400);
+            }
+            KeyCode::Esc => {
+                self.prompt = None;
+                self.canceled = true;
+                self.authentication_required.store(false, std::sync::atomic::Ordering::Relaxed);
+                if let Some(sender) = &self.cancel_signal_sender {
+                    let _ = sender.try_send(());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_status(&mut self, message: String, color: Color) {
+        self.status = Some(Span::styled(message, Style::new().fg(color)));
+    }
+
     fn update_states(&mut self) {
         self.scan_button_state = if self.focus == Focus::Scan {
             ButtonState::Selected
@@ -234,85 +558,92 @@ impl ViewComponent for WifiView {
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
         match action {
             Action::UpdateWifiState(impl_wi_fi) => {
-                // Generate sorted network list
-                let sorted_networks = impl_wi_fi.adapter.device.station.as_ref()
-                    .map(|station| {
-                        let mut networks = station.new_networks.clone();
-                        networks.sort_by(|a, b| b.1.cmp(&a.1)); // Descending sort by signal
-                        networks
-                    })
-                    .unwrap_or_default();
-                
-                self.sorted_networks = sorted_networks;
+                let station = impl_wi_fi.adapter.device.station.as_ref();
+                // The result of an attempt can get lost, iwd's state tells when it is over.
+                let waiting = self.prompt.is_some()
+                    || self.authentication_required.load(std::sync::atomic::Ordering::Relaxed);
+                if !waiting && station.is_some_and(|station| station.state != "connecting") {
+                    self.connecting = None;
+                    self.pending_passphrase = None;
+                }
+                self.sorted_networks = station.map(|station| by_signal(&station.new_networks)).unwrap_or_default();
+                self.sorted_known_networks =
+                    station.map(|station| by_signal(&station.known_networks)).unwrap_or_default();
                 self.iwd_wifi = Some(impl_wi_fi);
+                if let Some(selected) = self.known_state.selected() {
+                    let last = self.sorted_known_networks.len().checked_sub(1);
+                    self.known_state.select(last.map(|last| selected.min(last)));
+                }
             }
             Action::ScanComplete => {
                 self.is_scanning = false;
-                let iwd_wifi_clone = self.iwd_wifi.clone().unwrap();
-                let mut adapter_clone = iwd_wifi_clone.adapter.clone();
-                let sender = self.sender.clone().unwrap();
-                let current_mode = adapter_clone.device.mode.clone();
-
-                // In your async block
-                tokio::spawn(async move {
-                    match adapter_clone.refresh().await {
-                        Ok(_) => {
-                            let new_impl = ImplWiFi {
-                                session: iwd_wifi_clone.session,
-                                adapter: adapter_clone,
-                                current_mode: current_mode,
-                                agent_manager: iwd_wifi_clone.agent_manager,
-                            };
-
-                            // Send the update through the channel
-                            let _ = sender.send(Action::UpdateWifiState(new_impl));
+                self.refresh();
+            }
+            Action::Tick => {
+                if self.prompt.is_none() && self.authentication_required.load(std::sync::atomic::Ordering::Relaxed) {
+                    match (self.pending_passphrase.take(), &self.passkey_sender) {
+                        (Some(passphrase), Some(sender)) => {
+                            self.authentication_required.store(false, std::sync::atomic::Ordering::Relaxed);
+                            let _ = sender.try_send(passphrase.expose().to_string());
                         }
-                        Err(e) => {
-                            let _ = sender.send(Action::Error(format!("Refresh failed: {}", e)));
+                        _ => {
+                            let ssid = self.connecting.clone().unwrap_or_default();
+                            self.prompt = Some(PassphrasePrompt { ssid, passphrase: Secret::default(), show: false });
                         }
                     }
-                });
-            }
-            Action::Tick => {
+                }
                 if self.tick_count <= 180 {
                     self.tick_count += 1;
                     return Ok(None);
                 }
                 self.tick_count = 0_u8;
-                let iwd_wifi_clone = self.iwd_wifi.clone().unwrap();
-                let mut adapter_clone = iwd_wifi_clone.adapter.clone();
-                let sender = self.sender.clone().unwrap();
-                let current_mode = adapter_clone.device.mode.clone();
-
-                // In your async block
-                tokio::spawn(async move {
-                    match adapter_clone.refresh().await {
-                        Ok(_) => {
-                            let new_impl = ImplWiFi {
-                                session: iwd_wifi_clone.session,
-                                adapter: adapter_clone,
-                                current_mode: current_mode,
-                                agent_manager: iwd_wifi_clone.agent_manager,
-                            };
-
-                            // Send the update through the channel
-                            let _ = sender.send(Action::UpdateWifiState(new_impl));
-                        }
-                        Err(e) => {
-                            let _ = sender.send(Action::Error(format!("Refresh failed: {}", e)));
-                        }
+                self.refresh();
+            }
+            Action::WifiConnected { ssid, error } => {
+                self.connecting = None;
+                self.pending_passphrase = None;
+                match error {
+                    None => self.set_status(format!("Connected to {ssid}"), tailwind::LIME.c500),
+                    Some(_) if self.canceled => {
+                        self.set_status(format!("Canceled connecting to {ssid}"), tailwind::SLATE.c400)
                     }
-                });
+                    Some(e) => self.set_status(format!("Unable to connect to {ssid}: {e}"), tailwind::RED.c400),
+                }
+                self.refresh();
+            }
+            Action::KnownNetworkChanged(result) => {
+                match result {
+                    Ok(message) => self.set_status(message, tailwind::LIME.c500),
+                    Err(e) => self.set_status(e, tailwind::RED.c400),
+                }
+                self.refresh();
             }
+            Action::Error(e) => self.set_status(e, tailwind::RED.c400),
             _ => {}
         }
         Ok(None)
     }
 
     fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key.code);
+            return Ok(None);
+        }
+        if self.hidden_form.is_some() {
+            self.handle_hidden_key(key.code);
+            return Ok(None);
+        }
+        if key.code != KeyCode::Char('f') {
+            self.confirm_forget = None;
+        }
         match key.code {
             KeyCode::Up => self.move_focus_up(),
             KeyCode::Down => self.move_focus_down(),
+            // The agent's passphrase request only reaches this view while it is open.
+            KeyCode::Backspace if self.connecting.is_some() => {
+                let ssid = self.connecting.clone().unwrap_or_default();
+                self.set_status(format!("Still connecting to {ssid}…, wait until it finishes"), tailwind::AMBER.c400);
+            }
             KeyCode::Backspace => { return Ok(Some(Action::BackToMenu)); },
             KeyCode::Enter => match self.focus {
                 Focus::None => {},
@@ -336,13 +667,32 @@ impl ViewComponent for WifiView {
                         }
                     });
                 },
-                Focus::List => {},
+                Focus::List if self.list_state.selected() == Some(self.sorted_networks.len()) => {
+                    self.hidden_form = Some(HiddenForm::default());
+                }
+                Focus::Known | Focus::List => self.connect_selected(),
             }
+            KeyCode::Char('a') if self.focus == Focus::Known => self.toggle_autoconnect_selected(),
+            KeyCode::Char('f') | KeyCode::Delete if self.focus == Focus::Known => self.forget_selected(),
             _ => {},
         }
         Ok(None)
     }
 
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        let text = text.trim_end_matches(['\r', '\n']);
+        if let Some(prompt) = self.prompt.as_mut() {
+            text.chars().for_each(|c| prompt.passphrase.push(c));
+        } else if let Some(form) = self.hidden_form.as_mut() {
+            match form.field {
+                HiddenField::Ssid => form.ssid.push_str(text),
+                HiddenField::Passphrase => text.chars().for_each(|c| form.passphrase.push(c)),
+                HiddenField::Security | HiddenField::Show => {}
+            }
+        }
+        Ok(None)
+    }
+
     fn draw(&mut self, f: &mut ratatui::Frame<'_>, area: ratatui::prelude::Rect) -> color_eyre::eyre::Result<()> {
         let area = Block::default().padding(Padding::horizontal(2)).inner(area);
 
@@ -367,18 +717,15 @@ impl ViewComponent for WifiView {
                 .state(self.scan_button_state);
 
             // Know Network List
-            let know_network = self.iwd_wifi.as_ref().unwrap().adapter.device.station.as_ref()
-                    .map(|station| {
-                        let mut networks = station.known_networks.clone();
-                        networks.sort_by(|a, b| b.1.cmp(&a.1)); // Descending sort by signal
-                        networks
-                    })
-                    .unwrap_or_default();
-
-            let items: Vec<ListItem> = know_network
+            let items: Vec<ListItem> = self.sorted_known_networks
                 .iter()
-                .enumerate()
-                .map(|(i, (net, signal))| {
+                .map(|(net, signal)| {
+                    let known = net.known_network.as_ref();
+                    let autoconnect = if known.is_some_and(|k| k.is_autoconnect) { "autoconnect" } else { "manual" };
+                    let last_connected = known
+                        .and_then(|k| k.last_connected)
+                        .map(|t| format!("last connected {}", t.with_timezone(&Local).format("%Y-%m-%d %H:%M")))
+                        .unwrap_or_default();
                     let line = Line::from(vec![
                         format!("{}    ", net.name).into(),
                         Span::raw({
@@ -395,7 +742,12 @@ impl ViewComponent for WifiView {
                                 n if (25..50).contains(&n) => format!("{:3}% 󰤢", signal),
                                 _ => format!("{:3}% 󰤟", signal),
                             }
-                }       ),
+                        }),
+                        Span::styled(
+                            format!("  {autoconnect:<12}{last_connected}"),
+                            Style::new().fg(tailwind::SLATE.c400),
+                        ),
+                        Span::raw(if net.is_connected { "  connected" } else { "" }),
                     ]);
 
                     ListItem::new(line).style(Style::default())
@@ -404,10 +756,14 @@ impl ViewComponent for WifiView {
 
             let know_list = List::new(items)
                 .block(Block::default().borders(Borders::ALL).title("Known Networks"))
-                .highlight_style(Style::default().bg(Color::DarkGray));
+                .highlight_style(if self.focus == Focus::Known {
+                    Style::default().bg(Color::DarkGray).fg(Color::White)
+                } else {
+                    Style::default()
+                });
 
             // Network List
-            let items: Vec<ListItem> = self.sorted_networks
+            let mut items: Vec<ListItem> = self.sorted_networks
                 .iter()
                 .enumerate()
                 .map(|(i, (net, signal))| {
@@ -441,18 +797,225 @@ impl ViewComponent for WifiView {
                     ListItem::new(line).style(style)
                 })
                 .collect();
+            items.push(ListItem::new(Line::from("Connect to hidden network…").italic()));
 
             let list = List::new(items)
                 .block(Block::default().borders(Borders::ALL).title("Networks"))
                 .highlight_style(Style::default().bg(Color::DarkGray));
             
-            f.render_widget(know_list, layout[1]);
+            f.render_stateful_widget(know_list, layout[1], &mut self.known_state);
             f.render_stateful_widget(list, layout[2], &mut self.list_state);
             f.render_widget(scan_btn, layout[0]);
+
+            let help = match self.focus {
+                Focus::Known => "↑↓: move  Enter: connect  a: autoconnect on/off  f: forget  Backspace: back",
+                _ => "↑↓: move  Enter: scan / connect  Backspace: back",
+            };
+            let help = Line::from(help).fg(tailwind::SLATE.c400);
+            let status = self.status.clone().map(Line::from).unwrap_or_default();
+            f.render_widget(Paragraph::new(vec![status, help]), layout[3]);
+
+            if let Some(form) = &self.hidden_form {
+                draw_hidden_form(f, area, form);
+            }
+            if let Some(prompt) = &self.prompt {
+                draw_prompt(f, area, prompt);
+            }
         }
         else {
             Paragraph::new(Line::raw(self.error.clone().unwrap()).centered()).render(area, f.buffer_mut());
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// `networks` with the strongest first.
+fn by_signal(networks: &[(Network, i16)]) -> Vec<(Network, i16)> {
+    let mut networks = networks.to_vec();
+    networks.sort_by_key(|(_, signal)| std::cmp::Reverse(*signal));
+    networks
+}
+
+/// `height` rows in the middle of `area`, at most 60 columns wide.
+fn popup_area(area: Rect, height: u16) -> Rect {
+    let [_, area, _] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(height), Constraint::Fill(1)]).areas(area);
+    let [_, area, _] = Layout::horizontal([Constraint::Fill(1), Constraint::Max(60), Constraint::Fill(1)]).areas(area);
+    area
+}
+
+/// The hidden network form over the middle of `area`.
+fn draw_hidden_form(f: &mut ratatui::Frame<'_>, area: Rect, form: &HiddenForm) {
+    let field = |label: &str, value: String, field: HiddenField| {
+        let focused = form.field == field;
+        let style = if focused { Style::new().fg(tailwind::BLUE.c400) } else { Style::new() };
+        Line::from(vec![
+            Span::styled(format!("{label:<12}"), style),
+            Span::raw(value),
+            Span::raw(if focused && matches!(field, HiddenField::Ssid | HiddenField::Passphrase) { "█" } else { "" }),
+        ])
+    };
+    let security = match form.security {
+        Security::Open => "◀ Open ▶",
+        Security::Personal => "◀ WPA/WPA2/WPA3 Personal ▶",
+    };
+    let mut lines = vec![
+        field("SSID", form.ssid.clone(), HiddenField::Ssid),
+        field("Security", security.to_string(), HiddenField::Security),
+    ];
+    if form.security == Security::Personal {
+        let passphrase =
+            if form.show { form.passphrase.expose().to_string() } else { "*".repeat(form.passphrase.len()) };
+        lines.push(field("Passphrase", passphrase, HiddenField::Passphrase));
+        let show = if form.show { "[x] Show passphrase" } else { "[ ] Show passphrase" };
+        lines.push(field("", show.to_string(), HiddenField::Show));
+    }
+    let touched = !form.ssid.is_empty() || !form.passphrase.is_empty();
+    lines.push(match form.problem() {
+        Some(problem) if touched => Line::from(problem).fg(tailwind::RED.c400),
+        _ => Line::default(),
+    });
+
+    let area = popup_area(area, lines.len() as u16 + 2);
+    let block = Block::bordered()
+        .title(" Connect to hidden network ")
+        .title_bottom(Line::from(" Tab: next  Space: change  Enter: connect  Esc: cancel ").right_aligned())
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// The passphrase modal over the middle of `area`.
+fn draw_prompt(f: &mut ratatui::Frame<'_>, area: Rect, prompt: &PassphrasePrompt) {
+    let popup_area = popup_area(area, 6);
+
+    let passphrase = if prompt.show {
+        prompt.passphrase.expose().to_string()
+    } else {
+        "*".repeat(prompt.passphrase.len())
+    };
+    let problem = match validate_passphrase(prompt.passphrase.expose()) {
+        Err(e) if !prompt.passphrase.is_empty() => Line::from(e.to_string()).fg(tailwind::RED.c400),
+        _ => Line::default(),
+    };
+    let lines = vec![
+        Line::from(vec![Span::raw("Passphrase  ").bold(), Span::raw(passphrase), Span::raw("█")]),
+        problem,
+        Line::from(if prompt.show { "[x] Show passphrase" } else { "[ ] Show passphrase" }),
+    ];
+    let block = Block::bordered()
+        .title(format!(" Passphrase for {} ", prompt.ssid))
+        .title_bottom(Line::from(" Enter: connect  Tab: show/hide  Esc: cancel ").right_aligned())
+        .padding(Padding::horizontal(1));
+    f.render_widget(Clear, popup_area);
+    f.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use crossterm::event::KeyModifiers;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::components::views::test_util::{press, type_text};
+
+    #[test]
+    fn test_passphrase_prompt_feeds_agent() {
+        let mut view = WifiView::error_state("no iwd");
+        let (passkey_sender, passkey_receiver) = async_channel::unbounded();
+        let (cancel_signal_sender, cancel_signal_receiver) = async_channel::unbounded();
+        view.passkey_sender = Some(passkey_sender);
+        view.cancel_signal_sender = Some(cancel_signal_sender);
+        view.connecting = Some(String::from("Home"));
+
+        // The agent flags the request, the next tick opens the prompt.
+        view.authentication_required.store(true, Ordering::Relaxed);
+        view.update(Action::Tick).unwrap();
+        assert_eq!(view.prompt.as_ref().map(|p| p.ssid.as_str()), Some("Home"));
+
+        view.handle_paste(String::from("short")).unwrap();
+        press(&mut view, KeyCode::Enter);
+        assert!(view.prompt.is_some());
+        type_text(&mut view, " enough");
+        press(&mut view, KeyCode::Tab);
+        assert!(view.prompt.as_ref().unwrap().show);
+        press(&mut view, KeyCode::Enter);
+        assert!(view.prompt.is_none());
+        assert!(!view.authentication_required.load(Ordering::Relaxed));
+        assert_eq!(passkey_receiver.try_recv().unwrap(), "short enough");
+
+        view.authentication_required.store(true, Ordering::Relaxed);
+        view.update(Action::Tick).unwrap();
+        press(&mut view, KeyCode::Esc);
+        assert!(cancel_signal_receiver.try_recv().is_ok());
+        let error = Some(String::from("The connection attempt was aborted"));
+        view.update(Action::WifiConnected { ssid: String::from("Home"), error }).unwrap();
+        assert_eq!(view.status.unwrap().content, "Canceled connecting to Home");
+        assert_eq!(view.connecting, None);
+    }
+
+    #[test]
+    fn test_known_network_results() {
+        let mut view = WifiView::error_state("no iwd");
+        view.update(Action::KnownNetworkChanged(Ok(String::from("Forgot Home")))).unwrap();
+        assert_eq!(view.status.as_ref().map(|s| s.content.as_ref()), Some("Forgot Home"));
+        view.update(Action::KnownNetworkChanged(Err(String::from("Unable to change Home: Not Found")))).unwrap();
+        assert_eq!(view.status.as_ref().map(|s| s.style.fg), Some(Some(tailwind::RED.c400)));
+
+        // Without known networks, focus skips from the Scan button to the networks in range.
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Down);
+        assert_eq!(view.focus, Focus::List);
+    }
+
+    #[test]
+    fn test_hidden_network_form() {
+        let mut view = WifiView::error_state("no iwd");
+        let (passkey_sender, passkey_receiver) = async_channel::unbounded();
+        view.passkey_sender = Some(passkey_sender);
+
+        // The entry follows the networks in range, none here.
+        for _ in 0..2 {
+            press(&mut view, KeyCode::Down);
+        }
+        press(&mut view, KeyCode::Enter);
+        assert!(view.hidden_form.is_some());
+
+        view.handle_paste(String::from("Attic")).unwrap();
+        press(&mut view, KeyCode::Tab);
+        press(&mut view, KeyCode::Tab);
+        type_text(&mut view, "attic-secret");
+        let form = view.hidden_form.as_ref().unwrap();
+        assert_eq!((form.ssid.as_str(), form.passphrase.expose()), ("Attic", "attic-secret"));
+        assert_eq!(form.problem(), None);
+
+        // Switching to open drops the passphrase fields.
+        press(&mut view, KeyCode::BackTab);
+        press(&mut view, KeyCode::Char(' '));
+        press(&mut view, KeyCode::Tab);
+        assert_eq!(view.hidden_form.as_ref().unwrap().field, HiddenField::Ssid);
+        press(&mut view, KeyCode::Esc);
+        assert!(view.hidden_form.is_none());
+
+        // A passphrase given with the form answers the agent without asking again.
+        view.pending_passphrase = Some(Secret::new("attic-secret"));
+        view.authentication_required.store(true, Ordering::Relaxed);
+        view.update(Action::Tick).unwrap();
+        assert!(view.prompt.is_none());
+        assert_eq!(passkey_receiver.try_recv().unwrap(), "attic-secret");
+    }
+
+    #[test]
+    fn test_stay_while_connecting() {
+        let mut view = WifiView::error_state("no iwd");
+        view.connecting = Some(String::from("Home"));
+        assert_eq!(view.handle_key_events(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE)).unwrap(), None);
+        view.update(Action::WifiConnected { ssid: String::from("Home"), error: None }).unwrap();
+        assert_eq!(
+            view.handle_key_events(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE)).unwrap(),
+            Some(Action::BackToMenu)
+        );
+    }
+}
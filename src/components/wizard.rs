@@ -0,0 +1,322 @@
+//! The first-boot setup wizard, which walks through the views every new board needs.
+
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, LineGauge, Padding, Paragraph},
+    Frame,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{
+    views::{
+        Deferred, HostnameView, LocaleView, OverlaysView, PasswordView, SshView, TimeView, ViewComponent, WifiView,
+    },
+    Component,
+};
+use crate::{
+    action::Action,
+    app::Mode,
+    boards::identity::Board,
+    commands::apply::apply,
+    config::Config,
+    provision::Change,
+    system::{
+        password::PasswordPolicy,
+        privilege::{self, is_root, SudoSession},
+    },
+    widgets::SudoPrompt,
+};
+
+mod summary;
+use summary::Settings;
+
+pub struct Wizard {
+    command_tx: Option<UnboundedSender<Action>>,
+    root: PathBuf,
+    /// The views to walk through, in order. The summary comes after the last one.
+    pages: Vec<Box<dyn ViewComponent>>,
+    step: usize,
+    /// Why the password has to be changed, which keeps the wizard on the password page until it is.
+    stale: Option<String>,
+    error: Option<String>,
+    /// The settings before the first page, to show what the wizard is about to change.
+    before: Settings,
+    /// The changes of the pages, made from the summary.
+    deferred: Deferred,
+    /// Whether the changes are being made.
+    applying: bool,
+    policy: PasswordPolicy,
+    session: SudoSession,
+    /// Asks for the sudo password before a page that needs it.
+    sudo_prompt: Option<SudoPrompt>,
+}
+
+impl Wizard {
+    /// Set up `user`, whose password must be changed when `stale` gives a reason.
+    pub async fn new(
+        sender: UnboundedSender<Action>,
+        session: SudoSession,
+        user: String,
+        stale: Option<String>,
+    ) -> Self {
+        let root = PathBuf::from("/");
+        let board = Board::detect();
+        let mut password = PasswordView::init();
+        if let Some(reason) = &stale {
+            let _ = password.update(Action::ForcePasswordChange { user, reason: reason.clone() });
+        }
+        let mut pages: Vec<Box<dyn ViewComponent>> = vec![
+            Box::new(password),
+            Box::new(HostnameView::init(&board, &session)),
+            Box::new(LocaleView::init(&session)),
+            Box::new(TimeView::init(&session)),
+        ];
+        if board.kind.has_wifi() {
+            pages.push(Box::new(WifiView::init(sender.clone()).await));
+        }
+        pages.push(Box::new(SshView::init(sender, &session)));
+        pages.push(Box::new(OverlaysView::init(&session)));
+        // WiFi still connects right away, as the board may need the network while being set up.
+        let deferred = Deferred::default();
+        for page in pages.iter_mut() {
+            page.defer_changes(deferred.clone());
+        }
+
+        Wizard {
+            command_tx: None,
+            before: Settings::read(&root),
+            deferred,
+            applying: false,
+            policy: PasswordPolicy::default(),
+            root,
+            pages,
+            step: 0,
+            stale,
+            error: None,
+            session,
+            sudo_prompt: None,
+        }
+    }
+
+    fn on_summary(&self) -> bool {
+        self.step == self.pages.len()
+    }
+
+    fn title(&self, step: usize) -> &str {
+        self.pages.get(step).map_or("Summary", |page| page.title())
+    }
+
+    fn next(&mut self) {
+        if let (0, Some(reason)) = (self.step, &self.stale) {
+            self.error = Some(format!("{reason}, choose a new one"));
+            return;
+        }
+        self.step = (self.step + 1).min(self.pages.len());
+        self.ask_password();
+    }
+
+    fn back(&mut self) {
+        self.step = self.step.saturating_sub(1);
+        self.ask_password();
+    }
+
+    /// Ask for the sudo password when the page needs it and it was not given yet.
+    fn ask_password(&mut self) {
+        let needs_root = self.pages.get(self.step).is_some_and(|page| page.needs_root());
+        if needs_root && !is_root() && !self.session.is_authenticated() {
+            self.sudo_prompt = Some(SudoPrompt::new(self.session.clone()));
+        }
+    }
+
+    /// Make the kept changes in the background, reported back as [`Action::WizardApplied`]. The
+    /// password comes last, as sudo may still need the old one for the others.
+    fn finish(&mut self) -> Option<Action> {
+        let mut changes = self.deferred.changes();
+        if changes.is_empty() {
+            return Some(Action::EnterMode(Mode::Home));
+        }
+        if !is_root() && !self.session.is_authenticated() {
+            self.sudo_prompt = Some(SudoPrompt::new(self.session.clone()));
+            return None;
+        }
+        changes.sort_by_key(|change| matches!(change, Change::Password { .. }));
+        let tx = self.command_tx.clone()?;
+        let (root, policy, session, deferred) =
+            (self.root.clone(), self.policy.clone(), self.session.clone(), self.deferred.clone());
+        self.applying = true;
+        tokio::spawn(async move {
+            let privilege = || privilege::detect_interactive(&session);
+            for change in changes {
+                if let Err(e) = apply(&change, &root, None, &policy, &privilege).await {
+                    let _ = tx.send(Action::WizardApplied { error: Some(format!("Unable to {change}: {e}")) });
+                    return;
+                }
+                deferred.remove(&change);
+            }
+            let _ = tx.send(Action::WizardApplied { error: None });
+        });
+        None
+    }
+
+    fn draw_progress(&self, f: &mut Frame<'_>, area: Rect) {
+        let [steps_area, gauge_area] = Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).areas(area);
+        let steps = self.pages.len() + 1;
+        let mut spans = Vec::new();
+        for i in 0..steps {
+            if i > 0 {
+                spans.push(Span::styled(" › ", Style::new().fg(tailwind::SLATE.c500)));
+            }
+            let style = match i.cmp(&self.step) {
+                std::cmp::Ordering::Less => Style::new().fg(tailwind::LIME.c500),
+                std::cmp::Ordering::Equal => Style::new().fg(tailwind::BLUE.c400).bold(),
+                std::cmp::Ordering::Greater => Style::new().fg(tailwind::SLATE.c400),
+            };
+            spans.push(Span::styled(self.title(i).to_string(), style));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), steps_area);
+
+        let gauge = LineGauge::default()
+            .filled_style(Style::new().fg(tailwind::BLUE.c400))
+            .label(format!("{}/{steps}", self.step + 1))
+            .ratio((self.step + 1) as f64 / steps as f64);
+        f.render_widget(gauge, gauge_area);
+    }
+
+    fn draw_summary(&self, f: &mut Frame<'_>, area: Rect) {
+        let changes = self.deferred.changes();
+        let mut lines: Vec<Line> = self
+            .before
+            .compare(&self.before.with(&changes))
+            .into_iter()
+            .map(|(line, changed)| if changed { Line::from(line).fg(tailwind::LIME.c500) } else { Line::from(line) })
+            .collect();
+        if let Some(Change::Password { user, .. }) = changes.iter().find(|c| matches!(c, Change::Password { .. })) {
+            lines.push(Line::from(format!("Password of {user}: new")).fg(tailwind::LIME.c500));
+        }
+        let title = if changes.is_empty() { "Nothing to change" } else { "Enter sets up the board with" };
+        let block = Block::default().borders(Borders::TOP).title(title);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+impl Component for Wizard {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.policy = config.password_policy.clone();
+        for page in self.pages.iter_mut() {
+            page.register_config_handler(&config);
+        }
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if key.kind != KeyEventKind::Press || self.applying {
+            return Ok(None);
+        }
+        self.error = None;
+        if let Some(prompt) = self.sudo_prompt.as_mut() {
+            if prompt.handle_key(key.code) {
+                self.sudo_prompt = None;
+            }
+            return Ok(None);
+        }
+        // Ctrl is needed as the views use the plain keys themselves.
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('n') if ctrl => self.next(),
+            KeyCode::Char('p') if ctrl => self.back(),
+            KeyCode::Enter if self.on_summary() => return Ok(self.finish()),
+            KeyCode::Esc | KeyCode::Backspace if self.on_summary() => self.back(),
+            _ if self.on_summary() => {}
+            _ => match self.pages[self.step].handle_key_events(key)? {
+                // A view is done with when it would go back to the menu. The password view only
+                // lets go of a stale password once it was changed.
+                Some(Action::BackToMenu) => {
+                    if self.step == 0 {
+                        self.stale = None;
+                    }
+                    self.next();
+                }
+                action => return Ok(action),
+            },
+        }
+        Ok(None)
+    }
+
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        if let Some(prompt) = self.sudo_prompt.as_mut() {
+            prompt.handle_paste(&text);
+            return Ok(None);
+        }
+        match self.pages.get_mut(self.step) {
+            Some(page) => page.handle_paste(text),
+            None => Ok(None),
+        }
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        if let Action::WizardApplied { error } = &action {
+            self.applying = false;
+            match error {
+                Some(error) => self.error = Some(error.clone()),
+                None => return Ok(Some(Action::EnterMode(Mode::Home))),
+            }
+        }
+        // Background work of a page, like connecting to WiFi, reports back while another is shown.
+        for page in self.pages.iter_mut() {
+            if let Some(action) = page.update(action.clone())? {
+                if let Some(tx) = &self.command_tx {
+                    tx.send(action)?;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        let block = Block::bordered().title(" Beagle-Config setup ").padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [progress_area, title_area, content_area, error_area, help_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Length(2),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
+
+        self.draw_progress(frame, progress_area);
+        frame.render_widget(Paragraph::new(self.title(self.step).to_string()).bold(), title_area);
+        match self.pages.get_mut(self.step) {
+            Some(page) => page.draw(frame, content_area)?,
+            None => self.draw_summary(frame, content_area),
+        }
+
+        if self.applying {
+            frame.render_widget(Paragraph::new("Setting up the board…").fg(tailwind::AMBER.c400), error_area);
+        } else if let Some(error) = &self.error {
+            frame.render_widget(Paragraph::new(error.as_str()).fg(tailwind::RED.c400), error_area);
+        }
+        let help = match (self.on_summary(), self.step) {
+            (true, _) => "Enter: apply and finish  Esc: back",
+            (false, 0) => "Ctrl-n: next page",
+            (false, _) => "Ctrl-n: next page  Ctrl-p: previous page",
+        };
+        frame.render_widget(Paragraph::new(help).fg(tailwind::SLATE.c400), help_area);
+        if let Some(prompt) = &self.sudo_prompt {
+            prompt.draw(frame, area);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! What the setup wizard is about to change, shown on its last page.
+
+use std::path::Path;
+
+use crate::{
+    boot::overlays::{entry_file_name, BootConfig},
+    provision::Change,
+    system::{clock, hostname, locale, ssh, systemd::SystemBus},
+};
+
+/// The settings the wizard pages change, as far as they can be read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub hostname: Option<String>,
+    pub pretty_hostname: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub ssh: Option<bool>,
+    /// Enabled overlays in boot order.
+    pub overlays: Option<Vec<String>>,
+}
+
+impl Settings {
+    pub fn read(root: &Path) -> Self {
+        let names = hostname::detect(root, None).names().ok();
+        Settings {
+            hostname: names.as_ref().map(|names| names.static_name.clone()),
+            pretty_hostname: names.map(|names| names.pretty),
+            locale: locale::locale().ok().flatten(),
+            timezone: clock::detect(root, None).status().ok().map(|status| status.timezone),
+            ssh: ssh::status(&SystemBus::new()).ok().map(|status| status.enabled),
+            overlays: BootConfig::load(root)
+                .ok()
+                .map(|config| config.overlays.iter().map(|o| entry_file_name(o).to_string()).collect()),
+        }
+    }
+
+    /// The settings once `changes` are made.
+    pub fn with(&self, changes: &[Change]) -> Settings {
+        let mut settings = self.clone();
+        for change in changes {
+            match change {
+                Change::Hostname(name) => settings.hostname = Some(name.clone()),
+                Change::PrettyHostname(pretty) => settings.pretty_hostname = Some(pretty.clone()),
+                Change::Locale(lang) => settings.locale = Some(lang.clone()),
+                Change::Timezone(tz) => settings.timezone = Some(tz.clone()),
+                Change::Ssh(enabled) => settings.ssh = Some(*enabled),
+                Change::Overlays(names) => settings.overlays = Some(names.clone()),
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    /// One line per setting with whether it changed since `self`, saying what it was changed from.
+    pub fn compare(&self, after: &Settings) -> Vec<(String, bool)> {
+        let text = |value: &Option<String>| match value {
+            Some(text) if text.is_empty() => String::from("none"),
+            Some(text) => text.clone(),
+            None => String::from("unknown"),
+        };
+        let ssh = |value: Option<bool>| match value {
+            Some(true) => String::from("enabled"),
+            Some(false) => String::from("disabled"),
+            None => String::from("unknown"),
+        };
+        let overlays = |value: &Option<Vec<String>>| match value {
+            Some(names) if names.is_empty() => String::from("none"),
+            Some(names) => names.join(", "),
+            None => String::from("unknown"),
+        };
+        [
+            ("Hostname", text(&self.hostname), text(&after.hostname)),
+            ("Pretty name", text(&self.pretty_hostname), text(&after.pretty_hostname)),
+            ("Locale", text(&self.locale), text(&after.locale)),
+            ("Timezone", text(&self.timezone), text(&after.timezone)),
+            ("SSH", ssh(self.ssh), ssh(after.ssh)),
+            ("Overlays", overlays(&self.overlays), overlays(&after.overlays)),
+        ]
+        .into_iter()
+        .map(|(label, before, after)| {
+            if before == after {
+                (format!("{label}: {after}"), false)
+            } else {
+                (format!("{label}: {before} → {after}"), true)
+            }
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let before = Settings {
+            hostname: Some(String::from("beaglebone")),
+            pretty_hostname: Some(String::new()),
+            locale: None,
+            timezone: Some(String::from("Etc/UTC")),
+            ssh: Some(true),
+            overlays: Some(Vec::new()),
+        };
+        let after = before.with(&[
+            Change::Hostname(String::from("lab-1")),
+            Change::Ssh(false),
+            Change::Overlays(vec![String::from("A.dtbo"), String::from("B.dtbo")]),
+            Change::Password { user: String::from("debian"), password: Default::default() },
+        ]);
+        assert_eq!(
+            before.compare(&after),
+            [
+                (String::from("Hostname: beaglebone → lab-1"), true),
+                (String::from("Pretty name: none"), false),
+                (String::from("Locale: unknown"), false),
+                (String::from("Timezone: Etc/UTC"), false),
+                (String::from("SSH: enabled → disabled"), true),
+                (String::from("Overlays: none → A.dtbo, B.dtbo"), true),
+            ]
+        );
+    }
+}
@@ -11,7 +11,7 @@ use ratatui::style::{Color, Modifier, Style};
 use serde::{de::Deserializer, Deserialize};
 use tracing::error;
 
-use crate::{action::Action, app::Mode};
+use crate::{action::Action, app::Mode, system::password::PasswordPolicy};
 
 const CONFIG: &str = include_str!("../.config/config.json5");
 
@@ -31,6 +31,8 @@ pub struct Config {
     pub keybindings: KeyBindings,
     #[serde(default)]
     pub styles: Styles,
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
 }
 
 lazy_static! {
@@ -0,0 +1,4 @@
+pub mod cdev;
+pub mod line;
+#[cfg(test)]
+pub mod mock;
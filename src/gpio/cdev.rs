@@ -0,0 +1,437 @@
+//! GPIO access through the character device v2 uAPI (`linux/gpio.h`).
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read},
+    mem,
+    os::fd::{AsRawFd, FromRawFd},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use super::line::{Direction, Edge, EdgeEvent, GpioBackend, LineId, LineInfo};
+
+const GPIO_MAX_NAME_SIZE: usize = 32;
+const GPIO_V2_LINES_MAX: usize = 64;
+const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+
+const GPIO_V2_LINE_FLAG_USED: u64 = 1 << 0;
+const GPIO_V2_LINE_FLAG_ACTIVE_LOW: u64 = 1 << 1;
+const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+const GPIO_V2_LINE_FLAG_OUTPUT: u64 = 1 << 3;
+const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+
+const GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES: u32 = 2;
+
+const GPIO_V2_LINE_EVENT_RISING_EDGE: u32 = 1;
+
+const CONSUMER: &[u8] = b"beagle-config";
+
+#[repr(C)]
+struct GpioChipInfo {
+    name: [u8; GPIO_MAX_NAME_SIZE],
+    label: [u8; GPIO_MAX_NAME_SIZE],
+    lines: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpioV2LineAttribute {
+    id: u32,
+    padding: u32,
+    /// Union of `flags`, `values` and `debounce_period_us`.
+    value: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpioV2LineConfigAttribute {
+    attr: GpioV2LineAttribute,
+    mask: u64,
+}
+
+#[repr(C)]
+struct GpioV2LineConfig {
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [GpioV2LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+}
+
+#[repr(C)]
+struct GpioV2LineRequest {
+    offsets: [u32; GPIO_V2_LINES_MAX],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    config: GpioV2LineConfig,
+    num_lines: u32,
+    event_buffer_size: u32,
+    padding: [u32; 5],
+    fd: i32,
+}
+
+#[repr(C)]
+struct GpioV2LineInfo {
+    name: [u8; GPIO_MAX_NAME_SIZE],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    offset: u32,
+    num_attrs: u32,
+    flags: u64,
+    attrs: [GpioV2LineAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+    padding: [u32; 4],
+}
+
+#[repr(C)]
+struct GpioV2LineValues {
+    bits: u64,
+    mask: u64,
+}
+
+#[repr(C)]
+struct GpioV2LineEvent {
+    timestamp_ns: u64,
+    id: u32,
+    offset: u32,
+    seqno: u32,
+    line_seqno: u32,
+    padding: [u32; 6],
+}
+
+const fn ioc(dir: u32, nr: u32, size: usize) -> libc::Ioctl {
+    ((dir << 30) | ((size as u32) << 16) | (0xB4 << 8) | nr) as libc::Ioctl
+}
+
+const IOC_READ: u32 = 2;
+const IOC_READ_WRITE: u32 = 3;
+
+const GPIO_GET_CHIPINFO_IOCTL: libc::Ioctl = ioc(IOC_READ, 0x01, mem::size_of::<GpioChipInfo>());
+const GPIO_V2_GET_LINEINFO_IOCTL: libc::Ioctl =
+    ioc(IOC_READ_WRITE, 0x05, mem::size_of::<GpioV2LineInfo>());
+const GPIO_V2_GET_LINE_IOCTL: libc::Ioctl =
+    ioc(IOC_READ_WRITE, 0x07, mem::size_of::<GpioV2LineRequest>());
+const GPIO_V2_LINE_GET_VALUES_IOCTL: libc::Ioctl =
+    ioc(IOC_READ_WRITE, 0x0E, mem::size_of::<GpioV2LineValues>());
+const GPIO_V2_LINE_SET_VALUES_IOCTL: libc::Ioctl =
+    ioc(IOC_READ_WRITE, 0x0F, mem::size_of::<GpioV2LineValues>());
+
+/// Issue `request` on `file` with `arg` as its argument.
+///
+/// # Safety
+///
+/// `T` must be the structure the kernel expects for `request`.
+unsafe fn ioctl<T>(file: &File, request: libc::Ioctl, arg: &mut T) -> io::Result<()> {
+    if libc::ioctl(file.as_raw_fd(), request, arg as *mut T) < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Output,
+    Watch,
+}
+
+#[derive(Debug)]
+struct Request {
+    file: File,
+    mode: Mode,
+}
+
+struct Chip {
+    path: PathBuf,
+    name: String,
+    label: String,
+    line_names: Vec<String>,
+}
+
+pub struct CdevBackend {
+    chips: Vec<Chip>,
+    requests: HashMap<LineId, Request>,
+}
+
+impl CdevBackend {
+    pub fn new() -> Self {
+        Self::with_dev_dir(Path::new("/dev"))
+    }
+
+    /// Use the `gpiochipN` devices found in `dev`.
+    pub fn with_dev_dir(dev: &Path) -> Self {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dev)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with("gpiochip"))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.sort();
+
+        let chips = paths
+            .into_iter()
+            .filter_map(|path| Self::probe(path).ok())
+            .collect();
+
+        CdevBackend {
+            chips,
+            requests: HashMap::new(),
+        }
+    }
+
+    fn probe(path: PathBuf) -> Result<Chip> {
+        let file = File::open(&path)?;
+        // SAFETY: all-zero is a valid bit pattern for this plain C struct.
+        let mut info: GpioChipInfo = unsafe { mem::zeroed() };
+        // SAFETY: GPIO_GET_CHIPINFO_IOCTL takes a `struct gpiochip_info`.
+        unsafe { ioctl(&file, GPIO_GET_CHIPINFO_IOCTL, &mut info)? };
+
+        let line_names = (0..info.lines)
+            .map(|offset| raw_line_info(&file, offset).map(|i| c_string(&i.name)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Chip {
+            path,
+            name: c_string(&info.name),
+            label: c_string(&info.label),
+            line_names,
+        })
+    }
+
+    fn open_chip(line: &LineId) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&line.chip)
+            .map_err(|e| eyre!("Unable to open {}: {e}", line.chip.display()))
+    }
+
+    fn request(&self, line: &LineId, flags: u64, output: Option<bool>) -> Result<File> {
+        let chip = Self::open_chip(line)?;
+
+        // SAFETY: all-zero is a valid bit pattern for this plain C struct.
+        let mut req: GpioV2LineRequest = unsafe { mem::zeroed() };
+        req.offsets[0] = line.offset;
+        req.num_lines = 1;
+        req.consumer[..CONSUMER.len()].copy_from_slice(CONSUMER);
+        req.config.flags = flags;
+        if let Some(value) = output {
+            req.config.num_attrs = 1;
+            req.config.attrs[0] = GpioV2LineConfigAttribute {
+                attr: GpioV2LineAttribute {
+                    id: GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES,
+                    padding: 0,
+                    value: value as u64,
+                },
+                mask: 1,
+            };
+        }
+
+        // SAFETY: GPIO_V2_GET_LINE_IOCTL takes a `struct gpio_v2_line_request`.
+        unsafe { ioctl(&chip, GPIO_V2_GET_LINE_IOCTL, &mut req) }
+            .map_err(|e| eyre!("Unable to request line {}: {e}", line.offset))?;
+
+        // SAFETY: on success the kernel hands us ownership of a new line fd.
+        Ok(unsafe { File::from_raw_fd(req.fd) })
+    }
+}
+
+impl Default for CdevBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn raw_line_info(chip: &File, offset: u32) -> io::Result<GpioV2LineInfo> {
+    // SAFETY: all-zero is a valid bit pattern for this plain C struct.
+    let mut info: GpioV2LineInfo = unsafe { mem::zeroed() };
+    info.offset = offset;
+    // SAFETY: GPIO_V2_GET_LINEINFO_IOCTL takes a `struct gpio_v2_line_info`.
+    unsafe { ioctl(chip, GPIO_V2_GET_LINEINFO_IOCTL, &mut info)? };
+    Ok(info)
+}
+
+fn get_value(file: &File) -> Result<bool> {
+    let mut values = GpioV2LineValues { bits: 0, mask: 1 };
+    // SAFETY: GPIO_V2_LINE_GET_VALUES_IOCTL takes a `struct gpio_v2_line_values`.
+    unsafe { ioctl(file, GPIO_V2_LINE_GET_VALUES_IOCTL, &mut values)? };
+    Ok(values.bits & 1 == 1)
+}
+
+impl GpioBackend for CdevBackend {
+    fn lookup(&self, chip: &str, offset: u32) -> Option<LineId> {
+        self.chips
+            .iter()
+            .find(|c| c.name == chip || c.label == chip)
+            .filter(|c| (offset as usize) < c.line_names.len())
+            .map(|c| LineId {
+                chip: c.path.clone(),
+                offset,
+            })
+    }
+
+    fn find_by_name(&self, name: &str) -> Option<LineId> {
+        self.chips.iter().find_map(|c| {
+            c.line_names
+                .iter()
+                .position(|n| n == name)
+                .map(|offset| LineId {
+                    chip: c.path.clone(),
+                    offset: offset as u32,
+                })
+        })
+    }
+
+    fn line_info(&self, line: &LineId) -> Result<LineInfo> {
+        let chip = Self::open_chip(line)?;
+        let info = raw_line_info(&chip, line.offset)?;
+        Ok(LineInfo {
+            name: c_string(&info.name),
+            consumer: c_string(&info.consumer),
+            direction: if info.flags & GPIO_V2_LINE_FLAG_OUTPUT != 0 {
+                Direction::Output
+            } else {
+                Direction::Input
+            },
+            active_low: info.flags & GPIO_V2_LINE_FLAG_ACTIVE_LOW != 0,
+            used: info.flags & GPIO_V2_LINE_FLAG_USED != 0,
+        })
+    }
+
+    fn read_value(&mut self, line: &LineId) -> Result<bool> {
+        if let Some(req) = self.requests.get(line) {
+            return get_value(&req.file);
+        }
+        // Neither input nor output requested leaves the direction as it is.
+        let file = self.request(line, 0, None)?;
+        get_value(&file)
+    }
+
+    fn drive(&mut self, line: &LineId, value: bool) -> Result<()> {
+        if let Some(req) = self.requests.get(line) {
+            if req.mode == Mode::Output {
+                let mut values = GpioV2LineValues {
+                    bits: value as u64,
+                    mask: 1,
+                };
+                // SAFETY: GPIO_V2_LINE_SET_VALUES_IOCTL takes a `struct gpio_v2_line_values`.
+                unsafe { ioctl(&req.file, GPIO_V2_LINE_SET_VALUES_IOCTL, &mut values)? };
+                return Ok(());
+            }
+        }
+        self.release(line);
+        let file = self.request(line, GPIO_V2_LINE_FLAG_OUTPUT, Some(value))?;
+        self.requests.insert(
+            line.clone(),
+            Request {
+                file,
+                mode: Mode::Output,
+            },
+        );
+        Ok(())
+    }
+
+    fn watch(&mut self, line: &LineId) -> Result<()> {
+        self.release(line);
+        let file = self.request(
+            line,
+            GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING,
+            None,
+        )?;
+        // Events are polled from the UI tick, so reads must never block.
+        // SAFETY: plain fcntl calls on a file descriptor we own.
+        unsafe {
+            let flags = libc::fcntl(file.as_raw_fd(), libc::F_GETFL);
+            libc::fcntl(file.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+        self.requests.insert(
+            line.clone(),
+            Request {
+                file,
+                mode: Mode::Watch,
+            },
+        );
+        Ok(())
+    }
+
+    fn read_events(&mut self, line: &LineId) -> Result<Vec<EdgeEvent>> {
+        let Some(req) = self.requests.get_mut(line).filter(|r| r.mode == Mode::Watch) else {
+            return Ok(Vec::new());
+        };
+
+        let mut events = Vec::new();
+        let mut buf = [0u8; mem::size_of::<GpioV2LineEvent>()];
+        loop {
+            match req.file.read(&mut buf) {
+                Ok(n) if n == buf.len() => {
+                    // SAFETY: the kernel wrote a whole `struct gpio_v2_line_event`.
+                    let event: GpioV2LineEvent =
+                        unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+                    events.push(EdgeEvent {
+                        timestamp_ns: event.timestamp_ns,
+                        edge: if event.id == GPIO_V2_LINE_EVENT_RISING_EDGE {
+                            Edge::Rising
+                        } else {
+                            Edge::Falling
+                        },
+                    });
+                }
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(events)
+    }
+
+    fn release(&mut self, line: &LineId) {
+        self.requests.remove(line);
+    }
+
+    fn is_requested(&self, line: &LineId) -> bool {
+        self.requests.contains_key(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sizes from the kernel headers; a mismatch would make every ioctl fail with ENOTTY.
+    #[test]
+    fn test_uapi_struct_sizes() {
+        assert_eq!(mem::size_of::<GpioChipInfo>(), 68);
+        assert_eq!(mem::size_of::<GpioV2LineConfig>(), 272);
+        assert_eq!(mem::size_of::<GpioV2LineRequest>(), 592);
+        assert_eq!(mem::size_of::<GpioV2LineInfo>(), 256);
+        assert_eq!(mem::size_of::<GpioV2LineValues>(), 16);
+        assert_eq!(mem::size_of::<GpioV2LineEvent>(), 48);
+    }
+
+    #[test]
+    fn test_ioctl_numbers() {
+        assert_eq!(GPIO_GET_CHIPINFO_IOCTL, 0x8044_B401 as libc::Ioctl);
+        assert_eq!(GPIO_V2_GET_LINE_IOCTL, 0xC250_B407 as libc::Ioctl);
+        assert_eq!(GPIO_V2_LINE_GET_VALUES_IOCTL, 0xC010_B40E as libc::Ioctl);
+    }
+
+    #[test]
+    fn test_no_chips() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = CdevBackend::with_dev_dir(dir.path());
+        assert!(backend.find_by_name("GPIO14").is_none());
+        assert!(backend.lookup("gpiochip0", 0).is_none());
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+
+use crate::boards::description::PinInfo;
+
+/// A single line on a GPIO chip.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LineId {
+    pub chip: PathBuf,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineInfo {
+    pub name: String,
+    pub consumer: String,
+    pub direction: Direction,
+    pub active_low: bool,
+    /// Requested by someone, either a kernel driver or a user space process.
+    pub used: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeEvent {
+    pub timestamp_ns: u64,
+    pub edge: Edge,
+}
+
+/// Access to GPIO lines, implemented on top of `/dev/gpiochipN` and by a mock for tests.
+///
+/// Lines requested through `drive` or `watch` stay requested until `release` is called
+/// or the backend is dropped.
+pub trait GpioBackend: Send {
+    /// Find a line by the chip name (`gpiochip1`) or label and its offset.
+    fn lookup(&self, chip: &str, offset: u32) -> Option<LineId>;
+    /// Find a line by the name the device tree gave it.
+    fn find_by_name(&self, name: &str) -> Option<LineId>;
+    fn line_info(&self, line: &LineId) -> Result<LineInfo>;
+    /// Read the current value without changing the line direction.
+    fn read_value(&mut self, line: &LineId) -> Result<bool>;
+    /// Request the line as an output and set its value.
+    fn drive(&mut self, line: &LineId, value: bool) -> Result<()>;
+    /// Request the line as an input with edge detection on both edges.
+    fn watch(&mut self, line: &LineId) -> Result<()>;
+    /// Edge events queued since the last call, for a line set up with `watch`.
+    fn read_events(&mut self, line: &LineId) -> Result<Vec<EdgeEvent>>;
+    fn release(&mut self, line: &LineId);
+    fn is_requested(&self, line: &LineId) -> bool;
+
+    /// Map a header pin to its GPIO line, using the explicit mapping from the board
+    /// description when there is one and the device tree line names otherwise.
+    fn resolve(&self, header: &str, pin: &PinInfo) -> Option<LineId> {
+        if let Some(gpio) = &pin.gpio {
+            return self.lookup(&gpio.chip, gpio.line);
+        }
+        let candidates = [
+            pin.name.clone(),
+            pin.name.replace(' ', ""),
+            format!("{}_{:02}", header, pin.number),
+            format!("{}.{:02}", header, pin.number),
+        ];
+        candidates.iter().find_map(|name| self.find_by_name(name))
+    }
+}
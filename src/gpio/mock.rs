@@ -0,0 +1,245 @@
+//! In-memory GPIO backend for tests.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre::eyre, Result};
+
+use super::line::{Direction, Edge, EdgeEvent, GpioBackend, LineId, LineInfo};
+
+#[derive(Debug, Clone)]
+struct MockLine {
+    name: String,
+    direction: Direction,
+    value: bool,
+    consumer: String,
+    requested: bool,
+    watched: bool,
+    events: Vec<EdgeEvent>,
+}
+
+/// A single fake chip whose lines are named `names`.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    chip: String,
+    lines: Vec<MockLine>,
+    clock: u64,
+}
+
+impl MockBackend {
+    pub fn new(chip: &str, names: &[&str]) -> Self {
+        MockBackend {
+            chip: chip.to_string(),
+            lines: names
+                .iter()
+                .map(|name| MockLine {
+                    name: name.to_string(),
+                    direction: Direction::Input,
+                    value: false,
+                    consumer: String::new(),
+                    requested: false,
+                    watched: false,
+                    events: Vec::new(),
+                })
+                .collect(),
+            clock: 0,
+        }
+    }
+
+    /// Mark a line as claimed by a kernel driver.
+    pub fn claim(&mut self, offset: u32, consumer: &str) {
+        self.lines[offset as usize].consumer = consumer.to_string();
+    }
+
+    /// Change the level seen on an input, queueing an edge event if it is watched.
+    pub fn set_input(&mut self, offset: u32, value: bool) {
+        self.clock += 1;
+        let line = &mut self.lines[offset as usize];
+        if line.value != value && line.watched {
+            line.events.push(EdgeEvent {
+                timestamp_ns: self.clock,
+                edge: if value { Edge::Rising } else { Edge::Falling },
+            });
+        }
+        line.value = value;
+    }
+
+    fn line(&self, id: &LineId) -> Result<&MockLine> {
+        self.lines
+            .get(id.offset as usize)
+            .filter(|_| id.chip == Path::new(&self.chip))
+            .ok_or_else(|| eyre!("No such line {}", id.offset))
+    }
+
+    fn line_mut(&mut self, id: &LineId) -> Result<&mut MockLine> {
+        if id.chip != Path::new(&self.chip) {
+            return Err(eyre!("No such chip {}", id.chip.display()));
+        }
+        self.lines
+            .get_mut(id.offset as usize)
+            .ok_or_else(|| eyre!("No such line {}", id.offset))
+    }
+
+    fn request(&mut self, id: &LineId) -> Result<&mut MockLine> {
+        let line = self.line_mut(id)?;
+        if !line.requested && !line.consumer.is_empty() {
+            return Err(eyre!("Line {} is busy ({})", id.offset, line.consumer));
+        }
+        line.requested = true;
+        line.consumer = String::from("beagle-config");
+        Ok(line)
+    }
+}
+
+impl GpioBackend for MockBackend {
+    fn lookup(&self, chip: &str, offset: u32) -> Option<LineId> {
+        (chip == self.chip && (offset as usize) < self.lines.len()).then(|| LineId {
+            chip: PathBuf::from(&self.chip),
+            offset,
+        })
+    }
+
+    fn find_by_name(&self, name: &str) -> Option<LineId> {
+        self.lines
+            .iter()
+            .position(|l| l.name == name)
+            .map(|offset| LineId {
+                chip: PathBuf::from(&self.chip),
+                offset: offset as u32,
+            })
+    }
+
+    fn line_info(&self, id: &LineId) -> Result<LineInfo> {
+        let line = self.line(id)?;
+        Ok(LineInfo {
+            name: line.name.clone(),
+            consumer: line.consumer.clone(),
+            direction: line.direction,
+            active_low: false,
+            used: !line.consumer.is_empty(),
+        })
+    }
+
+    fn read_value(&mut self, id: &LineId) -> Result<bool> {
+        Ok(self.line(id)?.value)
+    }
+
+    fn drive(&mut self, id: &LineId, value: bool) -> Result<()> {
+        let line = self.request(id)?;
+        line.direction = Direction::Output;
+        line.watched = false;
+        line.value = value;
+        Ok(())
+    }
+
+    fn watch(&mut self, id: &LineId) -> Result<()> {
+        let line = self.request(id)?;
+        line.direction = Direction::Input;
+        line.watched = true;
+        Ok(())
+    }
+
+    fn read_events(&mut self, id: &LineId) -> Result<Vec<EdgeEvent>> {
+        Ok(std::mem::take(&mut self.line_mut(id)?.events))
+    }
+
+    fn release(&mut self, id: &LineId) {
+        if let Ok(line) = self.line_mut(id) {
+            if line.requested {
+                line.requested = false;
+                line.watched = false;
+                line.consumer.clear();
+                line.events.clear();
+            }
+        }
+    }
+
+    fn is_requested(&self, id: &LineId) -> bool {
+        self.line(id).is_ok_and(|l| l.requested)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::boards::description::{GpioLine, PinInfo, PinType};
+
+    fn pin(number: u16, name: &str, gpio: Option<GpioLine>) -> PinInfo {
+        PinInfo {
+            number,
+            name: name.to_string(),
+            function: String::new(),
+            pin_type: PinType::GPIO,
+            pad: None,
+            modes: Vec::new(),
+            note: None,
+            gpio,
+        }
+    }
+
+    #[test]
+    fn test_resolve_by_name() {
+        let backend = MockBackend::new("gpiochip0", &["GPIO14", "P9_12", "P8.07"]);
+        let id = |offset| Some(LineId { chip: PathBuf::from("gpiochip0"), offset });
+
+        assert_eq!(backend.resolve("HAT", &pin(8, "GPIO14", None)), id(0));
+        assert_eq!(backend.resolve("HAT", &pin(8, "GPIO 14", None)), id(0));
+        assert_eq!(backend.resolve("P9", &pin(12, "GPIO1_28", None)), id(1));
+        assert_eq!(backend.resolve("P8", &pin(7, "TIMER4", None)), id(2));
+        assert_eq!(backend.resolve("P8", &pin(9, "TIMER5", None)), None);
+    }
+
+    #[test]
+    fn test_resolve_explicit_mapping() {
+        let backend = MockBackend::new("gpiochip0", &["GPIO14", "", ""]);
+        let mapping = GpioLine { chip: String::from("gpiochip0"), line: 2 };
+        let line = backend.resolve("HAT", &pin(8, "GPIO14", Some(mapping))).unwrap();
+        assert_eq!(line.offset, 2);
+    }
+
+    #[test]
+    fn test_drive_and_release() {
+        let mut backend = MockBackend::new("gpiochip0", &["GPIO14"]);
+        let line = backend.find_by_name("GPIO14").unwrap();
+
+        backend.drive(&line, true).unwrap();
+        let info = backend.line_info(&line).unwrap();
+        assert_eq!(info.direction, Direction::Output);
+        assert_eq!(info.consumer, "beagle-config");
+        assert!(backend.read_value(&line).unwrap());
+
+        backend.release(&line);
+        assert!(!backend.is_requested(&line));
+        assert!(!backend.line_info(&line).unwrap().used);
+    }
+
+    #[test]
+    fn test_busy_line_refused() {
+        let mut backend = MockBackend::new("gpiochip0", &["GPIO14"]);
+        backend.claim(0, "leds-gpio");
+        let line = backend.find_by_name("GPIO14").unwrap();
+        assert!(backend.drive(&line, true).is_err());
+        assert!(backend.watch(&line).is_err());
+    }
+
+    #[test]
+    fn test_watch_edges() {
+        let mut backend = MockBackend::new("gpiochip0", &["GPIO14"]);
+        let line = backend.find_by_name("GPIO14").unwrap();
+        backend.watch(&line).unwrap();
+
+        backend.set_input(0, true);
+        backend.set_input(0, true);
+        backend.set_input(0, false);
+
+        let edges: Vec<_> = backend
+            .read_events(&line)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.edge)
+            .collect();
+        assert_eq!(edges, vec![Edge::Rising, Edge::Falling]);
+        assert!(backend.read_events(&line).unwrap().is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use crate::app::App;
 mod action;
 mod app;
 mod cli;
+mod commands;
 mod components;
 mod config;
 mod errors;
@@ -14,6 +15,11 @@ mod logging;
 mod tui;
 mod widgets;
 mod networks;
+mod boards;
+mod boot;
+mod gpio;
+mod system;
+mod provision;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -21,8 +27,11 @@ async fn main() -> Result<()> {
     crate::logging::init()?;
 
     let args = Cli::parse();
+    if let Some(command) = args.command {
+        std::process::exit(commands::run(command, args.json).await.into());
+    }
     
-    let mut app = App::new(args.tick_rate, args.frame_rate).await?;
+    let mut app = App::new(args.tick_rate, args.frame_rate, args.first_boot, args.wizard).await?;
     app.run().await?;
     Ok(())
 }
@@ -1,12 +1,14 @@
 use std::sync::Arc;
 use anyhow::Context;
 use iwdrs::{adapter::Adapter as iwdAdapter, modes::Mode, session::Session};
+use serde::Serialize;
 
 use crate::app::AppResult;
 use super::device::Device;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Adapter {
+    #[serde(skip)]
     pub adapter: iwdAdapter,
     pub is_powered: bool,
     pub name: String,
@@ -2,17 +2,21 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use iwdrs::{device::Device as iwdDevice, modes::Mode, session::Session};
+use serde::{Serialize, Serializer};
 
 use crate::app::AppResult;
 
 use super::station::Station;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Device {
+    #[serde(skip)]
     pub session: Arc<Session>,
+    #[serde(skip)]
     pub device: iwdDevice,
     pub name: String,
     pub address: String,
+    #[serde(serialize_with = "serialize_mode")]
     pub mode: Mode,
     pub is_powered: bool,
     pub station: Option<Station>,
@@ -116,4 +120,8 @@ impl Device {
         self.mode = current_mode;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn serialize_mode<S: Serializer>(mode: &Mode, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(mode)
+}
@@ -1,12 +1,20 @@
+use std::{fs, io, path::Path};
+
 use chrono::{DateTime, FixedOffset};
 use iwdrs::known_netowk::KnownNetwork as iwdKnownNetwork;
+use serde::Serialize;
 
 use crate::app::AppResult;
 
-#[derive(Debug, Clone)]
+/// Where iwd keeps the profiles of known networks.
+const IWD_STORAGE_DIR: &str = "/var/lib/iwd";
+
+#[derive(Debug, Clone, Serialize)]
 pub struct KnownNetwork {
+    #[serde(skip)]
     pub n: iwdKnownNetwork,
     pub name: String,
+    #[serde(rename = "network_type")]
     pub netowrk_type: String,
     pub is_autoconnect: bool,
     pub is_hidden: bool,
@@ -34,33 +42,53 @@ impl KnownNetwork {
         })
     }
 
+    /// Remove the network and its credentials from iwd.
     pub async fn forget(&self) -> AppResult<()> {
-        if let Err(e) = self.n.forget().await {
-            return Ok(());
-        }
+        self.n.forget().await?;
         Ok(())
     }
 
+    /// Turn connecting to the network automatically on when it is off and off when it is on.
     pub async fn toggle_autoconnect(&self) -> AppResult<()> {
-        if self.is_autoconnect {
-            match self.n.set_autoconnect(false).await {
-                Ok(_) => {
+        self.n.set_autoconnect(!self.is_autoconnect).await?;
+        Ok(())
+    }
 
-                }
-                Err(e) => {
+    /// Passphrase iwd stored for the network, read from its profile. Reading it requires root.
+    pub fn stored_passphrase(&self) -> AppResult<Option<String>> {
+        let path = Path::new(IWD_STORAGE_DIR).join(profile_file_name(&self.name, &self.netowrk_type));
+        match fs::read_to_string(path) {
+            Ok(profile) => Ok(profile
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("Passphrase="))
+                .map(String::from)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
 
-                }
-            }
-        } else {
-            match self.n.set_autoconnect(true).await {
-                Ok(_) => {
+/// File name of an iwd profile: the SSID itself when it only holds letters, digits, spaces,
+/// `-` and `_`, otherwise `=` followed by its hex encoding, with the network type as extension.
+fn profile_file_name(ssid: &str, network_type: &str) -> String {
+    let plain = ssid
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if plain {
+        format!("{ssid}.{network_type}")
+    } else {
+        let hex: String = ssid.bytes().map(|b| format!("{b:02x}")).collect();
+        format!("={hex}.{network_type}")
+    }
+}
 
-                }
-                Err(e) => {
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                }
-            }
-        }
-        Ok(())
+    #[test]
+    fn test_profile_file_name() {
+        assert_eq!(profile_file_name("Home WiFi", "psk"), "Home WiFi.psk");
+        assert_eq!(profile_file_name("café", "open"), "=636166c3a9.open");
     }
 }
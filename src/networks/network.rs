@@ -1,12 +1,15 @@
 use crate::app::AppResult;
 use iwdrs::netowrk::Network as iwdNetwork;
+use serde::Serialize;
 
 use super::know_network::KnownNetwork;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Network {
+    #[serde(skip)]
     pub n: iwdNetwork,
     pub name: String,
+    #[serde(rename = "network_type")]
     pub netowrk_type: String,
     pub is_connected: bool,
     pub known_network: Option<KnownNetwork>,
@@ -36,11 +39,81 @@ impl Network {
         })
     }
 
+    /// Connect to the network. iwd asks the registered agent for the passphrase when it needs one.
     pub async fn connect(&self) -> AppResult<()> {
-        match self.n.connect().await {
-            Ok(_) => {},
-            Err(e) => {}
-        }
+        self.n.connect().await.map_err(|e| failure_reason(&e.to_string()))?;
         Ok(())
     }
 }
+
+/// Check a WPA passphrase before handing it to iwd: 8 to 63 characters, or the 64 hex digits of a
+/// pre-shared key.
+pub fn validate_passphrase(passphrase: &str) -> AppResult<()> {
+    let length = passphrase.chars().count();
+    let hex_key = length == 64 && passphrase.chars().all(|c| c.is_ascii_hexdigit());
+    if (8..=63).contains(&length) || hex_key {
+        Ok(())
+    } else {
+        Err("A passphrase has 8 to 63 characters".into())
+    }
+}
+
+/// Check the SSID of a network to join by name: 1 to 32 bytes.
+pub fn validate_ssid(ssid: &str) -> AppResult<()> {
+    if ssid.is_empty() || ssid.len() > 32 {
+        return Err("An SSID has 1 to 32 bytes".into());
+    }
+    Ok(())
+}
+
+/// Why a connection failed, in words, from the D-Bus error iwd answered `Connect` or
+/// `ConnectHiddenNetwork` with.
+pub(super) fn failure_reason(error: &str) -> String {
+    let reasons = [
+        ("net.connman.iwd.Failed", "Authentication failed, check the passphrase"),
+        ("net.connman.iwd.InvalidFormat", "iwd did not accept the passphrase"),
+        ("net.connman.iwd.Aborted", "The connection attempt was aborted"),
+        ("net.connman.iwd.Busy", "Another connection attempt is in progress"),
+        ("net.connman.iwd.InProgress", "Another connection attempt is in progress"),
+        ("net.connman.iwd.Timeout", "The network did not answer in time"),
+        ("net.connman.iwd.NotSupported", "The security of this network is not supported"),
+        ("net.connman.iwd.NoAgent", "Nothing is registered to answer passphrase requests"),
+        ("net.connman.iwd.NotFound", "No hidden network with this name is in range"),
+        ("net.connman.iwd.NotHidden", "The network broadcasts its name, pick it from the list"),
+        ("net.connman.iwd.ServiceSetMismatch", "Hidden networks of that name use different security"),
+        ("net.connman.iwd.AlreadyConnected", "Already connected to this network"),
+    ];
+    reasons
+        .iter()
+        .find(|(name, _)| error.contains(name))
+        .map_or_else(|| error.to_string(), |(_, reason)| reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_passphrase() {
+        assert!(validate_passphrase("correct horse").is_ok());
+        assert!(validate_passphrase("short").is_err());
+        assert!(validate_passphrase(&"0a".repeat(32)).is_ok());
+        assert!(validate_passphrase(&"x".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_validate_ssid() {
+        assert!(validate_ssid("Attic").is_ok());
+        assert!(validate_ssid("").is_err());
+        assert!(validate_ssid(&"é".repeat(17)).is_err());
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        assert_eq!(
+            failure_reason("net.connman.iwd.Failed: Operation failed"),
+            "Authentication failed, check the passphrase"
+        );
+        assert_eq!(failure_reason("org.freedesktop.DBus.Error.NoReply"), "org.freedesktop.DBus.Error.NoReply");
+    }
+}
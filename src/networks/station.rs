@@ -2,21 +2,35 @@ use std::{collections::HashMap, sync::Arc};
 
 use futures::future::join_all;
 use iwdrs::session::Session;
+use zbus::{
+    zvariant::{OwnedObjectPath, OwnedValue},
+    Connection, Proxy,
+};
 use ratatui::widgets::TableState;
+use serde::{Serialize, Serializer};
 
 use crate::app::AppResult;
 
-use super::network::Network;
+use super::network::{failure_reason, Network};
 
-#[derive(Debug, Clone)]
+/// The D-Bus objects iwd manages, with their interfaces and properties.
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Station {
+    #[serde(skip)]
     pub session: Arc<Session>,
     pub state: String,
     pub is_scanning: bool,
     pub connected_network: Option<Network>,
+    /// Networks in range with their signal strength, in 100 * dBm as reported by iwd.
+    #[serde(serialize_with = "serialize_with_signal")]
     pub new_networks: Vec<(Network, i16)>,
+    #[serde(serialize_with = "serialize_with_signal")]
     pub known_networks: Vec<(Network, i16)>,
+    #[serde(skip)]
     pub known_networks_state: TableState,
+    #[serde(skip)]
     pub new_networks_state: TableState,
     pub diagnostic: HashMap<String, String>,
 }
@@ -214,4 +228,37 @@ impl Station {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Connect to the network `ssid` that does not broadcast its name. iwd finds out its security by
+/// probing and asks the registered agent for the passphrase of protected networks.
+pub async fn connect_hidden_network(ssid: &str) -> AppResult<()> {
+    // iwdrs keeps its connection to itself and has no binding for ConnectHiddenNetwork.
+    let connection = Connection::system().await?;
+    let objects = Proxy::new(&connection, "net.connman.iwd", "/", "org.freedesktop.DBus.ObjectManager").await?;
+    let objects: ManagedObjects = objects.call("GetManagedObjects", &()).await?;
+    let path = objects
+        .into_iter()
+        .find(|(_, interfaces)| interfaces.contains_key("net.connman.iwd.Station"))
+        .map(|(path, _)| path)
+        .ok_or("The WiFi device is not in station mode")?;
+
+    let station = Proxy::new(&connection, "net.connman.iwd", path, "net.connman.iwd.Station").await?;
+    station
+        .call_method("ConnectHiddenNetwork", &(ssid,))
+        .await
+        .map_err(|e| failure_reason(&e.to_string()))?;
+    Ok(())
+}
+
+/// Serialize `(network, signal)` pairs as network objects with a `signal` field in dBm.
+fn serialize_with_signal<S: Serializer>(networks: &[(Network, i16)], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct WithSignal<'a> {
+        #[serde(flatten)]
+        network: &'a Network,
+        signal: i16,
+    }
+
+    serializer.collect_seq(networks.iter().map(|(network, signal)| WithSignal { network, signal: signal / 100 }))
+}
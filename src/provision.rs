@@ -0,0 +1,392 @@
+//! Declarative device configuration, read from a TOML provisioning file.
+//!
+//! ```toml
+//! hostname = "beagle-01"
+//! locale = "en_US.UTF-8"
+//! timezone = "Europe/Berlin"
+//! overlays = ["BB-UART1-00A0"]
+//!
+//! [user]
+//! name = "debian"
+//! password = "..."
+//!
+//! [ssh]
+//! enabled = true
+//! authorized_keys = ["ssh-ed25519 AAAA... admin@laptop"]
+//!
+//! [pins]
+//! P9_14 = "EHRPWM1 A"
+//!
+//! [[wifi]]
+//! ssid = "Workshop"
+//! passphrase = "..."
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs, io,
+    path::Path,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boards::{
+        identity::Board,
+        pinmux::{staging_dir, Pinmux, OVERLAY_NAME},
+    },
+    boot::overlays::{entry_file_name, overlay_file_name, BootConfig},
+    system::{
+        authorized_keys::{self, same_key, AuthorizedKey},
+        clock, hostname, locale,
+        secret::Secret,
+        ssh::{self, SshStatus},
+        systemd::SystemBus,
+    },
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Provision {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Value of `LANG`, e.g. `en_US.UTF-8`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Overlays to apply at boot, in order. Enabled overlays missing from the list are disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlays: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<UserConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<SshConfig>,
+    /// Functions of the pins that do not use their default one, keyed by pin name (`P9_14`).
+    /// They are written to the generated pinmux overlay. Pins listed with their default function
+    /// are left out of it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pins: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wifi: Vec<WifiConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    pub name: String,
+    /// The password cannot be read back, so it is set on every run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SshConfig {
+    /// Run the SSH server now and at every boot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Account the keys are added to, the `[user]` one by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Keys added to `~/.ssh/authorized_keys`. Keys already there are kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorized_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WifiConfig {
+    pub ssid: String,
+    /// `open`, `psk` or `8021x`, as reported by iwd. Informational only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<String>,
+}
+
+/// The current value of each setting, or `None` where it could not be read.
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    pub hostname: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    /// File names of the enabled overlays, in boot order.
+    pub overlays: Option<Vec<String>>,
+    pub ssh: Option<SshStatus>,
+    pub authorized_keys: HashMap<String, Vec<String>>,
+    /// Pin functions staged in the generated pinmux overlay.
+    pub pins: Option<BTreeMap<String, String>>,
+    /// Names of the WiFi networks iwd already knows.
+    pub known_networks: Option<Vec<String>>,
+}
+
+impl State {
+    /// Read the settings `provision` touches, except WiFi which needs a running iwd session.
+    pub fn read(provision: &Provision, root: &Path) -> Self {
+        let keys_user = provision.authorized_keys_user();
+        State {
+            hostname: provision.hostname.as_ref().and_then(|_| hostname::hostname().ok()),
+            locale: provision.locale.as_ref().and_then(|_| locale::locale().ok().flatten()),
+            timezone: provision.timezone.as_ref().and_then(|_| Some(clock::detect(root, None).status().ok()?.timezone)),
+            // Pins need the overlay list too, to add the pinmux overlay to it.
+            overlays: (provision.overlays.is_some() || !provision.pins.is_empty())
+                .then(|| BootConfig::load(root).ok())
+                .flatten()
+                .map(|config| config.overlays.iter().map(|o| entry_file_name(o).to_string()).collect()),
+            ssh: provision.ssh.as_ref().and_then(|_| ssh::status(&SystemBus::new()).ok()),
+            authorized_keys: keys_user
+                .and_then(|user| Some((user.to_string(), authorized_keys::keys(user).ok()?)))
+                .into_iter()
+                .collect(),
+            pins: (!provision.pins.is_empty()).then(|| read_pins_including(&provision.pins)).flatten(),
+            known_networks: None,
+        }
+    }
+}
+
+/// Pin functions of the generated pinmux overlay, empty when there is none.
+pub fn read_pins() -> Option<BTreeMap<String, String>> {
+    let board = Board::detect().description().ok()?;
+    let pinmux = Pinmux::load(&board, &staging_dir()).ok()?;
+    Some(pinmux.map(|p| p.functions()).unwrap_or_default())
+}
+
+/// [`read_pins`] plus the current function of each pin `wanted` names, so pins the file sets to
+/// their default function do not count as a change.
+fn read_pins_including(wanted: &BTreeMap<String, String>) -> Option<BTreeMap<String, String>> {
+    let board = Board::detect().description().ok()?;
+    let pinmux = Pinmux::load(&board, &staging_dir()).ok()?.unwrap_or_default();
+    Some(pinmux.functions_including(&board, wanted.keys()))
+}
+
+/// One step needed to bring the device in line with a [`Provision`], or kept by the setup wizard
+/// until its summary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Hostname(String),
+    /// Set by the setup wizard only, provisioning files have no pretty name.
+    PrettyHostname(String),
+    Locale(String),
+    Timezone(String),
+    Password { user: String, password: Secret },
+    AuthorizedKeys { user: String, keys: Vec<String> },
+    Ssh(bool),
+    /// Overlay file names to enable, in boot order, replacing the current list.
+    Overlays(Vec<String>),
+    Pins(BTreeMap<String, String>),
+    Wifi(WifiConfig),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Hostname(name) => write!(f, "set hostname to {name}"),
+            Change::PrettyHostname(pretty) => write!(f, "set pretty hostname to {pretty}"),
+            Change::Locale(lang) => write!(f, "set locale to {lang}"),
+            Change::Timezone(tz) => write!(f, "set timezone to {tz}"),
+            Change::Password { user, .. } => write!(f, "set password of {user}"),
+            Change::AuthorizedKeys { user, keys } => {
+                write!(f, "add {} authorized key(s) for {user}", keys.len())
+            }
+            Change::Ssh(true) => write!(f, "enable the SSH server"),
+            Change::Ssh(false) => write!(f, "disable the SSH server"),
+            Change::Overlays(names) if names.is_empty() => write!(f, "disable all overlays"),
+            Change::Overlays(names) => write!(f, "set overlays to {}", names.join(", ")),
+            Change::Pins(pins) => {
+                let pins: Vec<String> = pins.iter().map(|(pin, function)| format!("{pin} {function}")).collect();
+                write!(f, "set pin functions {}", pins.join(", "))
+            }
+            Change::Wifi(network) => write!(f, "connect to WiFi network {}", network.ssid),
+        }
+    }
+}
+
+impl Provision {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        toml::from_str(&content).map_err(|e| eyre!("{}: {e}", path.display()))
+    }
+
+    /// Account whose `authorized_keys` are managed.
+    fn authorized_keys_user(&self) -> Option<&str> {
+        let ssh = self.ssh.as_ref().filter(|ssh| !ssh.authorized_keys.is_empty())?;
+        ssh.user
+            .as_deref()
+            .or_else(|| self.user.as_ref().map(|u| u.name.as_str()))
+    }
+
+    /// The changes needed to get from `state` to this configuration, in the order they should be
+    /// applied. Settings that already match are left out, so planning again after applying
+    /// yields only the password, which cannot be compared.
+    pub fn plan(&self, state: &State) -> Result<Vec<Change>> {
+        let mut changes = Vec::new();
+
+        if let Some(name) = &self.hostname {
+            hostname::validate(name)?;
+            if state.hostname.as_ref() != Some(name) {
+                changes.push(Change::Hostname(name.clone()));
+            }
+        }
+        if let Some(lang) = &self.locale {
+            if state.locale.as_ref() != Some(lang) {
+                changes.push(Change::Locale(lang.clone()));
+            }
+        }
+        if let Some(tz) = &self.timezone {
+            if state.timezone.as_ref() != Some(tz) {
+                changes.push(Change::Timezone(tz.clone()));
+            }
+        }
+        if let Some(UserConfig { name, password: Some(password) }) = &self.user {
+            changes.push(Change::Password { user: name.clone(), password: password.clone() });
+        }
+
+        if let Some(ssh) = &self.ssh {
+            if !ssh.authorized_keys.is_empty() {
+                let user = self
+                    .authorized_keys_user()
+                    .ok_or_else(|| eyre!("ssh.authorized_keys needs ssh.user or a [user] section"))?;
+                let existing = state.authorized_keys.get(user).map(Vec::as_slice).unwrap_or_default();
+                let mut missing: Vec<String> = Vec::new();
+                for key in &ssh.authorized_keys {
+                    AuthorizedKey::parse(key).map_err(|e| eyre!("{e}: {key}"))?;
+                    if !existing.iter().chain(&missing).any(|k| same_key(k, key)) {
+                        missing.push(key.clone());
+                    }
+                }
+                if !missing.is_empty() {
+                    changes.push(Change::AuthorizedKeys { user: user.to_string(), keys: missing });
+                }
+            }
+            if let Some(enabled) = ssh.enabled {
+                if !state.ssh.is_some_and(|s| s.active == enabled && s.enabled == enabled) {
+                    changes.push(Change::Ssh(enabled));
+                }
+            }
+        }
+
+        // The pinmux overlay is installed first, so the overlay list can name it.
+        if !self.pins.is_empty() && state.pins.as_ref() != Some(&self.pins) {
+            changes.push(Change::Pins(self.pins.clone()));
+        }
+
+        if let Some(overlays) = &self.overlays {
+            let wanted: Vec<String> = overlays.iter().map(|o| overlay_file_name(o)).collect();
+            if state.overlays.as_ref() != Some(&wanted) {
+                changes.push(Change::Overlays(wanted));
+            }
+        } else if !self.pins.is_empty() {
+            // Without a list of its own, the file gets the pinmux overlay added to the current one.
+            let pinmux = format!("{OVERLAY_NAME}.dtbo");
+            if let Some(current) = state.overlays.as_ref().filter(|current| !current.contains(&pinmux)) {
+                changes.push(Change::Overlays([current.as_slice(), &[pinmux]].concat()));
+            }
+        }
+
+        for network in &self.wifi {
+            let known = state
+                .known_networks
+                .as_ref()
+                .is_some_and(|known| known.contains(&network.ssid));
+            if !known {
+                changes.push(Change::Wifi(network.clone()));
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAII94+suHER+6M4pdT+ZDN4veoq4QKvkmBtbmXpwagTj0 admin@laptop";
+
+    fn provision() -> Provision {
+        toml::from_str(&format!(
+            r#"
+            hostname = "beagle-01"
+            timezone = "Europe/Berlin"
+            overlays = ["BB-UART1-00A0"]
+
+            [user]
+            name = "debian"
+
+            [ssh]
+            enabled = true
+            authorized_keys = ["{KEY}"]
+
+            [pins]
+            P9_14 = "EHRPWM1 A"
+
+            [[wifi]]
+            ssid = "Workshop"
+            passphrase = "secret"
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan_from_scratch() {
+        let changes = provision().plan(&State::default()).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Hostname(String::from("beagle-01")),
+                Change::Timezone(String::from("Europe/Berlin")),
+                Change::AuthorizedKeys { user: String::from("debian"), keys: vec![KEY.to_string()] },
+                Change::Ssh(true),
+                Change::Pins(BTreeMap::from([(String::from("P9_14"), String::from("EHRPWM1 A"))])),
+                Change::Overlays(vec![String::from("BB-UART1-00A0.dtbo")]),
+                Change::Wifi(WifiConfig {
+                    ssid: String::from("Workshop"),
+                    security: None,
+                    passphrase: Some(String::from("secret")),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_is_idempotent() {
+        let state = State {
+            hostname: Some(String::from("beagle-01")),
+            locale: None,
+            timezone: Some(String::from("Europe/Berlin")),
+            overlays: Some(vec![String::from("BB-UART1-00A0.dtbo")]),
+            ssh: Some(SshStatus { active: true, enabled: true }),
+            authorized_keys: HashMap::from([(String::from("debian"), vec![format!("{KEY} old comment")])]),
+            pins: Some(BTreeMap::from([(String::from("P9_14"), String::from("EHRPWM1 A"))])),
+            known_networks: Some(vec![String::from("Workshop")]),
+        };
+        assert_eq!(provision().plan(&state).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(toml::from_str::<Provision>("hostnam = \"typo\"").is_err());
+
+        let keys_without_user: Provision = toml::from_str(&format!("[ssh]\nauthorized_keys = [\"{KEY}\"]")).unwrap();
+        assert!(keys_without_user.plan(&State::default()).is_err());
+
+        let bad_hostname: Provision = toml::from_str("hostname = \"-nope\"").unwrap();
+        assert!(bad_hostname.plan(&State::default()).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let provision = provision();
+        let content = toml::to_string_pretty(&provision).unwrap();
+        assert!(content.starts_with("hostname = \"beagle-01\"\n"));
+        assert!(content.contains("[pins]\nP9_14 = \"EHRPWM1 A\"\n"));
+        assert_eq!(toml::from_str::<Provision>(&content).unwrap(), provision);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    io,
+    path::Path,
+    process::{Command, Output},
+};
+
+use color_eyre::{eyre::eyre, Report, Result};
+
+pub mod authorized_keys;
+pub mod clock;
+pub mod host_keys;
+pub mod hostname;
+pub mod keyboard;
+pub mod locale;
+pub mod password;
+pub mod privilege;
+pub mod secret;
+pub mod ssh;
+pub mod sshd_config;
+pub mod systemd;
+pub mod users;
+
+/// What `sd_booted()` checks, without linking libsystemd.
+fn booted_with_systemd(root: &Path) -> bool {
+    root.join("run/systemd/system").is_dir()
+}
+
+/// Run `program` and return its trimmed stdout, failing when it exits unsuccessfully.
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program).args(args).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(command_error(program, &output))
+    }
+}
+
+/// Run `program` as root with `input` on its stdin, failing when it exits unsuccessfully.
+pub(crate) fn run_privileged(
+    privilege: &dyn privilege::Privilege,
+    program: &str,
+    args: &[&str],
+    input: &secret::Secret,
+) -> Result<()> {
+    let output = privilege.run(program, args, input)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error(program, &output))
+    }
+}
+
+/// The way to become root, or a permission error saying that `what` needs root.
+pub(crate) fn require<'a>(
+    privilege: Option<&'a dyn privilege::Privilege>,
+    what: &str,
+) -> Result<&'a dyn privilege::Privilege> {
+    privilege.ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, format!("{what} needs root")).into())
+}
+
+/// Replace the file at `path` with `content`, as root.
+pub(crate) fn write_as_root(privilege: &dyn privilege::Privilege, path: &Path, content: &str) -> Result<()> {
+    run_privileged(privilege, "tee", &[&path.to_string_lossy()], &secret::Secret::new(content))
+}
+
+/// Turn a failed command into an error, keeping permission problems recognisable so callers
+/// can tell them apart from other failures.
+fn command_error(program: &str, output: &Output) -> Report {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let message = if stderr.is_empty() {
+        format!("{program} failed with {}", output.status)
+    } else {
+        format!("{program} failed: {stderr}")
+    };
+
+    let denied = ["Access denied", "authentication required", "Permission denied", "must be run as root"];
+    if denied.iter().any(|d| stderr.contains(d)) {
+        io::Error::new(io::ErrorKind::PermissionDenied, message).into()
+    } else {
+        eyre!(message)
+    }
+}
@@ -0,0 +1,371 @@
+//! Public keys allowed to log in over SSH, kept in `~/.ssh/authorized_keys`.
+
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{chown, DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    Engine,
+};
+use color_eyre::{eyre::eyre, Result};
+use sha2::{Digest, Sha256};
+
+/// Key types sshd accepts.
+const KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+    "ssh-dss",
+];
+
+/// A local account, as listed in `/etc/passwd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+}
+
+impl Account {
+    pub fn lookup(user: &str) -> Result<Self> {
+        Self::lookup_in(&fs::read_to_string("/etc/passwd")?, user)
+    }
+
+    fn lookup_in(passwd: &str, user: &str) -> Result<Self> {
+        passwd
+            .lines()
+            .map(|line| line.split(':').collect::<Vec<_>>())
+            .find(|fields| fields.len() >= 6 && fields[0] == user)
+            .map(|fields| {
+                Ok(Account {
+                    name: user.to_string(),
+                    uid: fields[2].parse()?,
+                    gid: fields[3].parse()?,
+                    home: PathBuf::from(fields[5]),
+                })
+            })
+            .unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::NotFound, format!("No user named {user}")).into()))
+    }
+
+    pub fn authorized_keys_path(&self) -> PathBuf {
+        self.home.join(".ssh/authorized_keys")
+    }
+}
+
+/// One line of an `authorized_keys` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizedKey {
+    /// Options such as `no-pty` or `command="..."`, as written.
+    pub options: Option<String>,
+    pub key_type: String,
+    /// The base64 encoded public key.
+    pub blob: String,
+    pub comment: String,
+}
+
+impl AuthorizedKey {
+    /// Parse and validate a line, checking the key data really is a key of the stated type.
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (options, rest) = match line.split_whitespace().next() {
+            Some(first) if KEY_TYPES.contains(&first) => (None, line),
+            _ => {
+                let (options, rest) = split_options(line);
+                (Some(options.to_string()), rest)
+            }
+        };
+
+        let mut words = rest.splitn(3, char::is_whitespace);
+        let key_type = words.next().unwrap_or_default();
+        if !KEY_TYPES.contains(&key_type) {
+            return Err(eyre!("Not a public key, expected a line like `ssh-ed25519 AAAA... comment`"));
+        }
+        let blob = words.next().unwrap_or_default().trim();
+        let data = STANDARD
+            .decode(blob)
+            .map_err(|_| eyre!("The {key_type} key data is not valid base64"))?;
+        // The key data starts with the key type as a length-prefixed string.
+        let prefix = data
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .and_then(|len| data.get(4..4 + len));
+        if prefix != Some(key_type.as_bytes()) {
+            return Err(eyre!("The key data does not hold a {key_type} key"));
+        }
+
+        Ok(AuthorizedKey {
+            options,
+            key_type: key_type.to_string(),
+            blob: blob.to_string(),
+            comment: words.next().unwrap_or_default().trim().to_string(),
+        })
+    }
+
+    /// The SHA256 fingerprint, as printed by `ssh-keygen -l`.
+    pub fn fingerprint(&self) -> String {
+        let data = STANDARD.decode(&self.blob).unwrap_or_default();
+        format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(data)))
+    }
+
+    /// Whether both hold the same key, whatever their options and comments.
+    pub fn same_key(&self, other: &AuthorizedKey) -> bool {
+        self.key_type == other.key_type && self.blob == other.blob
+    }
+}
+
+impl fmt::Display for AuthorizedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(options) = &self.options {
+            write!(f, "{options} ")?;
+        }
+        write!(f, "{} {}", self.key_type, self.blob)?;
+        if !self.comment.is_empty() {
+            write!(f, " {}", self.comment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split the options off a line, honouring spaces inside quotes.
+fn split_options(line: &str) -> (&str, &str) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => return (&line[..i], line[i..].trim_start()),
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+/// Whether two lines hold the same key, whatever their options and comments.
+pub fn same_key(a: &str, b: &str) -> bool {
+    match (AuthorizedKey::parse(a), AuthorizedKey::parse(b)) {
+        (Ok(a), Ok(b)) => a.same_key(&b),
+        _ => false,
+    }
+}
+
+/// Public keys in a file such as `id_ed25519.pub` or another `authorized_keys`, e.g. on a USB
+/// stick. Lines that are not keys are skipped, but at least one key must be found.
+pub fn import(path: &Path) -> Result<Vec<AuthorizedKey>> {
+    let keys: Vec<AuthorizedKey> = fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| AuthorizedKey::parse(line).ok())
+        .collect();
+    if keys.is_empty() {
+        return Err(eyre!("No public keys found in {}", path.display()));
+    }
+    Ok(keys)
+}
+
+/// The `authorized_keys` file of one account.
+#[derive(Debug, Clone)]
+pub struct AuthorizedKeysFile {
+    pub path: PathBuf,
+    /// Owner given to a newly created file, `None` to keep the one of the current process.
+    owner: Option<(u32, u32)>,
+}
+
+impl AuthorizedKeysFile {
+    pub fn for_user(user: &str) -> Result<Self> {
+        let account = Account::lookup(user)?;
+        Ok(Self::new(account.authorized_keys_path(), Some((account.uid, account.gid))))
+    }
+
+    pub fn new(path: PathBuf, owner: Option<(u32, u32)>) -> Self {
+        AuthorizedKeysFile { path, owner }
+    }
+
+    /// Raw key lines, without comments and blank lines.
+    pub fn lines(&self) -> Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The keys in the file, skipping lines that cannot be parsed.
+    pub fn keys(&self) -> Result<Vec<AuthorizedKey>> {
+        Ok(self.lines()?.iter().filter_map(|l| AuthorizedKey::parse(l).ok()).collect())
+    }
+
+    /// Append the `keys` that are not in the file yet and return how many were added. A newly
+    /// created `.ssh` directory and file get the permissions sshd insists on.
+    pub fn add(&self, keys: &[AuthorizedKey]) -> Result<usize> {
+        let existing = self.keys()?;
+        let mut missing: Vec<&AuthorizedKey> = Vec::new();
+        for key in keys {
+            if !existing.iter().chain(missing.iter().copied()).any(|k| k.same_key(key)) {
+                missing.push(key);
+            }
+        }
+        if missing.is_empty() {
+            return Ok(0);
+        }
+
+        let dir = self.path.parent().ok_or_else(|| eyre!("Invalid path {}", self.path.display()))?;
+        if !dir.exists() {
+            fs::DirBuilder::new().mode(0o700).create(dir)?;
+            self.chown(dir)?;
+        }
+        let created = !self.path.exists();
+        let unterminated = !created && fs::read(&self.path)?.last().is_some_and(|b| *b != b'\n');
+        let mut file = OpenOptions::new().create(true).append(true).mode(0o600).open(&self.path)?;
+        if created {
+            self.chown(&self.path)?;
+        }
+        if unterminated {
+            file.write_all(b"\n")?;
+        }
+        for key in &missing {
+            writeln!(file, "{key}")?;
+        }
+        Ok(missing.len())
+    }
+
+    /// Remove every line holding `key`. The file is replaced atomically and keeps its mode and
+    /// owner; comments and other lines are left as they are.
+    pub fn remove(&self, key: &AuthorizedKey) -> Result<()> {
+        let content = fs::read_to_string(&self.path)?;
+        let kept: String = content
+            .lines()
+            .filter(|line| !AuthorizedKey::parse(line).is_ok_and(|k| k.same_key(key)))
+            .map(|line| format!("{line}\n"))
+            .collect();
+
+        let metadata = fs::metadata(&self.path)?;
+        let temp = self.path.with_extension("beagle-config.tmp");
+        let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&temp)?;
+        let result = file
+            .write_all(kept.as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::set_permissions(&temp, fs::Permissions::from_mode(metadata.mode())))
+            .and_then(|_| match chown(&temp, Some(metadata.uid()), Some(metadata.gid())) {
+                // Only root can hand files to other users; as the owner we already match.
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+                other => other,
+            })
+            .and_then(|_| fs::rename(&temp, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        Ok(result?)
+    }
+
+    fn chown(&self, path: &Path) -> Result<()> {
+        if let Some((uid, gid)) = self.owner {
+            chown(path, Some(uid), Some(gid))?;
+        }
+        Ok(())
+    }
+}
+
+/// The account whose keys are managed by default: the one that ran `sudo`, else the current one.
+pub fn target_user() -> String {
+    std::env::var("SUDO_USER").unwrap_or_else(|_| whoami::username())
+}
+
+/// Key lines in the `authorized_keys` file of `user`.
+pub fn keys(user: &str) -> Result<Vec<String>> {
+    AuthorizedKeysFile::for_user(user)?.lines()
+}
+
+/// Append the keys `user` does not have yet and return how many were added.
+pub fn add_keys(user: &str, keys: &[String]) -> Result<usize> {
+    let keys = keys.iter().map(|k| AuthorizedKey::parse(k)).collect::<Result<Vec<_>>>()?;
+    AuthorizedKeysFile::for_user(user)?.add(&keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// A real ed25519 public key, `ssh-keygen -l` prints
+    /// `SHA256:ogskNat1cId3gJk5V32m/m5LRAkjijv/e2sSUFog6Ag`.
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAII94+suHER+6M4pdT+ZDN4veoq4QKvkmBtbmXpwagTj0 admin@laptop";
+
+    fn file(dir: &tempfile::TempDir) -> AuthorizedKeysFile {
+        AuthorizedKeysFile::new(dir.path().join(".ssh/authorized_keys"), None)
+    }
+
+    #[test]
+    fn test_lookup() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\ndebian:x:1000:1000:Debian:/home/debian:/bin/bash\n";
+        let account = Account::lookup_in(passwd, "debian").unwrap();
+        assert_eq!(account.uid, 1000);
+        assert_eq!(account.authorized_keys_path(), PathBuf::from("/home/debian/.ssh/authorized_keys"));
+        assert!(Account::lookup_in(passwd, "nobody").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let key = AuthorizedKey::parse(KEY).unwrap();
+        assert_eq!(key.key_type, "ssh-ed25519");
+        assert_eq!(key.comment, "admin@laptop");
+        assert_eq!(key.fingerprint(), "SHA256:ogskNat1cId3gJk5V32m/m5LRAkjijv/e2sSUFog6Ag");
+        assert_eq!(key.to_string(), KEY);
+
+        let restricted = AuthorizedKey::parse(&format!("command=\"echo hi there\",no-pty {KEY}")).unwrap();
+        assert_eq!(restricted.options.as_deref(), Some("command=\"echo hi there\",no-pty"));
+        assert!(restricted.same_key(&key));
+
+        assert!(AuthorizedKey::parse("garbage").is_err());
+        assert!(AuthorizedKey::parse("ssh-ed25519 not-base64!").is_err());
+        // Valid base64, but an RSA key claiming to be ed25519.
+        assert!(AuthorizedKey::parse("ssh-ed25519 AAAAB3NzaC1yc2EAAAADAQABAAAAgQC7").is_err());
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let home = tempfile::tempdir().unwrap();
+        let file = file(&home);
+        let key = AuthorizedKey::parse(KEY).unwrap();
+
+        assert_eq!(file.add(std::slice::from_ref(&key)).unwrap(), 1);
+        assert_eq!(file.add(std::slice::from_ref(&key)).unwrap(), 0);
+        assert_eq!(fs::read_to_string(&file.path).unwrap(), format!("{KEY}\n"));
+        assert_eq!(fs::metadata(&file.path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(file.path.parent().unwrap()).unwrap().permissions().mode() & 0o777, 0o700);
+
+        fs::write(&file.path, format!("# laptop\n{KEY}\n")).unwrap();
+        fs::set_permissions(&file.path, fs::Permissions::from_mode(0o640)).unwrap();
+        file.remove(&key).unwrap();
+        assert_eq!(fs::read_to_string(&file.path).unwrap(), "# laptop\n");
+        assert_eq!(fs::metadata(&file.path).unwrap().permissions().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("id_ed25519.pub");
+        fs::write(&path, format!("{KEY}\nnot a key\n")).unwrap();
+        assert_eq!(import(&path).unwrap().len(), 1);
+
+        fs::write(&path, "not a key\n").unwrap();
+        assert!(import(&path).is_err());
+    }
+}
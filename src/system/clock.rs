@@ -0,0 +1,311 @@
+//! The system clock: time, timezone and NTP sync. Through timedated over D-Bus when systemd
+//! runs, else by linking `/etc/localtime` and calling `date`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use zbus::{blocking::Connection, proxy, CacheProperties};
+
+use super::{
+    booted_with_systemd,
+    privilege::{is_root, Privilege},
+    run_privileged,
+    secret::Secret,
+    systemd::dbus_error,
+};
+
+const ZONEINFO: &str = "usr/share/zoneinfo";
+/// Copies of the database with other leap second handling, not zones of their own.
+const NOT_REGIONS: &[&str] = &["posix", "right"];
+
+/// What the clock is set to, apart from the time itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClockStatus {
+    pub timezone: String,
+    /// Whether a time sync service is installed, so NTP can be turned on.
+    pub can_ntp: bool,
+    pub ntp: bool,
+    pub synchronized: bool,
+    /// The NTP server timesyncd talks to, if it reports one.
+    pub server: Option<String>,
+}
+
+/// The ways to set the clock, so the view works with and without timedated.
+pub trait Clock: Send {
+    fn status(&self) -> Result<ClockStatus>;
+    /// `timezone` is a name from the tz database, e.g. `Europe/Berlin`.
+    fn set_timezone(&self, timezone: &str) -> Result<()>;
+    fn set_ntp(&self, on: bool) -> Result<()>;
+    fn set_time(&self, time: DateTime<Utc>) -> Result<()>;
+}
+
+/// timedated when systemd runs, else files and commands run as root.
+pub fn detect(root: &Path, privilege: Option<Box<dyn Privilege>>) -> Box<dyn Clock> {
+    if booted_with_systemd(root) {
+        if let Ok(connection) = Connection::system() {
+            // Without a polkit agent, timedated only takes changes from root over D-Bus.
+            let timedatectl = privilege.filter(|_| !is_root()).map(|privilege| Timedatectl { privilege });
+            return Box::new(Timedated { connection, timedatectl });
+        }
+    }
+    Box::new(LocaltimeLink::new(root.to_path_buf(), privilege))
+}
+
+#[proxy(
+    interface = "org.freedesktop.timedate1",
+    default_service = "org.freedesktop.timedate1",
+    default_path = "/org/freedesktop/timedate1"
+)]
+trait Timedate {
+    fn set_time(&self, usec_utc: i64, relative: bool, interactive: bool) -> zbus::Result<()>;
+    fn set_timezone(&self, timezone: &str, interactive: bool) -> zbus::Result<()>;
+    #[zbus(name = "SetNTP")]
+    fn set_ntp(&self, use_ntp: bool, interactive: bool) -> zbus::Result<()>;
+    #[zbus(property)]
+    fn timezone(&self) -> zbus::Result<String>;
+    #[zbus(property, name = "CanNTP")]
+    fn can_ntp(&self) -> zbus::Result<bool>;
+    #[zbus(property, name = "NTP")]
+    fn ntp(&self) -> zbus::Result<bool>;
+    #[zbus(property, name = "NTPSynchronized")]
+    fn ntp_synchronized(&self) -> zbus::Result<bool>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.timesync1.Manager",
+    default_service = "org.freedesktop.timesync1",
+    default_path = "/org/freedesktop/timesync1"
+)]
+trait Timesync {
+    #[zbus(property)]
+    fn server_name(&self) -> zbus::Result<String>;
+}
+
+/// systemd-timedated, which also keeps the RTC in step. Changes go through `timedatectl` run as
+/// root when not root already.
+pub struct Timedated {
+    connection: Connection,
+    timedatectl: Option<Timedatectl>,
+}
+
+impl Timedated {
+    fn proxy(&self) -> Result<TimedateProxyBlocking<'_>> {
+        Ok(TimedateProxyBlocking::builder(&self.connection).cache_properties(CacheProperties::No).build()?)
+    }
+}
+
+impl Clock for Timedated {
+    fn status(&self) -> Result<ClockStatus> {
+        let timedate = self.proxy()?;
+        // timesyncd only runs while NTP is on.
+        let server = TimesyncProxyBlocking::builder(&self.connection)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .and_then(|timesync| timesync.server_name())
+            .ok()
+            .filter(|server| !server.is_empty());
+        Ok(ClockStatus {
+            timezone: timedate.timezone().map_err(dbus_error)?,
+            can_ntp: timedate.can_ntp().map_err(dbus_error)?,
+            ntp: timedate.ntp().map_err(dbus_error)?,
+            synchronized: timedate.ntp_synchronized().map_err(dbus_error)?,
+            server,
+        })
+    }
+
+    fn set_timezone(&self, timezone: &str) -> Result<()> {
+        match &self.timedatectl {
+            Some(timedatectl) => timedatectl.run(&["set-timezone", timezone]),
+            None => self.proxy()?.set_timezone(timezone, false).map_err(dbus_error),
+        }
+    }
+
+    fn set_ntp(&self, on: bool) -> Result<()> {
+        match &self.timedatectl {
+            Some(timedatectl) => timedatectl.run(&["set-ntp", if on { "true" } else { "false" }]),
+            None => self.proxy()?.set_ntp(on, false).map_err(dbus_error),
+        }
+    }
+
+    fn set_time(&self, time: DateTime<Utc>) -> Result<()> {
+        match &self.timedatectl {
+            Some(timedatectl) => timedatectl.run(&["set-time", &format!("@{}", time.timestamp())]),
+            None => self.proxy()?.set_time(time.timestamp_micros(), false, false).map_err(dbus_error),
+        }
+    }
+}
+
+/// timedated's command line tool, run through a privilege.
+struct Timedatectl {
+    privilege: Box<dyn Privilege>,
+}
+
+impl Timedatectl {
+    fn run(&self, args: &[&str]) -> Result<()> {
+        run_privileged(self.privilege.as_ref(), "timedatectl", args, &Secret::default())
+    }
+}
+
+/// Without systemd: the timezone is the target of the `/etc/localtime` link and there is no
+/// NTP service to control.
+pub struct LocaltimeLink {
+    root: PathBuf,
+    privilege: Option<Box<dyn Privilege>>,
+}
+
+impl LocaltimeLink {
+    pub fn new(root: PathBuf, privilege: Option<Box<dyn Privilege>>) -> Self {
+        LocaltimeLink { root, privilege }
+    }
+
+    fn run(&self, program: &str, args: &[&str]) -> Result<()> {
+        let privilege = self
+            .privilege
+            .as_deref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "Setting the clock needs root"))?;
+        run_privileged(privilege, program, args, &Secret::default())
+    }
+}
+
+impl Clock for LocaltimeLink {
+    fn status(&self) -> Result<ClockStatus> {
+        let target = fs::read_link(self.root.join("etc/localtime"))?;
+        let timezone = target
+            .to_string_lossy()
+            .split_once("zoneinfo/")
+            .map(|(_, timezone)| timezone.to_string())
+            .unwrap_or_else(|| String::from("UTC"));
+        Ok(ClockStatus { timezone, ..ClockStatus::default() })
+    }
+
+    fn set_timezone(&self, timezone: &str) -> Result<()> {
+        let known = zones(&self.root)?;
+        if !known.iter().any(|(region, cities)| cities.iter().any(|city| timezone == join(region, city))) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown timezone {timezone}")).into());
+        }
+        let zone = Path::new("/").join(ZONEINFO).join(timezone);
+        let localtime = self.root.join("etc/localtime");
+        self.run("ln", &["-sfn", &zone.to_string_lossy(), &localtime.to_string_lossy()])
+    }
+
+    fn set_ntp(&self, _: bool) -> Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "NTP sync needs systemd-timesyncd").into())
+    }
+
+    fn set_time(&self, time: DateTime<Utc>) -> Result<()> {
+        self.run("date", &["--utc", "--set", &format!("@{}", time.timestamp())])
+    }
+}
+
+/// The zones of the tz database below `root`, by region: `Europe` holds `Berlin`, `America`
+/// holds `Argentina/Buenos_Aires`. Zones outside a region, like `UTC`, are in `Etc` as well.
+pub fn zones(root: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let zoneinfo = root.join(ZONEINFO);
+    let mut zones = BTreeMap::new();
+    for entry in fs::read_dir(&zoneinfo)? {
+        let entry = entry?;
+        let region = entry.file_name().to_string_lossy().to_string();
+        if !entry.file_type()?.is_dir() || NOT_REGIONS.contains(&region.as_str()) {
+            continue;
+        }
+        let mut cities = Vec::new();
+        collect_zones(&entry.path(), &entry.path(), &mut cities)?;
+        if !cities.is_empty() {
+            cities.sort();
+            zones.insert(region, cities);
+        }
+    }
+    Ok(zones)
+}
+
+/// Every zone of the tz database below `root` by its full name, e.g. `Europe/Berlin`.
+pub fn timezones(root: &Path) -> Result<Vec<String>> {
+    Ok(zones(root)?.iter().flat_map(|(region, cities)| cities.iter().map(|city| join(region, city))).collect())
+}
+
+/// The full name of `city` in `region`.
+pub fn join(region: &str, city: &str) -> String {
+    format!("{region}/{city}")
+}
+
+fn collect_zones(base: &Path, dir: &Path, zones: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_zones(base, &path, zones)?;
+        } else if is_tzif(&path) {
+            if let Ok(name) = path.strip_prefix(base) {
+                zones.push(name.to_string_lossy().to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Zone files start with `TZif`, unlike the tables next to them.
+fn is_tzif(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    fs::File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == b"TZif"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::privilege::mock::Recorder;
+
+    #[test]
+    fn test_localtime_link() {
+        let root = tempfile::tempdir().unwrap();
+        let zoneinfo = root.path().join(ZONEINFO);
+        for dir in ["Europe", "America/Argentina", "Etc", "posix/Europe"] {
+            fs::create_dir_all(zoneinfo.join(dir)).unwrap();
+        }
+        let files = ["Europe/Berlin", "Europe/Paris", "America/Argentina/Buenos_Aires", "Etc/UTC", "posix/Europe/Berlin"];
+        for zone in files {
+            fs::write(zoneinfo.join(zone), "TZif2").unwrap();
+        }
+        fs::write(zoneinfo.join("zone.tab"), "# tz zone descriptions\n").unwrap();
+        fs::write(zoneinfo.join("Europe/README"), "not a zone\n").unwrap();
+        fs::create_dir(root.path().join("etc")).unwrap();
+        symlink("/usr/share/zoneinfo/Etc/UTC", root.path().join("etc/localtime")).unwrap();
+
+        let zones = zones(root.path()).unwrap();
+        assert_eq!(zones.keys().collect::<Vec<_>>(), ["America", "Etc", "Europe"]);
+        assert_eq!(zones["America"], ["Argentina/Buenos_Aires"]);
+        assert_eq!(zones["Europe"], ["Berlin", "Paris"]);
+        assert_eq!(timezones(root.path()).unwrap()[..2], ["America/Argentina/Buenos_Aires", "Etc/UTC"]);
+
+        let recorder = Recorder::default();
+        let clock = LocaltimeLink::new(root.path().to_path_buf(), Some(Box::new(recorder.clone())));
+        assert_eq!(clock.status().unwrap().timezone, "Etc/UTC");
+        clock.set_timezone("Europe/Berlin").unwrap();
+        assert!(clock.set_timezone("Europe/Atlantis").is_err());
+        assert!(clock.set_ntp(true).is_err());
+        clock.set_time(DateTime::from_timestamp(1_700_000_000, 0).unwrap()).unwrap();
+        assert_eq!(
+            recorder.commands(),
+            [
+                format!("ln -sfn /usr/share/zoneinfo/Europe/Berlin {}/etc/localtime", root.path().display()),
+                String::from("date --utc --set @1700000000"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timedatectl() {
+        let recorder = Recorder::default();
+        let timedatectl = Timedatectl { privilege: Box::new(recorder.clone()) };
+        timedatectl.run(&["set-timezone", "Europe/Berlin"]).unwrap();
+        assert_eq!(recorder.commands(), ["timedatectl set-timezone Europe/Berlin"]);
+    }
+}
@@ -0,0 +1,163 @@
+//! The SSH server's host keys, which clients remember to recognise the board. Boards flashed
+//! from the same image share them until they are regenerated.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use color_eyre::{eyre::eyre, Result};
+use serde::Serialize;
+
+use super::{
+    authorized_keys::AuthorizedKey, privilege::Privilege, run_privileged, secret::Secret, ssh, systemd::Systemd,
+};
+
+/// Where the keys live, relative to the root directory.
+const SSH_DIR: &str = "etc/ssh";
+const SSH_KEYGEN: &str = "ssh-keygen";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostKey {
+    pub key_type: String,
+    pub fingerprint: String,
+    pub path: PathBuf,
+}
+
+/// The public host keys under `root`, sorted by file name.
+pub fn host_keys(root: &Path) -> Result<Vec<HostKey>> {
+    host_key_files(root)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|e| e == "pub"))
+        .map(|path| {
+            let key = AuthorizedKey::parse(&fs::read_to_string(&path)?)
+                .map_err(|e| eyre!("{}: {e}", path.display()))?;
+            Ok(HostKey { key_type: key.key_type.clone(), fingerprint: key.fingerprint(), path })
+        })
+        .collect()
+}
+
+/// Private and public host key files, `ssh_host_<type>_key[.pub]`.
+fn host_key_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(root.join(SSH_DIR))?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name().to_str().is_some_and(|name| {
+                name.starts_with("ssh_host_") && (name.ends_with("_key") || name.ends_with("_key.pub"))
+            })
+        })
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Replace the host keys with new ones as root and restart the SSH server if it is running.
+/// The old keys are moved into a backup directory next to them, whose path is returned, and put
+/// back if no new keys could be generated.
+pub fn regenerate(root: &Path, systemd: &dyn Systemd, privilege: &dyn Privilege) -> Result<PathBuf> {
+    regenerate_with(root, systemd, privilege, SSH_KEYGEN)
+}
+
+fn regenerate_with(root: &Path, systemd: &dyn Systemd, privilege: &dyn Privilege, keygen: &str) -> Result<PathBuf> {
+    let run = |program: &str, args: &[&str]| run_privileged(privilege, program, args, &Secret::default());
+    let backup = root
+        .join(SSH_DIR)
+        .join(format!("host-keys-{}.bak", Local::now().format("%Y%m%d-%H%M%S")));
+    run("install", &["-d", "-m", "700", &backup.to_string_lossy()])?;
+
+    let files = host_key_files(root)?;
+    let moved = if files.is_empty() {
+        Ok(())
+    } else {
+        let target = backup.to_string_lossy();
+        let names: Vec<_> = files.iter().map(|file| file.to_string_lossy()).collect();
+        let mut args = vec!["-t", target.as_ref()];
+        args.extend(names.iter().map(AsRef::as_ref));
+        run("mv", &args)
+    };
+    let generated = moved.and_then(|_| {
+        // With -A, ssh-keygen creates every missing key type below the -f prefix.
+        run(keygen, &["-A", "-f", &root.to_string_lossy()])?;
+        if host_keys(root)?.is_empty() {
+            return Err(eyre!("{keygen} did not create any host keys"));
+        }
+        Ok(())
+    });
+    if let Err(e) = generated {
+        restore(root, &backup, &files, privilege)?;
+        return Err(eyre!("Unable to regenerate the host keys, the old ones were kept: {e}"));
+    }
+
+    let unit = ssh::unit(systemd);
+    if systemd.unit_state(unit).is_ok_and(|state| state.is_active()) {
+        systemd
+            .restart(unit)
+            .map_err(|e| eyre!("New host keys were created, but restarting {unit} failed: {e}"))?;
+    }
+    Ok(backup)
+}
+
+/// Move the `moved` keys back from `backup`, replacing anything generated in the meantime. Only
+/// root can list the backup directory, so the keys are named rather than looked up.
+fn restore(root: &Path, backup: &Path, moved: &[PathBuf], privilege: &dyn Privilege) -> Result<()> {
+    let run = |program: &str, args: &[&str]| run_privileged(privilege, program, args, &Secret::default());
+    for file in host_key_files(root)? {
+        run("rm", &["-f", &file.to_string_lossy()])?;
+    }
+    for file in moved {
+        let name = file.file_name().ok_or_else(|| eyre!("Invalid path {}", file.display()))?;
+        run("mv", &[&backup.join(name).to_string_lossy(), &file.to_string_lossy()])?;
+    }
+    run("rmdir", &[&backup.to_string_lossy()])
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::{privilege::AsRoot, systemd::mock::MockSystemd};
+
+    const KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAII94+suHER+6M4pdT+ZDN4veoq4QKvkmBtbmXpwagTj0 root@beagle\n";
+
+    fn root() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join(SSH_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ssh_host_ed25519_key"), "private").unwrap();
+        fs::write(dir.join("ssh_host_ed25519_key.pub"), KEY).unwrap();
+        fs::write(dir.join("sshd_config"), "").unwrap();
+        root
+    }
+
+    #[test]
+    fn test_host_keys() {
+        let root = root();
+        assert_eq!(
+            host_keys(root.path()).unwrap(),
+            vec![HostKey {
+                key_type: String::from("ssh-ed25519"),
+                fingerprint: String::from("SHA256:ogskNat1cId3gJk5V32m/m5LRAkjijv/e2sSUFog6Ag"),
+                path: root.path().join("etc/ssh/ssh_host_ed25519_key.pub"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_failed_regeneration_keeps_keys() {
+        let root = root();
+        let systemd = MockSystemd::new(&["ssh.service"]);
+        assert!(regenerate_with(root.path(), &systemd, &AsRoot, "false").is_err());
+        // Succeeding without writing keys counts as a failure too.
+        assert!(regenerate_with(root.path(), &systemd, &AsRoot, "true").is_err());
+
+        let mut names: Vec<String> = fs::read_dir(root.path().join(SSH_DIR))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["ssh_host_ed25519_key", "ssh_host_ed25519_key.pub", "sshd_config"]);
+    }
+}
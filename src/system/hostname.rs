@@ -0,0 +1,360 @@
+//! The system hostname: static and pretty names through hostnamed when systemd runs, else
+//! `/etc/hostname` and `/etc/machine-info`, plus the `127.0.1.1` line of `/etc/hosts`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use zbus::{blocking::Connection, proxy, CacheProperties};
+
+use super::{
+    booted_with_systemd,
+    privilege::{is_root, Privilege},
+    require, run_privileged,
+    secret::Secret,
+    systemd::dbus_error,
+    write_as_root,
+};
+use crate::boards::identity::Board;
+
+/// Longest hostname the kernel accepts.
+const HOST_NAME_MAX: usize = 64;
+/// Longest label RFC 1123 allows between dots.
+const LABEL_MAX: usize = 63;
+/// Debian maps the hostname to this address, so it resolves without DNS.
+const LOOPBACK_HOST: &str = "127.0.1.1";
+
+/// The static hostname, as stored in `/etc/hostname`.
+pub fn hostname() -> Result<String> {
+    Ok(fs::read_to_string("/etc/hostname")?.trim().to_string())
+}
+
+/// Check `name` is a valid hostname as of RFC 1123: dot separated labels of letters, digits and
+/// inner hyphens.
+pub fn validate(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > HOST_NAME_MAX {
+        return Err(eyre!("Hostnames must be 1 to {HOST_NAME_MAX} characters long"));
+    }
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= LABEL_MAX
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if name.split('.').all(valid_label) {
+        Ok(())
+    } else {
+        Err(eyre!("Invalid hostname `{name}`, use letters, digits and hyphens"))
+    }
+}
+
+/// A name unique to `board`, made of its kind and the serial number in its EEPROM, e.g.
+/// `beagleplay-4521p0001234`.
+pub fn from_serial(board: &Board) -> Option<String> {
+    let serial: String = board
+        .eeprom
+        .as_ref()?
+        .serial
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if serial.is_empty() {
+        return None;
+    }
+    let mut name = format!("{}-{serial}", board.kind.description_id());
+    name.truncate(LABEL_MAX);
+    Some(name)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Names {
+    /// The hostname used on the network, e.g. `beagle-lab-3`.
+    pub static_name: String,
+    /// A free-form name for people, e.g. `Lab 3 weather station`.
+    pub pretty: String,
+}
+
+/// The ways to set the hostname, so the view works with and without hostnamed.
+pub trait Hostnames: Send {
+    fn names(&self) -> Result<Names>;
+    fn set_static(&self, name: &str) -> Result<()>;
+    fn set_pretty(&self, pretty: &str) -> Result<()>;
+}
+
+/// hostnamed when systemd runs, else files written as root.
+pub fn detect(root: &Path, privilege: Option<Box<dyn Privilege>>) -> Box<dyn Hostnames> {
+    if booted_with_systemd(root) {
+        if let Ok(connection) = Connection::system() {
+            // Without a polkit agent, hostnamed only takes changes from root over D-Bus.
+            let hostnamectl = privilege.filter(|_| !is_root()).map(|privilege| Hostnamectl { privilege });
+            return Box::new(Hostnamed { connection, hostnamectl });
+        }
+    }
+    Box::new(EtcHostname::new(root.to_path_buf(), privilege))
+}
+
+#[proxy(
+    interface = "org.freedesktop.hostname1",
+    default_service = "org.freedesktop.hostname1",
+    default_path = "/org/freedesktop/hostname1"
+)]
+trait Hostname {
+    fn set_static_hostname(&self, hostname: &str, interactive: bool) -> zbus::Result<()>;
+    fn set_pretty_hostname(&self, hostname: &str, interactive: bool) -> zbus::Result<()>;
+    #[zbus(property)]
+    fn static_hostname(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn pretty_hostname(&self) -> zbus::Result<String>;
+}
+
+/// systemd-hostnamed, which also updates the running hostname. Changes go through `hostnamectl`
+/// run as root when not root already.
+pub struct Hostnamed {
+    connection: Connection,
+    hostnamectl: Option<Hostnamectl>,
+}
+
+impl Hostnamed {
+    fn proxy(&self) -> Result<HostnameProxyBlocking<'_>> {
+        Ok(HostnameProxyBlocking::builder(&self.connection).cache_properties(CacheProperties::No).build()?)
+    }
+}
+
+impl Hostnames for Hostnamed {
+    fn names(&self) -> Result<Names> {
+        let hostname = self.proxy()?;
+        Ok(Names {
+            static_name: hostname.static_hostname().map_err(dbus_error)?,
+            pretty: hostname.pretty_hostname().map_err(dbus_error)?,
+        })
+    }
+
+    fn set_static(&self, name: &str) -> Result<()> {
+        validate(name)?;
+        match &self.hostnamectl {
+            Some(hostnamectl) => hostnamectl.set_static(name),
+            None => self.proxy()?.set_static_hostname(name, false).map_err(dbus_error),
+        }
+    }
+
+    fn set_pretty(&self, pretty: &str) -> Result<()> {
+        match &self.hostnamectl {
+            Some(hostnamectl) => hostnamectl.set_pretty(pretty),
+            None => self.proxy()?.set_pretty_hostname(pretty, false).map_err(dbus_error),
+        }
+    }
+}
+
+/// hostnamed's command line tool, run through a privilege.
+struct Hostnamectl {
+    privilege: Box<dyn Privilege>,
+}
+
+impl Hostnamectl {
+    fn set_static(&self, name: &str) -> Result<()> {
+        run_privileged(self.privilege.as_ref(), "hostnamectl", &["set-hostname", name], &Secret::default())
+    }
+
+    fn set_pretty(&self, pretty: &str) -> Result<()> {
+        let args = ["--pretty", "set-hostname", pretty];
+        run_privileged(self.privilege.as_ref(), "hostnamectl", &args, &Secret::default())
+    }
+}
+
+/// Without systemd: `/etc/hostname`, `hostname` for the running system, and the
+/// `PRETTY_HOSTNAME` of `/etc/machine-info`.
+pub struct EtcHostname {
+    root: PathBuf,
+    privilege: Option<Box<dyn Privilege>>,
+}
+
+impl EtcHostname {
+    pub fn new(root: PathBuf, privilege: Option<Box<dyn Privilege>>) -> Self {
+        EtcHostname { root, privilege }
+    }
+}
+
+impl Hostnames for EtcHostname {
+    fn names(&self) -> Result<Names> {
+        let static_name = fs::read_to_string(self.root.join("etc/hostname"))?.trim().to_string();
+        let pretty = fs::read_to_string(self.root.join("etc/machine-info"))
+            .unwrap_or_default()
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_HOSTNAME="))
+            .map(|pretty| pretty.trim_matches('"').to_string())
+            .unwrap_or_default();
+        Ok(Names { static_name, pretty })
+    }
+
+    fn set_static(&self, name: &str) -> Result<()> {
+        validate(name)?;
+        let privilege = require(self.privilege.as_deref(), "Changing the hostname")?;
+        write_as_root(privilege, &self.root.join("etc/hostname"), &format!("{name}\n"))?;
+        run_privileged(privilege, "hostname", &[name], &Secret::default())
+    }
+
+    fn set_pretty(&self, pretty: &str) -> Result<()> {
+        let path = self.root.join("etc/machine-info");
+        let mut lines: Vec<String> = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.starts_with("PRETTY_HOSTNAME="))
+            .map(String::from)
+            .collect();
+        if !pretty.is_empty() {
+            lines.push(format!("PRETTY_HOSTNAME=\"{}\"", pretty.replace(['"', '\\', '\n'], "")));
+        }
+        let content: String = lines.iter().map(|line| format!("{line}\n")).collect();
+        write_as_root(require(self.privilege.as_deref(), "Changing the hostname")?, &path, &content)
+    }
+}
+
+/// Whether the `127.0.1.1` line of `/etc/hosts` below `root` names `name`.
+pub fn hosts_resolves(root: &Path, name: &str) -> bool {
+    fs::read_to_string(root.join("etc/hosts")).is_ok_and(|hosts| {
+        hosts.lines().any(|line| {
+            let mut words = line.split_whitespace();
+            words.next() == Some(LOOPBACK_HOST) && words.any(|word| word == name)
+        })
+    })
+}
+
+/// Replace `old` by `name` on the `127.0.1.1` line of `/etc/hosts` below `root`, adding the line
+/// if missing, so `sudo` and others can still resolve the hostname. Call it before changing the
+/// hostname, so the new name resolves as soon as it is set.
+pub fn update_hosts(root: &Path, old: &str, name: &str, privilege: Option<&dyn Privilege>) -> Result<()> {
+    if hosts_resolves(root, name) {
+        return Ok(());
+    }
+    let path = root.join("etc/hosts");
+    let hosts = hosts_with(&fs::read_to_string(&path)?, old, name);
+    write_as_root(require(privilege, "Changing the hostname")?, &path, &hosts)
+}
+
+/// `hosts` with `old` replaced by `name` on the `127.0.1.1` line, keeping the other names on it
+/// such as a fully qualified one, and with `name` added when `old` is not there.
+fn hosts_with(hosts: &str, old: &str, name: &str) -> String {
+    let mut lines: Vec<String> = hosts.lines().map(String::from).collect();
+    let address = |line: &str| line.split_whitespace().next().map(String::from);
+    match lines.iter().position(|line| address(line).as_deref() == Some(LOOPBACK_HOST)) {
+        Some(i) if lines[i].split_whitespace().skip(1).any(|word| word == old) => {
+            lines[i] = replace_word(&lines[i], old, name);
+        }
+        Some(i) => lines[i] = format!("{} {name}", lines[i].trim_end()),
+        None => {
+            let after = lines.iter().rposition(|line| address(line).as_deref() == Some("127.0.0.1"));
+            lines.insert(after.map_or(0, |i| i + 1), format!("{LOOPBACK_HOST}\t{name}"));
+        }
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// `line` with every whitespace separated `old` replaced by `new`, spacing kept as it is.
+fn replace_word(line: &str, old: &str, new: &str) -> String {
+    let mut replaced = String::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        let space = rest.len() - rest.trim_start().len();
+        replaced.push_str(&rest[..space]);
+        rest = &rest[space..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        replaced.push_str(if &rest[..end] == old { new } else { &rest[..end] });
+        rest = &rest[end..];
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        boards::{eeprom::Eeprom, identity::BoardKind},
+        system::privilege::mock::Recorder,
+    };
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("beagle-01").is_ok());
+        assert!(validate("bbb.lab.example").is_ok());
+        assert!(validate("").is_err());
+        assert!(validate("-beagle").is_err());
+        assert!(validate("beagle_01").is_err());
+        assert!(validate("beagle..lab").is_err());
+        assert!(validate(&"a".repeat(65)).is_err());
+        assert!(validate(&"a".repeat(64)).is_err());
+        assert!(validate(&"a".repeat(63)).is_ok());
+    }
+
+    #[test]
+    fn test_from_serial() {
+        let mut board = Board { kind: BoardKind::BeaglePlay, model: None, compatible: Vec::new(), eeprom: None };
+        assert_eq!(from_serial(&board), None);
+        board.eeprom = Some(Eeprom {
+            board_name: String::from("BEAGLEPLAY-A0-"),
+            version: String::from("02"),
+            serial: String::from("4521P0001234"),
+        });
+        assert_eq!(from_serial(&board).as_deref(), Some("beagleplay-4521p0001234"));
+    }
+
+    #[test]
+    fn test_hostnamectl() {
+        let recorder = Recorder::default();
+        let hostnamectl = Hostnamectl { privilege: Box::new(recorder.clone()) };
+        hostnamectl.set_static("lab-3").unwrap();
+        hostnamectl.set_pretty("Lab 3").unwrap();
+        assert_eq!(recorder.commands(), ["hostnamectl set-hostname lab-3", "hostnamectl --pretty set-hostname Lab 3"]);
+    }
+
+    #[test]
+    fn test_hosts() {
+        let debian = "127.0.0.1\tlocalhost\n127.0.1.1\tBeagleBone\n\n::1\t\tlocalhost ip6-localhost\n";
+        assert_eq!(
+            hosts_with(debian, "BeagleBone", "lab-3"),
+            "127.0.0.1\tlocalhost\n127.0.1.1\tlab-3\n\n::1\t\tlocalhost ip6-localhost\n"
+        );
+        assert_eq!(
+            hosts_with("127.0.0.1 localhost\n::1 localhost\n", "BeagleBone", "lab-3"),
+            "127.0.0.1 localhost\n127.0.1.1\tlab-3\n::1 localhost\n"
+        );
+        // The fully qualified name and aliases stay.
+        assert_eq!(
+            hosts_with("127.0.1.1\tBeagleBone.lab.example  BeagleBone bb\n", "BeagleBone", "lab-3"),
+            "127.0.1.1\tBeagleBone.lab.example  lab-3 bb\n"
+        );
+        assert_eq!(hosts_with("127.0.1.1 bb\n", "BeagleBone", "lab-3"), "127.0.1.1 bb lab-3\n");
+
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("etc")).unwrap();
+        fs::write(root.path().join("etc/hosts"), debian).unwrap();
+        fs::write(root.path().join("etc/hostname"), "BeagleBone\n").unwrap();
+        fs::write(root.path().join("etc/machine-info"), "DEPLOYMENT=lab\n").unwrap();
+        assert!(hosts_resolves(root.path(), "BeagleBone"));
+        assert!(!hosts_resolves(root.path(), "lab-3"));
+
+        let recorder = Recorder::default();
+        let etc = EtcHostname::new(root.path().to_path_buf(), Some(Box::new(recorder.clone())));
+        assert_eq!(etc.names().unwrap(), Names { static_name: String::from("BeagleBone"), pretty: String::new() });
+        assert!(etc.set_static("lab_3").is_err());
+        update_hosts(root.path(), "BeagleBone", "BeagleBone", Some(&recorder)).unwrap();
+        update_hosts(root.path(), "BeagleBone", "lab-3", Some(&recorder)).unwrap();
+        etc.set_static("lab-3").unwrap();
+        etc.set_pretty("Lab 3").unwrap();
+        let etc_dir = root.path().join("etc");
+        assert_eq!(
+            recorder.commands(),
+            [
+                format!("tee {}/hosts", etc_dir.display()),
+                format!("tee {}/hostname", etc_dir.display()),
+                String::from("hostname lab-3"),
+                format!("tee {}/machine-info", etc_dir.display()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! The console keyboard layout, through localed over D-Bus when systemd runs, else
+//! `/etc/default/keyboard`, and the console font of `/etc/default/console-setup`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::Result;
+use zbus::{blocking::Connection, proxy, CacheProperties};
+
+use super::{
+    booted_with_systemd,
+    privilege::{is_root, Privilege},
+    require, run_privileged, secret::Secret, systemd::dbus_error,
+    write_as_root,
+};
+
+/// The XKB rules listing every layout and variant with a description.
+const BASE_LST: &str = "usr/share/X11/xkb/rules/base.lst";
+const CONSOLE_FONTS: &str = "usr/share/consolefonts";
+
+/// An XKB layout or variant, e.g. `de` described as `German`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub description: String,
+}
+
+/// Every layout, and the variants of each layout by its name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layouts {
+    pub layouts: Vec<Entry>,
+    pub variants: BTreeMap<String, Vec<Entry>>,
+}
+
+/// Read the layouts and variants from the XKB rules below `root`.
+pub fn layouts(root: &Path) -> Result<Layouts> {
+    let rules = fs::read_to_string(root.join(BASE_LST))?;
+    let mut layouts = Layouts::default();
+    let mut section = "";
+    for line in rules.lines() {
+        if let Some(name) = line.strip_prefix("! ") {
+            section = name.trim();
+            continue;
+        }
+        let Some((name, description)) = line.trim().split_once(char::is_whitespace) else { continue };
+        let entry = |description: &str| Entry { name: name.to_string(), description: description.trim().to_string() };
+        match section {
+            "layout" => layouts.layouts.push(entry(description)),
+            // Variants are described as `layout: description`.
+            "variant" => {
+                if let Some((layout, description)) = description.trim().split_once(": ") {
+                    layouts.variants.entry(layout.to_string()).or_default().push(entry(description));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(layouts)
+}
+
+/// An XKB layout with an optional variant; an empty variant is the layout's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    pub layout: String,
+    pub variant: String,
+}
+
+/// The ways to set the keyboard layout, so the view works with and without localed.
+pub trait Keyboard: Send {
+    fn layout(&self) -> Result<KeyboardLayout>;
+    fn set_layout(&self, layout: &KeyboardLayout) -> Result<()>;
+}
+
+/// localed when systemd runs, else `/etc/default/keyboard` written as root.
+pub fn detect(root: &Path, privilege: Option<Box<dyn Privilege>>) -> Box<dyn Keyboard> {
+    if booted_with_systemd(root) {
+        if let Ok(connection) = Connection::system() {
+            // Without a polkit agent, localed only takes changes from root over D-Bus.
+            let privilege = privilege.filter(|_| !is_root());
+            return Box::new(Localed { connection, privilege });
+        }
+    }
+    Box::new(DefaultKeyboard::new(root.to_path_buf(), privilege))
+}
+
+#[proxy(
+    interface = "org.freedesktop.locale1",
+    default_service = "org.freedesktop.locale1",
+    default_path = "/org/freedesktop/locale1"
+)]
+trait Locale {
+    #[zbus(name = "SetX11Keyboard")]
+    #[allow(clippy::too_many_arguments)]
+    fn set_x11_keyboard(
+        &self,
+        layout: &str,
+        model: &str,
+        variant: &str,
+        options: &str,
+        convert: bool,
+        interactive: bool,
+    ) -> zbus::Result<()>;
+    #[zbus(property, name = "X11Layout")]
+    fn x11_layout(&self) -> zbus::Result<String>;
+    #[zbus(property, name = "X11Model")]
+    fn x11_model(&self) -> zbus::Result<String>;
+    #[zbus(property, name = "X11Variant")]
+    fn x11_variant(&self) -> zbus::Result<String>;
+    #[zbus(property, name = "X11Options")]
+    fn x11_options(&self) -> zbus::Result<String>;
+}
+
+/// The X11 keyboard configuration localed keeps, of which the view only changes the layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct X11Keyboard {
+    layout: String,
+    model: String,
+    variant: String,
+    options: String,
+}
+
+impl X11Keyboard {
+    /// The configuration with the layout and variant of `layout`, the model and options kept.
+    fn with_layout(self, layout: &KeyboardLayout) -> Self {
+        X11Keyboard { layout: layout.layout.clone(), variant: layout.variant.clone(), ..self }
+    }
+}
+
+/// systemd-localed. Debian's localed keeps `/etc/default/keyboard` as its X11 configuration,
+/// and converting sets the matching console keymap too. Changes go through `localectl` run as
+/// root when not root already.
+pub struct Localed {
+    connection: Connection,
+    privilege: Option<Box<dyn Privilege>>,
+}
+
+impl Localed {
+    fn proxy(&self) -> Result<LocaleProxyBlocking<'_>> {
+        Ok(LocaleProxyBlocking::builder(&self.connection).cache_properties(CacheProperties::No).build()?)
+    }
+
+    fn x11_keyboard(&self) -> Result<X11Keyboard> {
+        let locale = self.proxy()?;
+        Ok(X11Keyboard {
+            layout: locale.x11_layout().map_err(dbus_error)?,
+            model: locale.x11_model().map_err(dbus_error)?,
+            variant: locale.x11_variant().map_err(dbus_error)?,
+            options: locale.x11_options().map_err(dbus_error)?,
+        })
+    }
+}
+
+impl Keyboard for Localed {
+    fn layout(&self) -> Result<KeyboardLayout> {
+        let keyboard = self.x11_keyboard()?;
+        Ok(KeyboardLayout { layout: keyboard.layout, variant: keyboard.variant })
+    }
+
+    /// `SetX11Keyboard` replaces the model and options too, so they are passed on as they are.
+    fn set_layout(&self, layout: &KeyboardLayout) -> Result<()> {
+        let keyboard = self.x11_keyboard()?.with_layout(layout);
+        if let Some(privilege) = &self.privilege {
+            return set_x11_keymap(&keyboard, privilege.as_ref());
+        }
+        self.proxy()?
+            .set_x11_keyboard(&keyboard.layout, &keyboard.model, &keyboard.variant, &keyboard.options, true, false)
+            .map_err(dbus_error)
+    }
+}
+
+/// Set `keyboard` with `localectl`, which converts it to a console keymap as well.
+fn set_x11_keymap(keyboard: &X11Keyboard, privilege: &dyn Privilege) -> Result<()> {
+    let args = ["set-x11-keymap", &keyboard.layout, &keyboard.model, &keyboard.variant, &keyboard.options];
+    run_privileged(privilege, "localectl", &args, &Secret::default())
+}
+
+/// Without systemd: `XKBLAYOUT` and `XKBVARIANT` of `/etc/default/keyboard`, applied to the
+/// console with `setupcon`.
+pub struct DefaultKeyboard {
+    root: PathBuf,
+    privilege: Option<Box<dyn Privilege>>,
+}
+
+impl DefaultKeyboard {
+    pub fn new(root: PathBuf, privilege: Option<Box<dyn Privilege>>) -> Self {
+        DefaultKeyboard { root, privilege }
+    }
+}
+
+impl Keyboard for DefaultKeyboard {
+    fn layout(&self) -> Result<KeyboardLayout> {
+        let keyboard = fs::read_to_string(self.root.join("etc/default/keyboard")).unwrap_or_default();
+        Ok(KeyboardLayout {
+            layout: var(&keyboard, "XKBLAYOUT").unwrap_or_default(),
+            variant: var(&keyboard, "XKBVARIANT").unwrap_or_default(),
+        })
+    }
+
+    fn set_layout(&self, layout: &KeyboardLayout) -> Result<()> {
+        let path = self.root.join("etc/default/keyboard");
+        let keyboard = fs::read_to_string(&path).unwrap_or_default();
+        let keyboard = set_vars(&keyboard, &[("XKBLAYOUT", &layout.layout), ("XKBVARIANT", &layout.variant)]);
+        let privilege = require(self.privilege.as_deref(), "Changing the console")?;
+        write_as_root(privilege, &path, &keyboard)?;
+        run_privileged(privilege, "setupcon", &["--keyboard-only"], &Secret::default())
+    }
+}
+
+/// A console font of console-setup, e.g. `Terminus` in `16x32`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConsoleFont {
+    pub face: String,
+    /// `width x height`, or only the height for fonts 8 pixels wide.
+    pub size: String,
+}
+
+impl std::fmt::Display for ConsoleFont {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.face, self.size)
+    }
+}
+
+/// The font `/etc/default/console-setup` below `root` sets, if any.
+pub fn console_font(root: &Path) -> Option<ConsoleFont> {
+    let setup = fs::read_to_string(root.join("etc/default/console-setup")).ok()?;
+    Some(ConsoleFont { face: var(&setup, "FONTFACE")?, size: var(&setup, "FONTSIZE").unwrap_or_default() })
+}
+
+/// The faces and sizes console-setup can load, from the file names in `/usr/share/consolefonts`,
+/// e.g. `Uni2-Terminus32x16.psf.gz` is Terminus in 16x32.
+pub fn console_fonts(root: &Path) -> Result<Vec<ConsoleFont>> {
+    let mut fonts: Vec<ConsoleFont> = fs::read_dir(root.join(CONSOLE_FONTS))?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().to_string_lossy().to_string();
+            let (_, font) = name.strip_suffix(".psf.gz")?.split_once('-')?;
+            let digits = font.find(|c: char| c.is_ascii_digit())?;
+            let (face, size) = font.split_at(digits);
+            // File names give the height first, FONTSIZE the width.
+            let size = match size.split_once('x') {
+                Some((height, width)) => format!("{width}x{height}"),
+                None => size.to_string(),
+            };
+            Some(ConsoleFont { face: face.to_string(), size })
+        })
+        .collect();
+    fonts.sort();
+    fonts.dedup();
+    Ok(fonts)
+}
+
+/// Make `font` the console font and load it.
+pub fn set_console_font(root: &Path, font: &ConsoleFont, privilege: Option<&dyn Privilege>) -> Result<()> {
+    let path = root.join("etc/default/console-setup");
+    let setup = fs::read_to_string(&path).unwrap_or_default();
+    let setup = set_vars(&setup, &[("FONTFACE", &font.face), ("FONTSIZE", &font.size)]);
+    let privilege = require(privilege, "Changing the console")?;
+    write_as_root(privilege, &path, &setup)?;
+    run_privileged(privilege, "setupcon", &["--font-only"], &Secret::default())
+}
+
+/// The value of `key` in a file of shell variables like `/etc/default/keyboard`, comments skipped.
+fn var(content: &str, key: &str) -> Option<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim().trim_matches('"').to_string())
+}
+
+/// `content` with each of `vars` set, replacing the existing assignment or adding one.
+fn set_vars(content: &str, vars: &[(&str, &str)]) -> String {
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+    for (key, value) in vars {
+        let line = format!("{key}=\"{value}\"");
+        let prefix = format!("{key}=");
+        match lines.iter().position(|l| l.trim().starts_with(&prefix)) {
+            Some(i) => lines[i] = line,
+            None => lines.push(line),
+        }
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::privilege::mock::Recorder;
+
+    #[test]
+    fn test_layouts() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("usr/share/X11/xkb/rules")).unwrap();
+        fs::write(
+            root.path().join(BASE_LST),
+            "! model\n  pc105           Generic 105-key PC\n\n\
+             ! layout\n  us              English (US)\n  de              German\n\n\
+             ! variant\n  intl            us: English (US, intl., with dead keys)\n\
+             \x20 nodeadkeys      de: German (no dead keys)\n\n\
+             ! option\n  grp                  Switching to another layout\n",
+        )
+        .unwrap();
+        let layouts = layouts(root.path()).unwrap();
+        assert_eq!(layouts.layouts[1], Entry { name: String::from("de"), description: String::from("German") });
+        assert_eq!(layouts.layouts.len(), 2);
+        assert_eq!(layouts.variants["de"][0].description, "German (no dead keys)");
+    }
+
+    #[test]
+    fn test_keyboard_and_font() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("etc/default")).unwrap();
+        fs::create_dir_all(root.path().join(CONSOLE_FONTS)).unwrap();
+        let fonts = ["Uni2-Terminus32x16.psf.gz", "Lat15-Terminus32x16.psf.gz", "Lat15-Fixed16.psf.gz", "README.fonts"];
+        for font in fonts {
+            fs::write(root.path().join(CONSOLE_FONTS).join(font), "").unwrap();
+        }
+        fs::write(
+            root.path().join("etc/default/keyboard"),
+            "# KEYBOARD CONFIGURATION FILE\nXKBMODEL=\"pc105\"\nXKBLAYOUT=\"us\"\nXKBVARIANT=\"\"\n",
+        )
+        .unwrap();
+        let setup = "CODESET=\"guess\"\nFONTFACE=\"Fixed\"\nFONTSIZE=\"8x16\"\n";
+        fs::write(root.path().join("etc/default/console-setup"), setup).unwrap();
+
+        let fonts = console_fonts(root.path()).unwrap();
+        assert_eq!(fonts.iter().map(ToString::to_string).collect::<Vec<_>>(), ["Fixed 16", "Terminus 16x32"]);
+        let fixed = ConsoleFont { face: String::from("Fixed"), size: String::from("8x16") };
+        assert_eq!(console_font(root.path()), Some(fixed));
+
+        let recorder = Recorder::default();
+        let keyboard = DefaultKeyboard::new(root.path().to_path_buf(), Some(Box::new(recorder.clone())));
+        assert_eq!(keyboard.layout().unwrap(), KeyboardLayout { layout: String::from("us"), variant: String::new() });
+        let german = KeyboardLayout { layout: String::from("de"), variant: String::from("nodeadkeys") };
+        keyboard.set_layout(&german).unwrap();
+        set_console_font(root.path(), &fonts[1], Some(&recorder)).unwrap();
+        let etc = root.path().join("etc/default");
+        assert_eq!(
+            recorder.commands(),
+            [
+                format!("tee {}/keyboard", etc.display()),
+                String::from("setupcon --keyboard-only"),
+                format!("tee {}/console-setup", etc.display()),
+                String::from("setupcon --font-only"),
+            ]
+        );
+
+        assert_eq!(
+            set_vars("XKBMODEL=\"pc105\"\nXKBLAYOUT=\"us\"\n", &[("XKBLAYOUT", "de"), ("XKBVARIANT", "nodeadkeys")]),
+            "XKBMODEL=\"pc105\"\nXKBLAYOUT=\"de\"\nXKBVARIANT=\"nodeadkeys\"\n"
+        );
+        let commented = "#XKBLAYOUT=\"fr\"\n  # XKBLAYOUT=\"it\"\nXKBLAYOUTS=\"es\"\nXKBLAYOUT=\"us\"\n";
+        assert_eq!(var(commented, "XKBLAYOUT").as_deref(), Some("us"));
+    }
+
+    #[test]
+    fn test_x11_keyboard_keeps_model_and_options() {
+        let current = X11Keyboard {
+            layout: String::from("us"),
+            model: String::from("pc105"),
+            variant: String::new(),
+            options: String::from("ctrl:nocaps"),
+        };
+        let german = KeyboardLayout { layout: String::from("de"), variant: String::from("nodeadkeys") };
+        assert_eq!(
+            current.clone().with_layout(&german),
+            X11Keyboard {
+                layout: String::from("de"),
+                model: String::from("pc105"),
+                variant: String::from("nodeadkeys"),
+                options: String::from("ctrl:nocaps"),
+            }
+        );
+
+        let recorder = Recorder::default();
+        set_x11_keymap(&current.with_layout(&german), &recorder).unwrap();
+        assert_eq!(recorder.commands(), ["localectl set-x11-keymap de pc105 nodeadkeys ctrl:nocaps"]);
+    }
+}
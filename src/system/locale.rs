@@ -0,0 +1,218 @@
+//! The system locale: the default `LANG`, managed through `localectl`, and the locales generated
+//! from `/etc/locale.gen`.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::Result;
+
+use super::{booted_with_systemd, privilege::Privilege, run, run_privileged, secret::Secret, write_as_root};
+
+/// The `LANG` of the system locale, if one is set.
+pub fn locale() -> Result<Option<String>> {
+    Ok(parse_lang(&run("localectl", &["status"])?))
+}
+
+/// Locales that are generated and can be selected.
+pub fn available_locales() -> Result<Vec<String>> {
+    Ok(run("localectl", &["list-locales"])?.lines().map(String::from).collect())
+}
+
+/// Make `lang` the default `LANG`, through `localectl` run as root.
+pub fn set_locale(lang: &str, privilege: &dyn Privilege) -> Result<()> {
+    let available = available_locales()?;
+    if !available.is_empty() && !available.iter().any(|l| l == lang) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("Locale {lang} is not generated")).into());
+    }
+    run_privileged(privilege, "localectl", &["set-locale", &format!("LANG={lang}")], &Secret::default())
+}
+
+/// Find `LANG=...` in the output of `localectl status`.
+fn parse_lang(status: &str) -> Option<String> {
+    status
+        .split_whitespace()
+        .find_map(|word| word.strip_prefix("LANG="))
+        .map(String::from)
+}
+
+/// A locale glibc can generate, e.g. `de_DE.UTF-8` in `UTF-8`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Supported {
+    pub name: String,
+    pub charset: String,
+}
+
+/// Every locale listed in `/usr/share/i18n/SUPPORTED` below `root`.
+pub fn supported(root: &Path) -> Result<Vec<Supported>> {
+    Ok(fs::read_to_string(root.join("usr/share/i18n/SUPPORTED"))?
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, charset)| Supported { name: name.to_string(), charset: charset.trim().to_string() })
+        .collect())
+}
+
+/// The generated locales and the default one, as the running system manages them.
+pub trait Locales: Send {
+    /// The generated locales as `locale -a` names them, e.g. `de_DE.utf8`.
+    fn generated(&self) -> Result<Vec<String>>;
+
+    /// Make `lang` the default locale, as root through `privilege`.
+    fn set_locale(&self, lang: &str, privilege: &dyn Privilege) -> Result<()>;
+}
+
+/// Asks `locale` and localed.
+pub struct SystemLocales;
+
+impl Locales for SystemLocales {
+    fn generated(&self) -> Result<Vec<String>> {
+        Ok(run("locale", &["-a"])?.lines().map(String::from).collect())
+    }
+
+    fn set_locale(&self, lang: &str, privilege: &dyn Privilege) -> Result<()> {
+        set_locale(lang, privilege)
+    }
+}
+
+/// Whether `a` and `b` name the same locale, as `de_DE.UTF-8` and `de_DE.utf8` do.
+pub fn same_locale(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+/// glibc ignores case and punctuation in the codeset.
+fn normalize(name: &str) -> String {
+    let Some((language, rest)) = name.split_once('.') else { return name.to_string() };
+    let (codeset, modifier) = rest.split_once('@').map_or((rest, None), |(c, m)| (c, Some(m)));
+    let codeset: String = codeset.chars().filter(char::is_ascii_alphanumeric).collect();
+    let modifier = modifier.map(|m| format!("@{m}")).unwrap_or_default();
+    format!("{language}.{}{modifier}", codeset.to_lowercase())
+}
+
+/// `/etc/locale.gen`, which lists the locales `locale-gen` generates. Entries for every
+/// supported locale are usually there, commented out.
+#[derive(Debug, Clone)]
+pub struct LocaleGen {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl LocaleGen {
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join("etc/locale.gen");
+        let content = match fs::read_to_string(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            content => content?,
+        };
+        Ok(LocaleGen { path, lines: content.lines().map(String::from).collect() })
+    }
+
+    pub fn is_enabled(&self, locale: &Supported) -> bool {
+        let wanted = Some((locale.name.as_str(), locale.charset.as_str()));
+        self.lines.iter().any(|line| Self::entry(line) == wanted)
+    }
+
+    /// Uncomment the entry of `locale`, or add one if there is none.
+    pub fn enable(&mut self, locale: &Supported) {
+        if self.is_enabled(locale) {
+            return;
+        }
+        let wanted = Some((locale.name.as_str(), locale.charset.as_str()));
+        let commented = self
+            .lines
+            .iter()
+            .position(|line| line.starts_with('#') && Self::entry(line.trim_start_matches('#')) == wanted);
+        let entry = format!("{} {}", locale.name, locale.charset);
+        match commented {
+            Some(i) => self.lines[i] = entry,
+            None => self.lines.push(entry),
+        }
+    }
+
+    /// The name and charset of a `name charset` line.
+    fn entry(line: &str) -> Option<(&str, &str)> {
+        let mut words = line.split_whitespace();
+        let (name, charset) = (words.next()?, words.next()?);
+        (words.next().is_none() && !name.starts_with('#')).then_some((name, charset))
+    }
+
+    fn content(&self) -> String {
+        self.lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// Write the file as root and generate the locales it lists.
+    pub fn save_and_generate(&self, privilege: &dyn Privilege) -> Result<()> {
+        write_as_root(privilege, &self.path, &self.content())?;
+        run_privileged(privilege, "locale-gen", &[], &Secret::default())
+    }
+}
+
+/// The default `LANG` of the system below `root`.
+pub fn default_locale(root: &Path) -> Option<String> {
+    if booted_with_systemd(root) {
+        return locale().ok().flatten();
+    }
+    fs::read_to_string(root.join("etc/default/locale"))
+        .ok()?
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("LANG="))
+        .map(|lang| lang.trim_matches('"').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::privilege::mock::Recorder;
+
+    #[test]
+    fn test_parse_lang() {
+        let status = "   System Locale: LANG=en_GB.UTF-8\n                  LC_TIME=de_DE.UTF-8\n       VC Keymap: us\n";
+        assert_eq!(parse_lang(status).as_deref(), Some("en_GB.UTF-8"));
+        assert_eq!(parse_lang("   System Locale: n/a\n"), None);
+    }
+
+    #[test]
+    fn test_locale_gen() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("etc/default")).unwrap();
+        fs::create_dir_all(root.path().join("usr/share/i18n")).unwrap();
+        fs::write(
+            root.path().join("usr/share/i18n/SUPPORTED"),
+            "de_DE.UTF-8 UTF-8\nde_DE ISO-8859-1\nen_US.UTF-8 UTF-8\nca_ES.UTF-8@valencia UTF-8\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("etc/locale.gen"),
+            "# This file lists locales that you wish to have built.\n\n# de_DE ISO-8859-1\n# de_DE.UTF-8 UTF-8\nen_US.UTF-8 UTF-8\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("etc/default/locale"), "LANG=\"en_US.UTF-8\"\n").unwrap();
+
+        let supported = supported(root.path()).unwrap();
+        assert_eq!(supported.len(), 4);
+        assert_eq!(supported[1], Supported { name: String::from("de_DE"), charset: String::from("ISO-8859-1") });
+
+        let mut locale_gen = LocaleGen::load(root.path()).unwrap();
+        assert!(locale_gen.is_enabled(&supported[2]));
+        assert!(!locale_gen.is_enabled(&supported[0]));
+        locale_gen.enable(&supported[0]);
+        locale_gen.enable(&supported[3]);
+        locale_gen.enable(&supported[2]);
+        assert_eq!(
+            locale_gen.content(),
+            "# This file lists locales that you wish to have built.\n\n# de_DE ISO-8859-1\nde_DE.UTF-8 UTF-8\nen_US.UTF-8 UTF-8\nca_ES.UTF-8@valencia UTF-8\n"
+        );
+
+        let recorder = Recorder::default();
+        locale_gen.save_and_generate(&recorder).unwrap();
+        assert_eq!(default_locale(root.path()).as_deref(), Some("en_US.UTF-8"));
+        let path = root.path().join("etc/locale.gen");
+        assert_eq!(recorder.commands(), [format!("tee {}", path.display()), String::from("locale-gen ")]);
+
+        assert!(same_locale("de_DE.UTF-8", "de_DE.utf8"));
+        assert!(same_locale("ca_ES.UTF-8@valencia", "ca_ES.utf8@valencia"));
+        assert!(!same_locale("de_DE", "de_DE.utf8"));
+    }
+}
@@ -0,0 +1,335 @@
+//! Changing account passwords with `chpasswd`, or `passwd` for the user's own.
+
+use std::{fmt, fs, io, path::Path, process::Command};
+
+use chrono::{Local, NaiveDate};
+use serde::Deserialize;
+
+use super::{
+    privilege::{run_with_input, AuthenticationFailed, Privilege},
+    secret::Secret,
+};
+
+/// PAM's helper that checks a password without root rights, for the user running it.
+const UNIX_CHKPWD: &[&str] = &["/usr/sbin/unix_chkpwd", "/sbin/unix_chkpwd"];
+
+/// Why a password could not be changed.
+#[derive(Debug)]
+pub enum PasswordError {
+    /// The password given to authenticate the change was not accepted.
+    WrongPassword,
+    /// The new password was rejected as too weak.
+    WeakPassword(String),
+    /// PAM refused the change for another reason.
+    Pam(String),
+    /// Root is needed and there is no way to become root.
+    PermissionDenied,
+    /// Another program holds the lock on the password database.
+    Locked,
+    /// The user name or password cannot be passed to chpasswd.
+    Invalid(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::WrongPassword => f.write_str("The current password is wrong"),
+            PasswordError::WeakPassword(reason) => write!(f, "The new password is too weak: {reason}"),
+            PasswordError::Pam(message) => write!(f, "The password could not be changed: {message}"),
+            PasswordError::PermissionDenied => f.write_str("Changing a password must be run as root"),
+            PasswordError::Locked => f.write_str("The password database is in use by another program, try again later"),
+            PasswordError::Invalid(message) => f.write_str(message),
+            PasswordError::Io(e) => write!(f, "Unable to run chpasswd or passwd: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+impl From<io::Error> for PasswordError {
+    fn from(error: io::Error) -> Self {
+        if AuthenticationFailed::is(&error) {
+            PasswordError::WrongPassword
+        } else if error.kind() == io::ErrorKind::PermissionDenied {
+            PasswordError::PermissionDenied
+        } else {
+            PasswordError::Io(error)
+        }
+    }
+}
+
+/// Rules new passwords are checked against, set under `password_policy` in the config file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// How many of lowercase letters, uppercase letters, digits and symbols must appear.
+    pub min_classes: usize,
+    /// Refuse passwords that contain the user name.
+    pub reject_username: bool,
+    /// Passwords that are never accepted, such as the one images ship with. Compared ignoring case.
+    pub forbidden: Vec<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            min_classes: 3,
+            reject_username: true,
+            forbidden: vec![String::from("temppwd")],
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// The rules `password` breaks, empty when it is acceptable.
+    pub fn check(&self, user: &str, password: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        if password.chars().count() < self.min_length {
+            problems.push(format!("Use at least {} characters", self.min_length));
+        }
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|present| **present).count() < self.min_classes {
+            problems.push(format!(
+                "Mix at least {} of lowercase, uppercase, digits and symbols",
+                self.min_classes
+            ));
+        }
+        if self.reject_username && !user.is_empty() && password.to_lowercase().contains(&user.to_lowercase()) {
+            problems.push(String::from("Do not use the user name"));
+        }
+        if self.forbidden.iter().any(|f| f.eq_ignore_ascii_case(password)) {
+            problems.push(String::from("This is a well-known default password"));
+        }
+        problems
+    }
+}
+
+/// Why a password has to be replaced before the board is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StalePassword {
+    /// The account still has the password the image shipped with.
+    Default,
+    /// The password is past its maximum age, or was marked to be changed at the next login.
+    Expired,
+}
+
+impl StalePassword {
+    /// Check whether `user` has one of the `policy`'s forbidden passwords or an expired one.
+    /// Without root, only the user running beagle-config can be checked.
+    pub fn detect(user: &str, policy: &PasswordPolicy) -> Option<Self> {
+        if policy.forbidden.iter().any(|password| accepts(user, password) == Some(true)) {
+            return Some(StalePassword::Default);
+        }
+        let output = Command::new("chage").env("LC_ALL", "C").args(["-l", user]).output().ok()?;
+        let chage = String::from_utf8_lossy(&output.stdout);
+        (output.status.success() && is_expired(&chage, Local::now().date_naive())).then_some(StalePassword::Expired)
+    }
+
+    /// [`StalePassword::detect`] on the first boot, and on other starts until a check found the
+    /// password of `user` fine, which a marker file in `dir` remembers. Each forbidden password
+    /// tried leaves a failed login in the auth log.
+    pub fn detect_unless_checked(dir: &Path, user: &str, policy: &PasswordPolicy, first_boot: bool) -> Option<Self> {
+        let marker = dir.join(format!("password-checked-{user}"));
+        if !first_boot && marker.exists() {
+            return None;
+        }
+        let stale = Self::detect(user, policy);
+        if stale.is_none() {
+            let _ = fs::create_dir_all(dir).and_then(|_| fs::write(&marker, ""));
+        }
+        stale
+    }
+
+    /// What the mandatory password screen says about it.
+    pub fn reason(&self, user: &str) -> String {
+        match self {
+            StalePassword::Default => format!("{user} still has the default password, which everyone knows"),
+            StalePassword::Expired => format!("The password of {user} has expired"),
+        }
+    }
+}
+
+/// Ask PAM whether `password` is the password of `user`, `None` when it cannot be asked. Failed
+/// checks end up in the auth log.
+fn accepts(user: &str, password: &str) -> Option<bool> {
+    let helper = UNIX_CHKPWD.iter().find(|path| Path::new(path).exists())?;
+    let output = run_with_input(Command::new(helper).args([user, "nonull"]), &Secret::new(password)).ok()?;
+    Some(output.status.success())
+}
+
+/// Read the password aging shown by `chage -l` in the C locale.
+fn is_expired(chage: &str, today: NaiveDate) -> bool {
+    chage.lines().filter_map(|line| line.split_once(':')).any(|(field, value)| {
+        match (field.trim(), value.trim()) {
+            (_, "password must be changed") => true,
+            ("Password expires", date) => NaiveDate::parse_from_str(date, "%b %d, %Y").is_ok_and(|date| date <= today),
+            _ => false,
+        }
+    })
+}
+
+/// Set the password of `user`, becoming root through `privilege`, once it passes `policy`.
+pub fn change_password(
+    user: &str,
+    new_password: &Secret,
+    policy: &PasswordPolicy,
+    privilege: &dyn Privilege,
+) -> Result<(), PasswordError> {
+    check_new(user, new_password, policy)?;
+    let input = Secret::new(format!("{user}:{}\n", new_password.expose()));
+    let output = privilege.run("chpasswd", &[], &input)?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(classify(&stderr))
+}
+
+/// Set the password of `user`, who runs beagle-config, with `passwd`, which needs no root or sudo
+/// rights. `current` is checked with PAM first, so a typo is reported as such.
+pub fn change_own_password(
+    user: &str,
+    current: &Secret,
+    new_password: &Secret,
+    policy: &PasswordPolicy,
+) -> Result<(), PasswordError> {
+    check_new(user, new_password, policy)?;
+    if current.expose().contains('\n') || accepts(user, current.expose()) == Some(false) {
+        return Err(PasswordError::WrongPassword);
+    }
+    // passwd asks for the current password, then for the new one twice.
+    let input = Secret::new(format!("{}\n{}\n{}\n", current.expose(), new_password.expose(), new_password.expose()));
+    let output = run_with_input(Command::new("passwd").env("LC_ALL", "C"), &input)?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(classify(&stderr))
+}
+
+/// Refuse new passwords that fail `policy` or cannot be passed on a line of their own.
+fn check_new(user: &str, new_password: &Secret, policy: &PasswordPolicy) -> Result<(), PasswordError> {
+    if new_password.is_empty() {
+        return Err(PasswordError::WeakPassword(String::from("it is empty")));
+    }
+    let problems = policy.check(user, new_password.expose());
+    if !problems.is_empty() {
+        return Err(PasswordError::WeakPassword(problems.join("; ")));
+    }
+    // chpasswd reads `user:password` lines.
+    if user.is_empty() || user.contains([':', '\n']) {
+        return Err(PasswordError::Invalid(format!("Invalid user name `{user}`")));
+    }
+    if new_password.expose().contains('\n') {
+        return Err(PasswordError::Invalid(String::from("Passwords cannot contain line breaks")));
+    }
+    Ok(())
+}
+
+/// Tell a password that PAM's quality checks rejected from other failures.
+fn classify(stderr: &str) -> PasswordError {
+    if let Some((_, reason)) = stderr.split_once("BAD PASSWORD:") {
+        return PasswordError::WeakPassword(reason.lines().next().unwrap_or_default().trim().to_string());
+    }
+    if stderr.contains("cannot lock") {
+        return PasswordError::Locked;
+    }
+    let denied = ["Permission denied", "must be run as root", "may not"];
+    if denied.iter().any(|d| stderr.contains(d)) {
+        return PasswordError::PermissionDenied;
+    }
+    PasswordError::Pam(if stderr.is_empty() { String::from("chpasswd failed") } else { stderr.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Output;
+
+    use super::*;
+    use crate::system::privilege::mock::Recorder;
+
+    /// Runs nothing and answers like chpasswd with the given stderr.
+    struct Fake(&'static str);
+
+    impl Privilege for Fake {
+        fn run(&self, _: &str, _: &[&str], _: &Secret) -> io::Result<Output> {
+            std::process::Command::new("sh").args(["-c", &format!("echo '{}' >&2; exit 1", self.0)]).output()
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(policy.check("debian", "Correct-Horse-7"), Vec::<String>::new());
+        assert_eq!(policy.check("debian", "temppwd").len(), 3);
+        assert_eq!(policy.check("debian", "Debian-2024"), ["Do not use the user name"]);
+        assert_eq!(
+            policy.check("debian", "alllowercase"),
+            ["Mix at least 3 of lowercase, uppercase, digits and symbols"]
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let chage = |last, expires| {
+            format!("Last password change\t\t\t\t\t: {last}\nPassword expires\t\t\t\t\t: {expires}\nPassword inactive\t\t\t\t\t: never\n")
+        };
+        assert!(!is_expired(&chage("May 20, 2025", "never"), today));
+        assert!(!is_expired(&chage("May 20, 2025", "Jun 10, 2025"), today));
+        assert!(is_expired(&chage("Mar 01, 2025", "May 31, 2025"), today));
+        assert!(is_expired(&chage("password must be changed", "password must be changed"), today));
+    }
+
+    #[test]
+    fn test_errors() {
+        let password = Secret::new("Correct-Horse-7");
+        let policy = PasswordPolicy::default();
+        let change = |stderr| change_password("debian", &password, &policy, &Fake(stderr)).unwrap_err();
+        assert!(matches!(
+            change("BAD PASSWORD: The password is shorter than 8 characters"),
+            PasswordError::WeakPassword(reason) if reason == "The password is shorter than 8 characters"
+        ));
+        assert!(matches!(
+            change("chpasswd: (user debian) pam_chauthtok() failed, error:"),
+            PasswordError::Pam(_)
+        ));
+        assert!(matches!(change("chpasswd: cannot lock /etc/shadow; try again later."), PasswordError::Locked));
+        assert!(matches!(change("chpasswd: Permission denied."), PasswordError::PermissionDenied));
+        assert!(matches!(change_password("a:b", &password, &policy, &Fake("")), Err(PasswordError::Invalid(_))));
+        // Only a rejected password is a wrong password, not every refusal.
+        let wrong = PasswordError::from(AuthenticationFailed::error("Wrong password"));
+        assert!(matches!(wrong, PasswordError::WrongPassword));
+        let dismissed = io::Error::new(io::ErrorKind::PermissionDenied, "Authentication was dismissed");
+        assert!(matches!(PasswordError::from(dismissed), PasswordError::PermissionDenied));
+        let empty = change_password("debian", &Secret::default(), &policy, &Fake(""));
+        assert!(matches!(empty, Err(PasswordError::WeakPassword(_))));
+        // The policy applies to every caller, before chpasswd runs.
+        let recorder = Recorder::default();
+        let weak = change_password("debian", &Secret::new("temppwd"), &policy, &recorder);
+        assert!(matches!(weak, Err(PasswordError::WeakPassword(reason)) if reason.contains("well-known")));
+        assert!(recorder.commands().is_empty());
+        // So does it to the user's own password, checked before the current one.
+        let own = change_own_password("debian", &Secret::new("temppwd"), &Secret::new("temppwd"), &policy);
+        assert!(matches!(own, Err(PasswordError::WeakPassword(_))));
+        let typo = change_own_password("debian", &Secret::new("temp\npwd"), &password, &policy);
+        assert!(matches!(typo, Err(PasswordError::WrongPassword)));
+    }
+
+    #[test]
+    fn test_checked_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("data/password-checked-nobody-here");
+        let policy = PasswordPolicy::default();
+        assert_eq!(StalePassword::detect_unless_checked(&dir.path().join("data"), "nobody-here", &policy, false), None);
+        assert!(marker.exists());
+    }
+}
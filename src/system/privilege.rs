@@ -0,0 +1,224 @@
+//! Running commands as root: directly, through polkit's `pkexec` or through `sudo`.
+
+use std::{
+    error, fmt,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Output, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use super::secret::Secret;
+
+/// A way to run a command as root. Secrets only ever travel on stdin, never as arguments, so
+/// they do not show up in the process list.
+pub trait Privilege: Send {
+    /// Run `program` as root with `input` on its stdin. Failing to authenticate is reported
+    /// as [`io::ErrorKind::PermissionDenied`] wrapping [`AuthenticationFailed`].
+    fn run(&self, program: &str, args: &[&str], input: &Secret) -> io::Result<Output>;
+}
+
+/// The password or the polkit dialog was not accepted, as opposed to the command itself being
+/// refused.
+#[derive(Debug)]
+pub struct AuthenticationFailed(&'static str);
+
+impl fmt::Display for AuthenticationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl error::Error for AuthenticationFailed {}
+
+impl AuthenticationFailed {
+    pub(crate) fn error(message: &'static str) -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, AuthenticationFailed(message))
+    }
+
+    /// Whether `error` was returned because authentication failed.
+    pub fn is(error: &io::Error) -> bool {
+        error.get_ref().is_some_and(|inner| inner.is::<AuthenticationFailed>())
+    }
+}
+
+/// Already running as root.
+pub struct AsRoot;
+
+impl Privilege for AsRoot {
+    fn run(&self, program: &str, args: &[&str], input: &Secret) -> io::Result<Output> {
+        run_with_input(Command::new(program).args(args), input)
+    }
+}
+
+/// polkit, which asks through the desktop's or the terminal's authentication agent.
+pub struct Pkexec;
+
+impl Privilege for Pkexec {
+    fn run(&self, program: &str, args: &[&str], input: &Secret) -> io::Result<Output> {
+        let output = run_with_input(Command::new("pkexec").arg(program).args(args), input)?;
+        // pkexec exits with 126 when the dialog was dismissed and 127 when authentication failed.
+        match output.status.code() {
+            Some(126) => Err(io::Error::new(io::ErrorKind::PermissionDenied, "Authentication was dismissed")),
+            Some(127) => Err(AuthenticationFailed::error("Authentication failed")),
+            _ => Ok(output),
+        }
+    }
+}
+
+/// sudo, authenticated with the user's own password.
+pub struct Sudo {
+    password: Secret,
+}
+
+impl Sudo {
+    pub fn new(password: Secret) -> Self {
+        Sudo { password }
+    }
+}
+
+impl Privilege for Sudo {
+    fn run(&self, program: &str, args: &[&str], input: &Secret) -> io::Result<Output> {
+        // -k ignores cached credentials, so sudo always consumes the password line itself and
+        // never passes it on to the command. Checking it on its own first keeps a wrong
+        // password apart from a failure of the command.
+        let password = Secret::new(format!("{}\n", self.password.expose()));
+        let check = run_with_input(Command::new("sudo").args(["-k", "-S", "-p", "", "true"]), &password)?;
+        if !check.status.success() {
+            return Err(AuthenticationFailed::error("Wrong password"));
+        }
+
+        let input = Secret::new(format!("{}{}", password.expose(), input.expose()));
+        run_with_input(Command::new("sudo").args(["-k", "-S", "-p", "", program]).args(args), &input)
+    }
+}
+
+/// sudo with the password the interactive interface asked for, shared by all its views. Until
+/// it is given, commands fail without running; a password sudo stops accepting is forgotten, so
+/// it is asked for again.
+#[derive(Clone, Default)]
+pub struct SudoSession(Arc<Mutex<Option<Secret>>>);
+
+impl SudoSession {
+    pub fn is_authenticated(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    /// Check `password` with sudo and keep it for the commands to come.
+    pub fn authenticate(&self, password: Secret) -> io::Result<()> {
+        Sudo::new(password.clone()).run("true", &[], &Secret::default())?;
+        *self.0.lock().unwrap() = Some(password);
+        Ok(())
+    }
+}
+
+impl Privilege for SudoSession {
+    fn run(&self, program: &str, args: &[&str], input: &Secret) -> io::Result<Output> {
+        let Some(password) = self.0.lock().unwrap().clone() else {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Not authenticated, open the view again to enter your password",
+            ));
+        };
+        let result = Sudo::new(password).run(program, args, input);
+        if result.as_ref().is_err_and(AuthenticationFailed::is) {
+            *self.0.lock().unwrap() = None;
+        }
+        result
+    }
+}
+
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// The best way to become root: directly when already root, through sudo when the user gave
+/// their password, else through polkit if it is installed.
+pub fn detect(sudo_password: Option<Secret>) -> Option<Box<dyn Privilege>> {
+    if is_root() {
+        Some(Box::new(AsRoot))
+    } else if let Some(password) = sudo_password {
+        Some(Box::new(Sudo::new(password)))
+    } else if Path::new("/usr/bin/pkexec").exists() {
+        Some(Box::new(Pkexec))
+    } else {
+        None
+    }
+}
+
+/// How the interactive interface becomes root: directly when already root, else through sudo
+/// with the password `session` asked for. Never through pkexec, whose terminal agent would
+/// draw over the interface and read the keys meant for it.
+pub fn detect_interactive(session: &SudoSession) -> Option<Box<dyn Privilege>> {
+    if is_root() {
+        Some(Box::new(AsRoot))
+    } else {
+        Some(Box::new(session.clone()))
+    }
+}
+
+pub(super) fn run_with_input(command: &mut Command, input: &Secret) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "Unable to open stdin"))?;
+    // A program that exits early closes its stdin; its exit status tells what went wrong.
+    let written = stdin.write_all(input.expose().as_bytes());
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    match written {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(output),
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Records the commands it is asked to run instead of running them.
+    #[derive(Default, Clone)]
+    pub struct Recorder(pub Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        pub fn commands(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Privilege for Recorder {
+        fn run(&self, program: &str, args: &[&str], _: &Secret) -> io::Result<Output> {
+            self.0.lock().unwrap().push(format!("{program} {}", args.join(" ")));
+            Command::new("true").output()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_goes_to_stdin() {
+        let output = AsRoot.run("cat", &[], &Secret::new("user:secret\n")).unwrap();
+        assert_eq!(output.stdout, b"user:secret\n");
+        assert_eq!(format!("{:?}", Secret::new("secret")), "Secret(********)");
+    }
+
+    #[test]
+    fn test_sudo_session_needs_a_password() {
+        let session = SudoSession::default();
+        assert!(!session.is_authenticated());
+        let error = session.run("true", &[], &Secret::default()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!AuthenticationFailed::is(&error));
+    }
+}
@@ -0,0 +1,77 @@
+//! Passwords and passphrases, kept out of logs and debug output.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A password or passphrase. `Debug` and `Display` never show it, and its buffer is zeroed when
+/// it is dropped. That is best effort: copies left behind when `push` grows the buffer, or made
+/// with `clone` or `format!`, are not wiped.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Secret(secret.into())
+    }
+
+    /// The secret itself, only to be handed to the program that needs it.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Length in characters, e.g. to draw a masked input field.
+    pub fn len(&self) -> usize {
+        self.0.chars().count()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.0.push(c);
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        self.0.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.wipe();
+        self.0.clear();
+    }
+
+    fn wipe(&mut self) {
+        // SAFETY: zero bytes are valid UTF-8, so the string stays well formed.
+        for byte in unsafe { self.0.as_bytes_mut() } {
+            // Volatile, so the writes are not optimised away right before the memory is freed.
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Secret(secret)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(********)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("********")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
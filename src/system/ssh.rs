@@ -0,0 +1,71 @@
+//! The OpenSSH server, managed through systemd.
+
+use color_eyre::Result;
+use serde::Serialize;
+
+use super::systemd::Systemd;
+
+/// Unit names used by the distributions we ship on: Debian calls it `ssh`, others `sshd`.
+const UNITS: &[&str] = &["ssh.service", "sshd.service"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SshStatus {
+    pub active: bool,
+    /// Started at boot.
+    pub enabled: bool,
+}
+
+/// Name of the installed SSH server unit.
+pub fn unit(systemd: &dyn Systemd) -> &'static str {
+    UNITS
+        .iter()
+        .copied()
+        .find(|unit| systemd.unit_state(unit).is_ok_and(|state| state.is_loaded()))
+        .unwrap_or(UNITS[0])
+}
+
+pub fn status(systemd: &dyn Systemd) -> Result<SshStatus> {
+    let state = systemd.unit_state(unit(systemd))?;
+    Ok(SshStatus { active: state.is_active(), enabled: state.is_enabled() })
+}
+
+/// Start the server now and at every boot.
+pub fn enable(systemd: &dyn Systemd) -> Result<()> {
+    let unit = unit(systemd);
+    systemd.enable(unit)?;
+    systemd.start(unit)
+}
+
+/// Stop the server and keep it from starting at boot.
+pub fn disable(systemd: &dyn Systemd) -> Result<()> {
+    let unit = unit(systemd);
+    systemd.disable(unit)?;
+    systemd.stop(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::systemd::mock::MockSystemd;
+
+    #[test]
+    fn test_enable_and_disable() {
+        let systemd = MockSystemd::new(&["sshd.service"]);
+        assert_eq!(unit(&systemd), "sshd.service");
+        assert_eq!(status(&systemd).unwrap(), SshStatus { active: false, enabled: false });
+
+        enable(&systemd).unwrap();
+        assert_eq!(status(&systemd).unwrap(), SshStatus { active: true, enabled: true });
+        disable(&systemd).unwrap();
+        assert_eq!(status(&systemd).unwrap(), SshStatus { active: false, enabled: false });
+    }
+
+    #[test]
+    fn test_missing_server() {
+        let systemd = MockSystemd::new(&[]);
+        assert_eq!(unit(&systemd), "ssh.service");
+        assert!(enable(&systemd).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! The OpenSSH server configuration, `/etc/ssh/sshd_config`.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use super::{privilege::Privilege, run_privileged, secret::Secret, ssh, systemd::Systemd, write_as_root};
+
+/// Location of the configuration, relative to the root directory.
+const SSHD_CONFIG: &str = "etc/ssh/sshd_config";
+const SSHD: &str = "/usr/sbin/sshd";
+
+/// Keywords the settings are read from and written to.
+const KEYWORDS: &[&str] = &["PasswordAuthentication", "PermitRootLogin", "Port", "AllowUsers"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermitRootLogin {
+    Yes,
+    /// Only with a key, the default.
+    ProhibitPassword,
+    ForcedCommandsOnly,
+    No,
+}
+
+impl PermitRootLogin {
+    const ALL: [PermitRootLogin; 4] = [Self::No, Self::ProhibitPassword, Self::ForcedCommandsOnly, Self::Yes];
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "yes" => Some(Self::Yes),
+            // `without-password` is the old name of `prohibit-password`.
+            "prohibit-password" | "without-password" => Some(Self::ProhibitPassword),
+            "forced-commands-only" => Some(Self::ForcedCommandsOnly),
+            "no" => Some(Self::No),
+            _ => None,
+        }
+    }
+
+    /// The next value, from most to least restrictive, wrapping around.
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|v| *v == self).unwrap_or_default();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        let i = Self::ALL.iter().position(|v| *v == self).unwrap_or_default();
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for PermitRootLogin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Yes => "yes",
+            Self::ProhibitPassword => "prohibit-password",
+            Self::ForcedCommandsOnly => "forced-commands-only",
+            Self::No => "no",
+        })
+    }
+}
+
+/// The settings the SSH Security view edits, with sshd's defaults for missing keywords.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshdSettings {
+    pub password_authentication: bool,
+    pub permit_root_login: PermitRootLogin,
+    pub port: u16,
+    /// Empty to let every account log in.
+    pub allow_users: Vec<String>,
+}
+
+impl SshdSettings {
+    /// Why these settings would lock `user` out, if they would.
+    pub fn lockout(&self, user: &str, has_keys: bool) -> Option<String> {
+        if !self.password_authentication && !has_keys {
+            return Some(format!("{user} has no authorized keys, add one before turning off password login"));
+        }
+        let allowed = self.allow_users.iter().any(|pattern| {
+            let name = pattern.split('@').next().unwrap_or(pattern);
+            name == user || name.contains(['*', '?'])
+        });
+        if !self.allow_users.is_empty() && !allowed {
+            return Some(format!("AllowUsers must include {user}, or they could no longer log in"));
+        }
+        None
+    }
+}
+
+/// Split a configuration line into keyword and value, which may be separated by `=`.
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let value = line[end..].trim_start();
+    Some((&line[..end], value.strip_prefix('=').unwrap_or(value).trim()))
+}
+
+fn is_keyword(line: &str, keyword: &str) -> bool {
+    directive(line).is_some_and(|(k, _)| k.eq_ignore_ascii_case(keyword))
+}
+
+/// Lines before the first `Match` block apply to every connection.
+fn global_lines<'a, 'b>(lines: &'b [&'a str]) -> &'b [&'a str] {
+    let end = lines.iter().position(|l| is_keyword(l, "Match")).unwrap_or(lines.len());
+    &lines[..end]
+}
+
+#[derive(Debug, Clone)]
+pub struct SshdConfig {
+    pub path: PathBuf,
+    root: PathBuf,
+    content: String,
+    /// The server binary used to validate changes.
+    sshd: PathBuf,
+}
+
+impl SshdConfig {
+    /// Load the configuration found under `root`.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(SSHD_CONFIG);
+        let content =
+            fs::read_to_string(&path).map_err(|e| eyre!("Unable to read {}: {e}", path.display()))?;
+        Ok(SshdConfig { path, root: root.to_path_buf(), content, sshd: PathBuf::from(SSHD) })
+    }
+
+    /// Validate with another server binary.
+    #[cfg(test)]
+    pub fn with_sshd(mut self, sshd: impl Into<PathBuf>) -> Self {
+        self.sshd = sshd.into();
+        self
+    }
+
+    /// Values of `keyword` in the global section; sshd uses the first one.
+    fn values(&self, keyword: &str) -> Vec<&str> {
+        let lines: Vec<&str> = self.content.lines().collect();
+        global_lines(&lines)
+            .iter()
+            .copied()
+            .filter_map(directive)
+            .filter(|(k, _)| k.eq_ignore_ascii_case(keyword))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    pub fn settings(&self) -> SshdSettings {
+        let first = |keyword| self.values(keyword).first().copied();
+        SshdSettings {
+            password_authentication: first("PasswordAuthentication").is_none_or(|v| v.eq_ignore_ascii_case("yes")),
+            permit_root_login: first("PermitRootLogin")
+                .and_then(PermitRootLogin::parse)
+                .unwrap_or(PermitRootLogin::ProhibitPassword),
+            port: first("Port").and_then(|v| v.parse().ok()).unwrap_or(22),
+            allow_users: self
+                .values("AllowUsers")
+                .iter()
+                .flat_map(|v| v.split_whitespace())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    /// The file with `settings` applied. Only keywords whose value changes are touched, and
+    /// comments and `Match` blocks are kept.
+    pub fn render(&self, settings: &SshdSettings) -> String {
+        let current = self.settings();
+        let mut lines: Vec<String> = self.content.lines().map(String::from).collect();
+        if current.password_authentication != settings.password_authentication {
+            let value = if settings.password_authentication { "yes" } else { "no" };
+            set(&mut lines, "PasswordAuthentication", Some(value));
+        }
+        if current.permit_root_login != settings.permit_root_login {
+            set(&mut lines, "PermitRootLogin", Some(&settings.permit_root_login.to_string()));
+        }
+        if current.port != settings.port {
+            set(&mut lines, "Port", Some(&settings.port.to_string()));
+        }
+        if current.allow_users != settings.allow_users {
+            let users = settings.allow_users.join(" ");
+            set(&mut lines, "AllowUsers", (!users.is_empty()).then_some(users.as_str()));
+        }
+        let mut content = lines.join("\n");
+        content.push('\n');
+        content
+    }
+
+    pub fn is_modified(&self, settings: &SshdSettings) -> bool {
+        self.render(settings) != self.content
+    }
+
+    /// Managed keywords that a file pulled in with `Include` sets as well, with that file.
+    /// sshd keeps the first value it reads, so these usually win over `sshd_config`.
+    pub fn overrides(&self) -> Vec<(String, PathBuf)> {
+        let dir = self.root.join("etc/ssh");
+        let included: Vec<PathBuf> = self
+            .values("Include")
+            .iter()
+            .flat_map(|v| v.split_whitespace())
+            .flat_map(|pattern| match pattern.strip_prefix('/') {
+                Some(absolute) => expand(&self.root.join(absolute)),
+                None => expand(&dir.join(pattern)),
+            })
+            .collect();
+
+        let mut overrides = Vec::new();
+        for file in included {
+            let Ok(content) = fs::read_to_string(&file) else { continue };
+            let lines: Vec<&str> = content.lines().collect();
+            for keyword in KEYWORDS {
+                if global_lines(&lines).iter().any(|l| is_keyword(l, keyword)) {
+                    overrides.push((keyword.to_string(), file.clone()));
+                }
+            }
+        }
+        overrides
+    }
+
+    /// Write `settings` as root and reload the server. The new file is checked with `sshd -t`
+    /// first and left untouched if sshd rejects it; if the reload fails the previous version is
+    /// restored. Returns the path of the backup copy.
+    pub fn save(
+        &mut self,
+        settings: &SshdSettings,
+        systemd: &dyn Systemd,
+        privilege: &dyn Privilege,
+    ) -> Result<PathBuf> {
+        let content = self.render(settings);
+        let run = |program: &str, args: &[&str]| run_privileged(privilege, program, args, &Secret::default());
+        let temp_path = self.path.with_extension("beagle-config");
+        let temp = temp_path.to_string_lossy();
+        write_as_root(privilege, &temp_path, &content).map_err(|e| eyre!("Unable to write {temp}: {e}"))?;
+        // sshd reads the host keys while checking, which only root can.
+        if let Err(e) = run(&self.sshd.to_string_lossy(), &["-t", "-f", &temp]) {
+            let _ = run("rm", &["-f", &temp]);
+            return Err(eyre!("The new configuration was rejected, nothing was changed. {e}"));
+        }
+
+        let path = self.path.to_string_lossy();
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        let replaced = run("chmod", &["--reference", &path, &temp])
+            .and_then(|_| run("cp", &["-p", &path, &backup.to_string_lossy()]))
+            .and_then(|_| run("mv", &[&temp, &path]));
+        if let Err(e) = replaced {
+            let _ = run("rm", &["-f", &temp]);
+            return Err(eyre!("Unable to write {path}: {e}"));
+        }
+
+        let unit = ssh::unit(systemd);
+        if systemd.unit_state(unit).is_ok_and(|state| state.is_active()) {
+            if let Err(e) = systemd.reload(unit) {
+                run("cp", &["-p", &backup.to_string_lossy(), &path])?;
+                let _ = systemd.reload(unit);
+                return Err(eyre!("Reloading {unit} failed, the previous configuration was restored: {e}"));
+            }
+        }
+        self.content = content;
+        Ok(backup)
+    }
+}
+
+/// Set `keyword` to `value` in the global section, or remove it for `None`. The first line
+/// is replaced and repeated ones are dropped; a new line goes below the commented-out default
+/// if there is one, else before the first `Match` block.
+fn set(lines: &mut Vec<String>, keyword: &str, value: Option<&str>) {
+    let borrowed: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let global_end = global_lines(&borrowed).len();
+    let existing: Vec<usize> = (0..global_end).filter(|&i| is_keyword(&lines[i], keyword)).collect();
+    let commented = (0..global_end).find(|&i| {
+        lines[i].trim().strip_prefix('#').is_some_and(|l| is_keyword(l, keyword))
+    });
+
+    let line = value.map(|v| format!("{keyword} {v}"));
+    let keep = match (existing.first(), line) {
+        (Some(&first), Some(line)) => {
+            lines[first] = line;
+            Some(first)
+        }
+        (None, Some(line)) => {
+            lines.insert(commented.map_or(global_end, |i| i + 1), line);
+            None
+        }
+        (_, None) => None,
+    };
+    for &i in existing.iter().rev() {
+        if Some(i) != keep {
+            lines.remove(i);
+        }
+    }
+}
+
+/// Files matching `pattern`, which may have one `*` in its file name.
+fn expand(pattern: &Path) -> Vec<PathBuf> {
+    let name = pattern.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let Some((prefix, suffix)) = name.split_once('*') else {
+        return vec![pattern.to_path_buf()];
+    };
+    let Some(Ok(entries)) = pattern.parent().map(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|n| n.len() >= prefix.len() + suffix.len() && n.starts_with(prefix) && n.ends_with(suffix))
+        })
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::{privilege::AsRoot, systemd::mock::MockSystemd};
+
+    const CONFIG: &str = "\
+Include /etc/ssh/sshd_config.d/*.conf
+#Port 22
+#PermitRootLogin prohibit-password
+PasswordAuthentication yes
+AllowUsers debian
+AllowUsers admin
+
+Match User backup
+\tPasswordAuthentication no
+";
+
+    fn config(root: &Path, content: &str) -> SshdConfig {
+        fs::create_dir_all(root.join("etc/ssh/sshd_config.d")).unwrap();
+        fs::write(root.join(SSHD_CONFIG), content).unwrap();
+        SshdConfig::load(root).unwrap().with_sshd("true")
+    }
+
+    #[test]
+    fn test_settings_and_render() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path(), CONFIG);
+        let mut settings = config.settings();
+        assert_eq!(
+            settings,
+            SshdSettings {
+                password_authentication: true,
+                permit_root_login: PermitRootLogin::ProhibitPassword,
+                port: 22,
+                allow_users: vec![String::from("debian"), String::from("admin")],
+            }
+        );
+        assert!(!config.is_modified(&settings));
+
+        settings.password_authentication = false;
+        settings.permit_root_login = PermitRootLogin::No;
+        settings.port = 2222;
+        settings.allow_users = vec![String::from("debian")];
+        assert_eq!(
+            config.render(&settings),
+            "\
+Include /etc/ssh/sshd_config.d/*.conf
+#Port 22
+Port 2222
+#PermitRootLogin prohibit-password
+PermitRootLogin no
+PasswordAuthentication no
+AllowUsers debian
+
+Match User backup
+\tPasswordAuthentication no
+"
+        );
+    }
+
+    #[test]
+    fn test_save_and_reject() {
+        let root = tempfile::tempdir().unwrap();
+        let systemd = MockSystemd::new(&["ssh.service"]);
+        systemd.start("ssh.service").unwrap();
+        let mut config = config(root.path(), CONFIG);
+        let mut settings = config.settings();
+        settings.password_authentication = false;
+
+        config.save(&settings, &systemd, &AsRoot).unwrap();
+        assert!(fs::read_to_string(&config.path).unwrap().contains("PasswordAuthentication no\nAllowUsers"));
+        assert_eq!(fs::read_to_string(root.path().join("etc/ssh/sshd_config.bak")).unwrap(), CONFIG);
+
+        let mut config = config.with_sshd("false");
+        settings.port = 2222;
+        assert!(config.save(&settings, &systemd, &AsRoot).is_err());
+        assert!(!fs::read_to_string(&config.path).unwrap().contains("Port 2222"));
+        assert!(!config.path.with_extension("beagle-config").exists());
+    }
+
+    #[test]
+    fn test_overrides_and_lockout() {
+        let root = tempfile::tempdir().unwrap();
+        let config = config(root.path(), CONFIG);
+        let drop_in = root.path().join("etc/ssh/sshd_config.d/50-cloud-init.conf");
+        fs::write(&drop_in, "PasswordAuthentication yes\n").unwrap();
+        assert_eq!(config.overrides(), vec![(String::from("PasswordAuthentication"), drop_in)]);
+
+        let mut settings = config.settings();
+        assert_eq!(settings.lockout("debian", false), None);
+        assert!(settings.lockout("beagle", true).is_some());
+        settings.password_authentication = false;
+        assert!(settings.lockout("debian", false).is_some());
+        assert_eq!(settings.lockout("debian", true), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Units managed by systemd, over its D-Bus API.
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use zbus::{blocking::Connection, proxy, zvariant::OwnedObjectPath, CacheProperties};
+
+use super::{
+    privilege::{is_root, Privilege},
+    run_privileged,
+    secret::Secret,
+};
+
+#[cfg(test)]
+pub mod mock;
+
+/// State of a unit as systemd reports it, e.g. `loaded`, `active` and `enabled`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnitState {
+    pub load_state: String,
+    pub active_state: String,
+    /// Empty when the unit has no unit file.
+    pub unit_file_state: String,
+}
+
+impl UnitState {
+    pub fn is_loaded(&self) -> bool {
+        self.load_state == "loaded"
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active_state == "active"
+    }
+
+    /// Started at boot.
+    pub fn is_enabled(&self) -> bool {
+        self.unit_file_state == "enabled"
+    }
+}
+
+/// The parts of the systemd manager we use, so tests can run against a fake one.
+pub trait Systemd: Send {
+    fn unit_state(&self, unit: &str) -> Result<UnitState>;
+    fn start(&self, unit: &str) -> Result<()>;
+    fn stop(&self, unit: &str) -> Result<()>;
+    fn restart(&self, unit: &str) -> Result<()>;
+    /// Ask a running unit to reload its configuration.
+    fn reload(&self, unit: &str) -> Result<()>;
+    /// Start the unit at boot.
+    fn enable(&self, unit: &str) -> Result<()>;
+    fn disable(&self, unit: &str) -> Result<()>;
+}
+
+/// Changes reported when enabling or disabling unit files: type, file and symlink target.
+type UnitFileChanges = Vec<(String, String, String)>;
+
+#[proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+    fn start_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn stop_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn restart_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn reload_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn enable_unit_files(
+        &self,
+        files: &[&str],
+        runtime: bool,
+        force: bool,
+    ) -> zbus::Result<(bool, UnitFileChanges)>;
+    fn disable_unit_files(&self, files: &[&str], runtime: bool) -> zbus::Result<UnitFileChanges>;
+    fn reload(&self) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "org.freedesktop.systemd1.Unit", default_service = "org.freedesktop.systemd1")]
+trait Unit {
+    #[zbus(property)]
+    fn load_state(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn unit_file_state(&self) -> zbus::Result<String>;
+}
+
+/// systemd on the system bus. The connection is opened on first use, so creating this never
+/// fails and a missing bus shows up as an error of the first call instead.
+#[derive(Debug, Clone, Default)]
+pub struct SystemBus {
+    connection: Arc<Mutex<Option<Connection>>>,
+}
+
+impl SystemBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn manager(&self) -> Result<ManagerProxyBlocking<'static>> {
+        let mut connection = self.connection.lock().map_err(|e| eyre!("{e}"))?;
+        let connection = match connection.as_ref() {
+            Some(connection) => connection.clone(),
+            None => {
+                let system = Connection::system().map_err(|e| {
+                    io::Error::new(io::ErrorKind::NotConnected, format!("Unable to reach systemd: {e}"))
+                })?;
+                connection.insert(system).clone()
+            }
+        };
+        Ok(ManagerProxyBlocking::new(&connection)?)
+    }
+}
+
+impl Systemd for SystemBus {
+    fn unit_state(&self, unit: &str) -> Result<UnitState> {
+        let manager = self.manager()?;
+        let path = manager.load_unit(unit).map_err(dbus_error)?;
+        let unit = UnitProxyBlocking::builder(manager.inner().connection())
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()?;
+        Ok(UnitState {
+            load_state: unit.load_state().map_err(dbus_error)?,
+            active_state: unit.active_state().map_err(dbus_error)?,
+            unit_file_state: unit.unit_file_state().map_err(dbus_error)?,
+        })
+    }
+
+    fn start(&self, unit: &str) -> Result<()> {
+        self.manager()?.start_unit(unit, "replace").map_err(dbus_error)?;
+        Ok(())
+    }
+
+    fn stop(&self, unit: &str) -> Result<()> {
+        self.manager()?.stop_unit(unit, "replace").map_err(dbus_error)?;
+        Ok(())
+    }
+
+    fn restart(&self, unit: &str) -> Result<()> {
+        self.manager()?.restart_unit(unit, "replace").map_err(dbus_error)?;
+        Ok(())
+    }
+
+    fn reload(&self, unit: &str) -> Result<()> {
+        self.manager()?.reload_unit(unit, "replace").map_err(dbus_error)?;
+        Ok(())
+    }
+
+    fn enable(&self, unit: &str) -> Result<()> {
+        let manager = self.manager()?;
+        manager.enable_unit_files(&[unit], false, false).map_err(dbus_error)?;
+        manager.reload().map_err(dbus_error)
+    }
+
+    fn disable(&self, unit: &str) -> Result<()> {
+        let manager = self.manager()?;
+        manager.disable_unit_files(&[unit], false).map_err(dbus_error)?;
+        manager.reload().map_err(dbus_error)
+    }
+}
+
+/// systemd on the system bus, changing units through `systemctl` run as root when not root
+/// already, as systemd only takes changes from root over D-Bus without a polkit agent.
+pub fn detect(privilege: Option<Box<dyn Privilege>>) -> Box<dyn Systemd> {
+    match privilege.filter(|_| !is_root()) {
+        Some(privilege) => Box::new(Systemctl { bus: SystemBus::new(), privilege }),
+        None => Box::new(SystemBus::new()),
+    }
+}
+
+/// Reads units over D-Bus and changes them with `systemctl` through a privilege.
+pub struct Systemctl {
+    bus: SystemBus,
+    privilege: Box<dyn Privilege>,
+}
+
+impl Systemctl {
+    fn run(&self, args: &[&str]) -> Result<()> {
+        run_privileged(self.privilege.as_ref(), "systemctl", args, &Secret::default())
+    }
+}
+
+impl Systemd for Systemctl {
+    fn unit_state(&self, unit: &str) -> Result<UnitState> {
+        self.bus.unit_state(unit)
+    }
+
+    fn start(&self, unit: &str) -> Result<()> {
+        self.run(&["start", unit])
+    }
+
+    fn stop(&self, unit: &str) -> Result<()> {
+        self.run(&["stop", unit])
+    }
+
+    fn restart(&self, unit: &str) -> Result<()> {
+        self.run(&["restart", unit])
+    }
+
+    fn reload(&self, unit: &str) -> Result<()> {
+        self.run(&["reload", unit])
+    }
+
+    /// Unlike `EnableUnitFiles`, `systemctl enable` also reloads the manager.
+    fn enable(&self, unit: &str) -> Result<()> {
+        self.run(&["enable", unit])
+    }
+
+    fn disable(&self, unit: &str) -> Result<()> {
+        self.run(&["disable", unit])
+    }
+}
+
+/// Keep refused and unknown requests recognisable, like [`super::command_error`] does.
+pub(super) fn dbus_error(error: zbus::Error) -> Report {
+    let kind = match &error {
+        zbus::Error::MethodError(name, _, _) => match name.as_str() {
+            "org.freedesktop.DBus.Error.AccessDenied"
+            | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" => {
+                Some(io::ErrorKind::PermissionDenied)
+            }
+            "org.freedesktop.systemd1.NoSuchUnit" => Some(io::ErrorKind::NotFound),
+            _ => None,
+        },
+        _ => None,
+    };
+    match kind {
+        Some(kind) => io::Error::new(kind, error.to_string()).into(),
+        None => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::privilege::mock::Recorder;
+
+    #[test]
+    fn test_systemctl() {
+        let recorder = Recorder::default();
+        let systemctl = Systemctl { bus: SystemBus::new(), privilege: Box::new(recorder.clone()) };
+        systemctl.enable("ssh.service").unwrap();
+        systemctl.start("ssh.service").unwrap();
+        systemctl.restart("ssh.service").unwrap();
+        assert_eq!(
+            recorder.commands(),
+            ["systemctl enable ssh.service", "systemctl start ssh.service", "systemctl restart ssh.service"]
+        );
+    }
+}
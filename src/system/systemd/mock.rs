@@ -0,0 +1,84 @@
+//! In-memory systemd for tests.
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use super::{Systemd, UnitState};
+
+/// Knows the units it was given; clones share their state, so a test can keep one to inspect
+/// what a view did with another.
+#[derive(Debug, Clone, Default)]
+pub struct MockSystemd {
+    units: Arc<Mutex<HashMap<String, UnitState>>>,
+}
+
+impl MockSystemd {
+    /// A systemd with the stopped and disabled `units` installed.
+    pub fn new(units: &[&str]) -> Self {
+        let units = units
+            .iter()
+            .map(|unit| {
+                let state = UnitState {
+                    load_state: String::from("loaded"),
+                    active_state: String::from("inactive"),
+                    unit_file_state: String::from("disabled"),
+                };
+                (unit.to_string(), state)
+            })
+            .collect();
+        MockSystemd { units: Arc::new(Mutex::new(units)) }
+    }
+
+    fn update(&self, unit: &str, f: impl FnOnce(&mut UnitState)) -> Result<()> {
+        let mut units = self.units.lock().unwrap();
+        let state = units
+            .get_mut(unit)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unit {unit} not found")))?;
+        f(state);
+        Ok(())
+    }
+}
+
+impl Systemd for MockSystemd {
+    fn unit_state(&self, unit: &str) -> Result<UnitState> {
+        // Like LoadUnit, unknown units are reported as not found rather than failing.
+        Ok(self.units.lock().unwrap().get(unit).cloned().unwrap_or_else(|| UnitState {
+            load_state: String::from("not-found"),
+            active_state: String::from("inactive"),
+            unit_file_state: String::new(),
+        }))
+    }
+
+    fn start(&self, unit: &str) -> Result<()> {
+        self.update(unit, |s| s.active_state = String::from("active"))
+    }
+
+    fn stop(&self, unit: &str) -> Result<()> {
+        self.update(unit, |s| s.active_state = String::from("inactive"))
+    }
+
+    fn restart(&self, unit: &str) -> Result<()> {
+        self.start(unit)
+    }
+
+    fn reload(&self, unit: &str) -> Result<()> {
+        // Like systemd, only running units can be reloaded.
+        if !self.unit_state(unit)?.is_active() {
+            return Err(eyre!("Unit {unit} is not active"));
+        }
+        Ok(())
+    }
+
+    fn enable(&self, unit: &str) -> Result<()> {
+        self.update(unit, |s| s.unit_file_state = String::from("enabled"))
+    }
+
+    fn disable(&self, unit: &str) -> Result<()> {
+        self.update(unit, |s| s.unit_file_state = String::from("disabled"))
+    }
+}
@@ -0,0 +1,215 @@
+//! Local user accounts, read from `/etc/passwd` and managed with `useradd`, `usermod`, `userdel`
+//! and `gpasswd`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use super::{privilege::Privilege, run_privileged, secret::Secret};
+
+/// Groups new users join so they can use the headers without root, where they exist.
+pub const DEFAULT_GROUPS: &[&str] = &["gpio", "i2c", "spi", "dialout", "pwm", "plugdev", "video", "audio"];
+/// Members may run any command through sudo.
+pub const SUDO_GROUP: &str = "sudo";
+/// Range of user ids Debian gives to people rather than services.
+const UID_RANGE: std::ops::RangeInclusive<u32> = 1000..=59999;
+const NAME_MAX: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    /// The first field of the GECOS comment.
+    pub full_name: String,
+    pub home: PathBuf,
+    pub shell: String,
+    /// Supplementary groups, from `/etc/group`.
+    pub groups: Vec<String>,
+    /// Whether password logins are disabled, unknown when `/etc/shadow` cannot be read.
+    pub locked: Option<bool>,
+}
+
+impl User {
+    pub fn is_sudoer(&self) -> bool {
+        self.groups.iter().any(|g| g == SUDO_GROUP)
+    }
+}
+
+/// The people with accounts on the system below `root`, sorted by user id.
+pub fn users(root: &Path) -> Result<Vec<User>> {
+    let passwd = fs::read_to_string(root.join("etc/passwd"))?;
+    let group = fs::read_to_string(root.join("etc/group")).unwrap_or_default();
+    // Only root can read the shadow file.
+    let shadow = fs::read_to_string(root.join("etc/shadow")).ok();
+
+    let mut users: Vec<User> = passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .filter(|fields| fields.len() >= 7)
+        .filter_map(|fields| {
+            let uid = fields[2].parse().ok().filter(|uid| UID_RANGE.contains(uid))?;
+            if fields[6].ends_with("nologin") || fields[6].ends_with("false") {
+                return None;
+            }
+            let name = fields[0].to_string();
+            Some(User {
+                groups: member_of(&group, &name),
+                locked: shadow.as_deref().map(|shadow| is_locked(shadow, &name)),
+                uid,
+                full_name: fields[4].split(',').next().unwrap_or_default().to_string(),
+                home: PathBuf::from(fields[5]),
+                shell: fields[6].to_string(),
+                name,
+            })
+        })
+        .collect();
+    users.sort_by_key(|user| user.uid);
+    Ok(users)
+}
+
+/// Groups listing `user` as a member, given the content of `/etc/group`.
+fn member_of(group: &str, user: &str) -> Vec<String> {
+    group
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .filter(|fields| fields.len() >= 4 && fields[3].split(',').any(|member| member == user))
+        .map(|fields| fields[0].to_string())
+        .collect()
+}
+
+/// A `!` in front of the hash turns password logins off without losing the password.
+fn is_locked(shadow: &str, user: &str) -> bool {
+    shadow
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| *name == user)
+        .is_some_and(|(_, rest)| rest.starts_with('!'))
+}
+
+/// The groups of [`DEFAULT_GROUPS`] that exist below `root`, plus `sudo` when asked for.
+pub fn default_groups(root: &Path, sudo: bool) -> Vec<String> {
+    let group = fs::read_to_string(root.join("etc/group")).unwrap_or_default();
+    let existing: Vec<&str> = group.lines().filter_map(|line| line.split(':').next()).collect();
+    DEFAULT_GROUPS
+        .iter()
+        .chain(sudo.then_some(&SUDO_GROUP))
+        .filter(|g| existing.contains(g))
+        .map(|g| g.to_string())
+        .collect()
+}
+
+/// Check `name` is a user name `useradd` accepts without `--badname`.
+pub fn validate(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        && name.len() <= NAME_MAX;
+    if valid {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "Invalid user name `{name}`, use up to {NAME_MAX} lowercase letters, digits, `_` and `-`"
+        ))
+    }
+}
+
+/// Create `name` with a home directory, a login shell and `groups`. The account has no
+/// password until one is set.
+pub fn create(name: &str, groups: &[String], privilege: &dyn Privilege) -> Result<()> {
+    validate(name)?;
+    let groups = groups.join(",");
+    let mut args = vec!["--create-home", "--shell", "/bin/bash"];
+    if !groups.is_empty() {
+        args.extend(["--groups", &groups]);
+    }
+    args.extend(["--", name]);
+    run(privilege, "useradd", &args)
+}
+
+/// Delete `name` together with its home directory and mail spool.
+pub fn delete(name: &str, privilege: &dyn Privilege) -> Result<()> {
+    run(privilege, "userdel", &["--remove", "--", name])
+}
+
+/// Turn password logins of `name` off or back on. SSH keys keep working.
+pub fn set_locked(name: &str, locked: bool, privilege: &dyn Privilege) -> Result<()> {
+    run(privilege, "usermod", &[if locked { "--lock" } else { "--unlock" }, "--", name])
+}
+
+/// Add `name` to or remove it from the sudo group.
+pub fn set_sudo(name: &str, sudo: bool, privilege: &dyn Privilege) -> Result<()> {
+    run(privilege, "gpasswd", &[if sudo { "--add" } else { "--delete" }, name, SUDO_GROUP])
+}
+
+fn run(privilege: &dyn Privilege, program: &str, args: &[&str]) -> Result<()> {
+    run_privileged(privilege, program, args, &Secret::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::system::privilege::mock::Recorder;
+
+    fn root() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/passwd"),
+            "root:x:0:0:root:/root:/bin/bash\n\
+             debian:x:1000:1000:Debian,,,:/home/debian:/bin/bash\n\
+             alice:x:1001:1001:Alice Liddell:/home/alice:/bin/bash\n\
+             service:x:1002:1002::/var/lib/service:/usr/sbin/nologin\n\
+             nobody:x:65534:65534:nobody:/nonexistent:/usr/sbin/nologin\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("etc/group"),
+            "sudo:x:27:debian\ndialout:x:20:debian,alice\ngpio:x:997:debian\ndebian:x:1000:\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("etc/shadow"), "debian:$y$j9T$abc:19000:0:99999:7:::\nalice:!$y$j9T$def:19000::::::\n")
+            .unwrap();
+        root
+    }
+
+    #[test]
+    fn test_users() {
+        let root = root();
+        let users = users(root.path()).unwrap();
+        assert_eq!(users.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), ["debian", "alice"]);
+        assert_eq!(users[0].full_name, "Debian");
+        assert_eq!(users[0].groups, ["sudo", "dialout", "gpio"]);
+        assert!(users[0].is_sudoer());
+        assert_eq!(users[0].locked, Some(false));
+        assert_eq!(users[1].locked, Some(true));
+        assert!(!users[1].is_sudoer());
+
+        assert_eq!(default_groups(root.path(), false), ["gpio", "dialout"]);
+        assert_eq!(default_groups(root.path(), true), ["gpio", "dialout", "sudo"]);
+    }
+
+    #[test]
+    fn test_commands() {
+        let recorder = Recorder::default();
+        create("bob", &[String::from("gpio"), String::from("dialout")], &recorder).unwrap();
+        set_sudo("bob", true, &recorder).unwrap();
+        set_locked("bob", true, &recorder).unwrap();
+        delete("bob", &recorder).unwrap();
+        assert!(create("-rf", &[], &recorder).is_err());
+        assert!(create("Bob", &[], &recorder).is_err());
+        assert_eq!(
+            recorder.commands(),
+            [
+                "useradd --create-home --shell /bin/bash --groups gpio,dialout -- bob",
+                "gpasswd --add bob sudo",
+                "usermod --lock -- bob",
+                "userdel --remove -- bob",
+            ]
+        );
+    }
+}
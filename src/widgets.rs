@@ -1,5 +1,9 @@
 mod button;
+mod filter_list;
 mod switch;
+mod sudo_prompt;
 
 pub use button::{TextButtonWidget, ButtonState, ButtonWidget};
-pub use switch::*;
\ No newline at end of file
+pub use filter_list::FilterList;
+pub use switch::*;
+pub use sudo_prompt::SudoPrompt;
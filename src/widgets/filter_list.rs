@@ -0,0 +1,135 @@
+//! A list long enough to need searching, such as locales or timezones.
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Rect,
+    style::{palette::tailwind, Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, HighlightSpacing, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+const PAGE: u16 = 10;
+
+/// A list narrowed down to the entries containing a filter, ignoring case.
+///
+/// `/` starts a search, which Enter ends and Esc clears, so the keys of the view around it keep
+/// working.
+#[derive(Debug, Clone, Default)]
+pub struct FilterList {
+    items: Vec<String>,
+    filter: String,
+    searching: bool,
+    /// Indices into `items` of the entries matching the filter.
+    visible: Vec<usize>,
+    state: ListState,
+}
+
+impl FilterList {
+    pub fn new(items: Vec<String>) -> Self {
+        let mut list = FilterList { items, ..Default::default() };
+        list.refilter();
+        list
+    }
+
+    /// Replace the entries, keeping the selected one if it is still there.
+    pub fn set_items(&mut self, items: Vec<String>) {
+        let selected = self.selected().map(String::from);
+        self.items = items;
+        self.refilter();
+        if let Some(selected) = selected {
+            self.select(&selected);
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        let i = self.visible.get(self.state.selected()?)?;
+        Some(&self.items[*i])
+    }
+
+    /// Select `item`, clearing the filter if it hides it. Unknown items change nothing.
+    pub fn select(&mut self, item: &str) {
+        let Some(index) = self.items.iter().position(|i| i == item) else { return };
+        if !self.visible.contains(&index) {
+            self.filter.clear();
+            self.refilter();
+        }
+        self.state.select(self.visible.iter().position(|i| *i == index));
+    }
+
+    /// Whether typed characters currently go to the filter.
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    /// Move through the list or edit the filter. Returns whether the key was used, so the
+    /// caller can handle the others.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Up => self.state.select_previous(),
+            KeyCode::Down => self.state.select_next(),
+            KeyCode::PageUp => self.state.scroll_up_by(PAGE),
+            KeyCode::PageDown => self.state.scroll_down_by(PAGE),
+            KeyCode::Home => self.state.select_first(),
+            KeyCode::End => self.state.select_last(),
+            KeyCode::Char('/') if !self.is_searching() => self.searching = true,
+            KeyCode::Char(c) if self.is_searching() => {
+                self.filter.push(c);
+                self.refilter();
+            }
+            KeyCode::Backspace if !self.filter.is_empty() => {
+                self.filter.pop();
+                self.refilter();
+            }
+            KeyCode::Enter if self.searching => self.searching = false,
+            KeyCode::Esc if self.searching || !self.filter.is_empty() => {
+                self.searching = false;
+                self.filter.clear();
+                self.refilter();
+            }
+            _ => return false,
+        }
+        // ListState only clamps the selection when drawn, keep it valid for `selected` meanwhile.
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.min(self.visible.len().saturating_sub(1))));
+        }
+        true
+    }
+
+    pub fn handle_paste(&mut self, text: &str) {
+        if self.is_searching() {
+            self.filter.push_str(text.trim());
+            self.refilter();
+        }
+    }
+
+    fn refilter(&mut self) {
+        let selected = self.state.selected().and_then(|i| self.visible.get(i)).copied();
+        let filter = self.filter.to_lowercase();
+        self.visible = (0..self.items.len()).filter(|i| self.items[*i].to_lowercase().contains(&filter)).collect();
+        let position = selected.and_then(|selected| self.visible.iter().position(|i| *i == selected));
+        self.state.select(position.or((!self.visible.is_empty()).then_some(0)));
+    }
+
+    /// Draw the matching entries inside `block`, each rendered by `line`. The filter shows at
+    /// the bottom of the block.
+    pub fn draw<'a>(&'a mut self, f: &mut Frame<'_>, area: Rect, block: Block<'a>, line: impl Fn(&'a str) -> Line<'a>) {
+        let block = if self.is_searching() || !self.filter.is_empty() {
+            let cursor = if self.is_searching() { "█" } else { "" };
+            block.title_bottom(Line::from(format!(" /{}{cursor} ", self.filter)).fg(tailwind::BLUE.c400))
+        } else {
+            block
+        };
+        if self.visible.is_empty() {
+            f.render_widget(Paragraph::new("No matches").fg(tailwind::SLATE.c400).block(block), area);
+            return;
+        }
+        let items: Vec<ListItem> = self.visible.iter().map(|i| ListItem::new(line(&self.items[*i]))).collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::new().bg(tailwind::SLATE.c700).add_modifier(Modifier::BOLD))
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        f.render_stateful_widget(list, area, &mut self.state);
+    }
+}
@@ -0,0 +1,73 @@
+//! Asks for the user's password, so the interface can make changes through sudo.
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{palette::tailwind, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, Padding, Paragraph},
+    Frame,
+};
+
+use crate::system::{privilege::SudoSession, secret::Secret};
+
+/// A modal for the password sudo asks for, which is checked and kept in a [`SudoSession`].
+pub struct SudoPrompt {
+    session: SudoSession,
+    password: Secret,
+    error: Option<String>,
+}
+
+impl SudoPrompt {
+    pub fn new(session: SudoSession) -> Self {
+        SudoPrompt { session, password: Secret::default(), error: None }
+    }
+
+    /// Edit or submit the password. Returns whether the prompt is done with, because the
+    /// password was accepted or the prompt dismissed.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char(c) => self.password.push(c),
+            KeyCode::Backspace => {
+                self.password.pop();
+            }
+            KeyCode::Enter => match self.session.authenticate(std::mem::take(&mut self.password)) {
+                Ok(()) => return true,
+                Err(e) => self.error = Some(e.to_string()),
+            },
+            KeyCode::Esc => return true,
+            _ => {}
+        }
+        false
+    }
+
+    pub fn handle_paste(&mut self, text: &str) {
+        text.trim_end_matches(['\r', '\n']).chars().for_each(|c| self.password.push(c));
+    }
+
+    /// Draw the prompt over the middle of `area`.
+    pub fn draw(&self, f: &mut Frame<'_>, area: Rect) {
+        let [_, area, _] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(6), Constraint::Fill(1)]).areas(area);
+        let [_, area, _] = Layout::horizontal([Constraint::Fill(1), Constraint::Max(60), Constraint::Fill(1)]).areas(area);
+
+        let lines = vec![
+            Line::from("Changing system settings needs your password for sudo.").fg(tailwind::SLATE.c400),
+            Line::from(vec![
+                Span::raw("Password  ").bold(),
+                Span::raw("*".repeat(self.password.len())),
+                Span::raw("█"),
+            ]),
+            match &self.error {
+                Some(error) => Line::from(error.as_str()).fg(tailwind::RED.c400),
+                None => Line::default(),
+            },
+        ];
+        let block = Block::bordered()
+            .title(" Authentication ")
+            .title_bottom(Line::from(" Enter: confirm  Esc: continue without ").right_aligned())
+            .padding(Padding::horizontal(1));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}